@@ -0,0 +1,375 @@
+use crate::ast::*;
+use crate::eval::{arithmetic, eval, is_aggregate, resolve_column, Scope, ScopeColumn};
+use crate::part1::DbError;
+use crate::value::{values_key, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+// 表达式中是否含有聚合函数
+pub fn contains_aggregate(expr: &Expr) -> bool {
+    let mut found = Vec::new();
+    collect_aggregates(expr, &mut found).is_err() || !found.is_empty()
+}
+
+// 收集表达式中出现的聚合调用，相同的调用只记录一次
+pub fn collect_aggregates(expr: &Expr, out: &mut Vec<Expr>) -> Result<(), DbError> {
+    match expr {
+        Expr::Function { name, args, .. } if is_aggregate(name) => {
+            let mut nested = Vec::new();
+            for arg in args {
+                collect_aggregates(arg, &mut nested)?;
+            }
+            if !nested.is_empty() {
+                return Err(DbError::InvalidQuery(format!("聚合函数 {} 中不能嵌套聚合函数", name)));
+            }
+            if !out.contains(expr) {
+                out.push(expr.clone());
+            }
+            Ok(())
+        }
+        Expr::Literal(_) | Expr::Column(_) | Expr::QualifiedColumn { .. } => Ok(()),
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => collect_aggregates(expr, out),
+        Expr::Binary { left, right, .. } => {
+            collect_aggregates(left, out)?;
+            collect_aggregates(right, out)
+        }
+        Expr::Function { args, .. } => args.iter().try_for_each(|arg| collect_aggregates(arg, out)),
+        Expr::InList { expr, list, .. } => {
+            collect_aggregates(expr, out)?;
+            list.iter().try_for_each(|item| collect_aggregates(item, out))
+        }
+        Expr::Between { expr, low, high, .. } => {
+            collect_aggregates(expr, out)?;
+            collect_aggregates(low, out)?;
+            collect_aggregates(high, out)
+        }
+        Expr::Like { expr, pattern, escape, .. } => {
+            collect_aggregates(expr, out)?;
+            collect_aggregates(pattern, out)?;
+            escape.iter().try_for_each(|escape| collect_aggregates(escape, out))
+        }
+        // 子查询中的聚合属于子查询本身
+        Expr::Subquery(_) | Expr::Exists(_) => Ok(()),
+        Expr::InSubquery { expr, .. } => collect_aggregates(expr, out),
+    }
+}
+
+// 分组后的作用域：先是各分组键，再是各聚合结果；
+// 最后为列形式的分组键保留原来的列名，供相关子查询引用外层的分组列
+pub fn grouped_scope(group_by: &[Expr], columns: &[ScopeColumn], aggregate_count: usize) -> Vec<ScopeColumn> {
+    let mut scope: Vec<ScopeColumn> = (0..group_by.len())
+        .map(|i| format!("#group{}", i))
+        .chain((0..aggregate_count).map(|i| format!("#agg{}", i)))
+        .map(|name| ScopeColumn {
+            table: None,
+            name,
+            data_type: None,
+            qualified_only: false,
+        })
+        .collect();
+    scope.extend(
+        group_by
+            .iter()
+            .filter_map(|expr| column_position(expr, columns))
+            .map(|i| columns[i].clone()),
+    );
+    scope
+}
+
+// 列引用在分组前作用域中的下标
+fn column_position(expr: &Expr, columns: &[ScopeColumn]) -> Option<usize> {
+    match expr {
+        Expr::Column(name) => resolve_column(columns, None, name).ok(),
+        Expr::QualifiedColumn { table, name } => resolve_column(columns, Some(table), name).ok(),
+        _ => None,
+    }
+}
+
+// 把分组后求值的表达式改写为引用分组键与聚合结果的列引用；
+// columns 为分组前的作用域，用于识别 name 与 t.name 指向同一列
+pub fn rewrite(expr: &Expr, group_by: &[Expr], aggregates: &[Expr], columns: &[ScopeColumn]) -> Result<Expr, DbError> {
+    let position = column_position(expr, columns);
+    let same_group = |g: &Expr| g == expr || (position.is_some() && column_position(g, columns) == position);
+    if let Some(i) = group_by.iter().position(same_group) {
+        return Ok(Expr::Column(format!("#group{}", i)));
+    }
+    if let Some(i) = aggregates.iter().position(|a| a == expr) {
+        return Ok(Expr::Column(format!("#agg{}", i)));
+    }
+    // 本层找不到的列是对外层查询的引用，在分组后照常求值
+    let outer_reference = |expr: &Expr| match expr {
+        Expr::Column(name) => matches!(resolve_column(columns, None, name), Err(DbError::ColumnNotFound(_))),
+        Expr::QualifiedColumn { table, name } => {
+            matches!(resolve_column(columns, Some(table), name), Err(DbError::ColumnNotFound(_)))
+        }
+        _ => false,
+    };
+    let go = |e: &Expr| rewrite(e, group_by, aggregates, columns).map(Box::new);
+    Ok(match expr {
+        Expr::Literal(_) | Expr::Subquery(_) | Expr::Exists(_) => expr.clone(),
+        Expr::Column(_) | Expr::QualifiedColumn { .. } if outer_reference(expr) => expr.clone(),
+        Expr::Column(_) | Expr::QualifiedColumn { .. } => {
+            return Err(DbError::InvalidQuery(format!(
+                "列 '{}' 必须出现在 GROUP BY 子句中或用于聚合函数",
+                expr
+            )))
+        }
+        Expr::Unary { op, expr } => Expr::Unary { op: *op, expr: go(expr)? },
+        Expr::Binary { left, op, right } => Expr::Binary {
+            left: go(left)?,
+            op: *op,
+            right: go(right)?,
+        },
+        Expr::Function { name, args, distinct } => Expr::Function {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| rewrite(arg, group_by, aggregates, columns))
+                .collect::<Result<_, _>>()?,
+            distinct: *distinct,
+        },
+        Expr::IsNull { expr, negated } => Expr::IsNull { expr: go(expr)?, negated: *negated },
+        Expr::InList { expr, list, negated } => Expr::InList {
+            expr: go(expr)?,
+            list: list
+                .iter()
+                .map(|item| rewrite(item, group_by, aggregates, columns))
+                .collect::<Result<_, _>>()?,
+            negated: *negated,
+        },
+        Expr::Between { expr, low, high, negated } => Expr::Between {
+            expr: go(expr)?,
+            low: go(low)?,
+            high: go(high)?,
+            negated: *negated,
+        },
+        Expr::Like { expr, pattern, escape, negated, case_insensitive } => Expr::Like {
+            expr: go(expr)?,
+            pattern: go(pattern)?,
+            escape: escape.as_deref().map(go).transpose()?,
+            negated: *negated,
+            case_insensitive: *case_insensitive,
+        },
+        Expr::InSubquery { expr, query, negated } => Expr::InSubquery {
+            expr: go(expr)?,
+            query: query.clone(),
+            negated: *negated,
+        },
+    })
+}
+
+// 按 GROUP BY 逐行分组并累计聚合值，只保留各组的中间结果，不保留各行，
+// 因此超出内存的表也可以逐行交给它。没有 GROUP BY 时所有行（即使为空）构成一组
+pub struct Groups<'a> {
+    group_by: &'a [Expr],
+    aggregates: &'a [Expr],
+    columns: &'a [ScopeColumn],
+    groups: Vec<(Vec<Value>, Vec<Accumulator>)>,
+    index: HashMap<Vec<(&'static str, String)>, usize>,
+}
+
+impl<'a> Groups<'a> {
+    // columns 为分组前的作用域
+    pub fn new(group_by: &'a [Expr], aggregates: &'a [Expr], columns: &'a [ScopeColumn]) -> Result<Self, DbError> {
+        let mut groups = Groups {
+            group_by,
+            aggregates,
+            columns,
+            groups: Vec::new(),
+            index: HashMap::new(),
+        };
+        if group_by.is_empty() {
+            let accumulators = groups.accumulators()?;
+            groups.groups.push((Vec::new(), accumulators));
+        }
+        Ok(groups)
+    }
+
+    // 把一行计入所在的组，env 提供外层作用域
+    pub fn add(&mut self, values: &[Value], env: &Scope) -> Result<(), DbError> {
+        let scope = Scope { columns: self.columns, values, ..*env };
+        let key = self
+            .group_by
+            .iter()
+            .map(|expr| eval(expr, &scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        let args = self
+            .aggregates
+            .iter()
+            .map(|agg| match agg {
+                Expr::Function { args, .. } => args.iter().map(|arg| eval(arg, &scope)).collect(),
+                _ => unreachable!("只收集聚合调用"),
+            })
+            .collect::<Result<Vec<Vec<Value>>, DbError>>()?;
+
+        let slot = if self.group_by.is_empty() {
+            0
+        } else {
+            let key_text = values_key(&key);
+            match self.index.get(&key_text) {
+                Some(&slot) => slot,
+                None => {
+                    let accumulators = self.accumulators()?;
+                    self.groups.push((key, accumulators));
+                    self.index.insert(key_text, self.groups.len() - 1);
+                    self.groups.len() - 1
+                }
+            }
+        };
+        for (accumulator, args) in self.groups[slot].1.iter_mut().zip(&args) {
+            accumulator.add(args)?;
+        }
+        Ok(())
+    }
+
+    // 每组输出一行，各列与 grouped_scope 对应
+    pub fn finish(self) -> Result<Vec<Vec<Value>>, DbError> {
+        let key_columns: Vec<usize> = self
+            .group_by
+            .iter()
+            .enumerate()
+            .filter(|(_, expr)| column_position(expr, self.columns).is_some())
+            .map(|(i, _)| i)
+            .collect();
+        self.groups
+            .into_iter()
+            .map(|(key, accumulators)| {
+                let aliases: Vec<Value> = key_columns.iter().map(|&i| key[i].clone()).collect();
+                let mut output = key;
+                for accumulator in accumulators {
+                    output.push(accumulator.finish()?);
+                }
+                output.extend(aliases);
+                Ok(output)
+            })
+            .collect()
+    }
+
+    fn accumulators(&self) -> Result<Vec<Accumulator>, DbError> {
+        self.aggregates.iter().map(Accumulator::new).collect()
+    }
+}
+
+// 一组中一个聚合函数的中间结果
+struct Accumulator {
+    name: String,
+    distinct: bool,
+    arity: usize,
+    seen: HashSet<Vec<(&'static str, String)>>, // DISTINCT 时已计入的值
+    count: usize,                               // 计入的值的个数，COUNT(*) 时为行数
+    value: Option<Value>,                       // SUM 和 AVG 的和，MIN 和 MAX 的当前值
+    parts: Vec<String>,                         // STRING_AGG 和 GROUP_CONCAT 的各个值
+    separator: Option<String>,                  // 分隔符，取第一行的值
+}
+
+impl Accumulator {
+    fn new(agg: &Expr) -> Result<Accumulator, DbError> {
+        let Expr::Function { name, args, distinct } = agg else {
+            unreachable!("只收集聚合调用");
+        };
+        let arity = args.len();
+        let arity_error = |expected: &str| {
+            Err(DbError::TypeMismatch(format!("聚合函数 {} 需要 {} 个参数，实际为 {}", name, expected, arity)))
+        };
+        match name.as_str() {
+            "COUNT" if arity > 1 => return arity_error("0 或 1"),
+            "STRING_AGG" if arity != 2 => return arity_error("2"),
+            "GROUP_CONCAT" if arity != 1 && arity != 2 => return arity_error("1 或 2"),
+            "SUM" | "AVG" | "MIN" | "MAX" if arity != 1 => return arity_error("1"),
+            _ => {}
+        }
+        Ok(Accumulator {
+            name: name.clone(),
+            distinct: *distinct,
+            arity,
+            seen: HashSet::new(),
+            count: 0,
+            value: None,
+            parts: Vec::new(),
+            separator: None,
+        })
+    }
+
+    // 计入一行的参数值，忽略 NULL，DISTINCT 时去重
+    fn add(&mut self, args: &[Value]) -> Result<(), DbError> {
+        let concat = matches!(self.name.as_str(), "STRING_AGG" | "GROUP_CONCAT");
+        if concat && self.separator.is_none() {
+            self.separator = Some(args.get(1).map(|sep| sep.to_string()).unwrap_or_else(|| ",".to_string()));
+        }
+        if self.arity == 0 {
+            self.count += 1;
+            return Ok(());
+        }
+        let v = &args[0];
+        if v.is_null() || (self.distinct && !self.seen.insert(values_key(std::slice::from_ref(v)))) {
+            return Ok(());
+        }
+        self.count += 1;
+        match self.name.as_str() {
+            "SUM" | "AVG" => {
+                self.value = Some(match self.value.take() {
+                    None if matches!(v, Value::Interval(_)) => v.clone(),
+                    None => arithmetic(&Value::Int(0), BinaryOp::Plus, v)?,
+                    Some(sum) => arithmetic(&sum, BinaryOp::Plus, v)?,
+                });
+            }
+            // 相等的值中 MIN 取第一个，MAX 取最后一个
+            "MIN" if self.value.as_ref().is_none_or(|min| v.sort_cmp(min) == Ordering::Less) => {
+                self.value = Some(v.clone());
+            }
+            "MAX" if self.value.as_ref().is_none_or(|max| v.sort_cmp(max) != Ordering::Less) => {
+                self.value = Some(v.clone());
+            }
+            _ if concat => self.parts.push(v.to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, DbError> {
+        match self.name.as_str() {
+            "COUNT" => Ok(Value::Int(self.count as i64)),
+            "SUM" | "AVG" => match self.value {
+                None => Ok(Value::Null),
+                // Decimal 和时间间隔的平均值仍为原来的类型，其余按浮点数计算
+                Some(sum @ (Value::Decimal(_) | Value::Interval(_))) if self.name == "AVG" => {
+                    arithmetic(&sum, BinaryOp::Divide, &Value::Int(self.count as i64))
+                }
+                Some(sum) if self.name == "AVG" => arithmetic(&sum, BinaryOp::Divide, &Value::Float(self.count as f64)),
+                Some(sum) => Ok(sum),
+            },
+            "MIN" | "MAX" => Ok(self.value.unwrap_or(Value::Null)),
+            // STRING_AGG(x, sep) / GROUP_CONCAT(x [, sep])
+            _ if self.parts.is_empty() => Ok(Value::Null),
+            _ => Ok(Value::Text(self.parts.join(self.separator.as_deref().unwrap_or(",")))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::part1::{Database, DbError};
+
+    fn rows(db: &mut Database, sql: &str) -> Vec<String> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("|")).collect()
+    }
+
+    #[test]
+    fn groups_filtered_by_having() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (g VARCHAR(5), x INT, tag VARCHAR(5))").unwrap();
+        db.execute("INSERT INTO t VALUES ('a', 1, 'p'), ('a', 3, 'p'), ('b', 5, 'q'), ('b', NULL, 'r'), ('c', NULL, NULL)").unwrap();
+        assert_eq!(
+            rows(&mut db, "SELECT g, COUNT(*), COUNT(x), COUNT(DISTINCT tag), SUM(x), AVG(x), MIN(x), MAX(x), STRING_AGG(tag, ',') FROM t GROUP BY g ORDER BY g"),
+            ["a|2|2|1|4|2|1|3|p,p", "b|2|1|2|5|5|5|5|q,r", "c|1|0|0|NULL|NULL|NULL|NULL|NULL"]
+        );
+        assert_eq!(rows(&mut db, "SELECT g FROM t GROUP BY g HAVING SUM(x) > 4"), ["b"]);
+        // 没有 GROUP BY 时整张表为一组，空表也返回一行
+        assert_eq!(rows(&mut db, "SELECT COUNT(*), SUM(x) FROM t WHERE x > 100"), ["0|NULL"]);
+        // 不在 GROUP BY 中的列不能直接选择
+        assert!(matches!(db.execute("SELECT g, x FROM t GROUP BY g"), Err(DbError::InvalidQuery(_))));
+        assert!(db.execute("SELECT g FROM t WHERE COUNT(*) > 1 GROUP BY g").is_err());
+    }
+}
@@ -0,0 +1,665 @@
+// SQL 语句的抽象语法树
+use crate::blob::to_hex;
+use crate::part1::{DataType, ForeignKey};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Query(Query),
+    Insert(Insert),
+    Update(Update),
+    Delete(Delete),
+    CreateTable(CreateTable),
+    DropTable(DropTable),
+    AlterTable(AlterTable),
+}
+
+// 查询：单个 SELECT，或对两个查询结果做集合运算
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Select(Box<Select>),
+    SetOperation(Box<SetOperation>),
+}
+
+// left UNION|INTERSECT|EXCEPT [ALL] right [ORDER BY ...] [LIMIT n] [OFFSET m]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetOperation {
+    pub left: Query,
+    pub op: SetOperator,
+    pub all: bool,
+    pub right: Query,
+    pub order_by: Vec<OrderByExpr>, // 只能引用输出列名或序号
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperator {
+    Union,
+    Intersect,
+    Except,
+}
+
+// SELECT [DISTINCT] items FROM table_ref [WHERE expr] [GROUP BY ...] [HAVING expr]
+//     [ORDER BY ...] [LIMIT n] [OFFSET m]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub distinct: bool,
+    pub projection: Vec<SelectItem>, // 为空表示旧语法 SELECT FROM table，等同于 *
+    pub from: TableRef,
+    pub selection: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderByExpr>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+// ORDER BY 中的一个排序键
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByExpr {
+    pub expr: Expr,
+    pub descending: bool,
+    pub nulls_first: Option<bool>, // 未指定时升序 NULL 在后，降序 NULL 在前
+}
+
+// 选择列表中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Wildcard,
+    QualifiedWildcard(String), // table.*
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+// FROM 子句中的数据来源
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableRef {
+    Table {
+        name: String,
+        alias: Option<String>,
+    },
+    Join {
+        left: Box<TableRef>,
+        right: Box<TableRef>,
+        kind: JoinKind,
+        constraint: JoinConstraint,
+    },
+    // 派生表 (query) [AS] alias
+    Subquery {
+        query: Box<Query>,
+        alias: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Cross,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinConstraint {
+    On(Expr),
+    Using(Vec<String>),
+    None, // CROSS JOIN 或逗号分隔的表
+}
+
+// INSERT INTO table [(col, ...)] VALUES (...), (...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    pub table: String,
+    pub columns: Option<Vec<String>>,
+    pub rows: Vec<Vec<Expr>>,
+}
+
+// UPDATE table SET col = expr, ... [WHERE expr]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub table: String,
+    pub assignments: Vec<(String, Expr)>,
+    pub selection: Option<Expr>,
+}
+
+// DELETE FROM table [WHERE expr]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub table: String,
+    pub selection: Option<Expr>,
+}
+
+// CREATE TABLE [IF NOT EXISTS] table (col type [PRIMARY KEY], ... [, PRIMARY KEY (col, ...)])
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: String,
+    pub if_not_exists: bool,
+    pub columns: Vec<ColumnDef>,
+    pub primary_key: Vec<String>, // 表级主键约束，可为复合主键
+    pub foreign_keys: Vec<ForeignKey>, // 表级 FOREIGN KEY (col, ...) REFERENCES ...
+}
+
+// 列定义：col type [PRIMARY KEY] [NOT NULL | NULL] [UNIQUE] [DEFAULT expr] [CHECK (expr)]
+//        [REFERENCES table [(col)] [ON DELETE action] [ON UPDATE action]]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: DataType,
+    pub primary_key: bool,
+    pub not_null: bool,
+    pub unique: bool,
+    pub default: Option<Expr>,
+    pub check: Option<Expr>,
+    pub references: Option<ForeignKey>, // 被引用的列为空时取被引用表的主键
+}
+
+// DROP TABLE [IF EXISTS] table
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropTable {
+    pub name: String,
+    pub if_exists: bool,
+}
+
+// ALTER TABLE table action
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlterTable {
+    pub name: String,
+    pub action: AlterAction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterAction {
+    // ADD [COLUMN] column_def
+    AddColumn(ColumnDef),
+    // DROP [COLUMN] col
+    DropColumn(String),
+    // RENAME [COLUMN] col TO new_name
+    RenameColumn { name: String, new_name: String },
+    // RENAME TO new_name
+    RenameTable(String),
+    // ALTER [COLUMN] col [SET DATA] TYPE type
+    AlterColumnType { name: String, data_type: DataType },
+}
+
+// 字面量
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+    JsonGet,     // ->，取 JSON 的成员或元素
+    JsonGetText, // ->>，同上但结果为 SQL 值
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+}
+
+// 表达式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    Column(String),
+    // table.column，table 为表名或别名
+    QualifiedColumn {
+        table: String,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    // 函数调用，如 UPPER(name)、COUNT(DISTINCT id)；COUNT(*) 表示为无参数的 COUNT
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        distinct: bool,
+    },
+    // expr IS [NOT] NULL
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    // expr [NOT] IN (a, b, ...)
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    // expr [NOT] BETWEEN low AND high
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    // expr [NOT] LIKE|ILIKE pattern [ESCAPE escape]
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+        negated: bool,
+        case_insensitive: bool,
+    },
+    // 标量子查询 (SELECT ...)，结果至多一行一列
+    Subquery(Box<Query>),
+    // expr [NOT] IN (SELECT ...)
+    InSubquery {
+        expr: Box<Expr>,
+        query: Box<Query>,
+        negated: bool,
+    },
+    // EXISTS (SELECT ...)
+    Exists(Box<Query>),
+}
+
+impl fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self {
+            SetOperator::Union => "UNION",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        };
+        write!(f, "{}", keyword)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::Concat => "||",
+            BinaryOp::JsonGet => "->",
+            BinaryOp::JsonGetText => "->>",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Null => write!(f, "NULL"),
+            Literal::Bool(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::String(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Literal::Blob(bytes) => write!(f, "X'{}'", to_hex(bytes)),
+        }
+    }
+}
+
+impl Expr {
+    // 依次访问表达式及其全部子表达式，不进入子查询
+    pub fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        f(self);
+        match self {
+            Expr::Literal(_) | Expr::Column(_) | Expr::QualifiedColumn { .. } | Expr::Subquery(_) | Expr::Exists(_) => {}
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::InSubquery { expr, .. } => expr.visit_mut(f),
+            Expr::Binary { left, right, .. } => {
+                left.visit_mut(f);
+                right.visit_mut(f);
+            }
+            Expr::Function { args, .. } => args.iter_mut().for_each(|arg| arg.visit_mut(f)),
+            Expr::InList { expr, list, .. } => {
+                expr.visit_mut(f);
+                list.iter_mut().for_each(|item| item.visit_mut(f));
+            }
+            Expr::Between { expr, low, high, .. } => {
+                expr.visit_mut(f);
+                low.visit_mut(f);
+                high.visit_mut(f);
+            }
+            Expr::Like { expr, pattern, escape, .. } => {
+                expr.visit_mut(f);
+                pattern.visit_mut(f);
+                if let Some(escape) = escape {
+                    escape.visit_mut(f);
+                }
+            }
+        }
+    }
+
+    // 与 visit_mut 相同，只读访问
+    pub fn visit<'a>(&'a self, f: &mut dyn FnMut(&'a Expr)) {
+        f(self);
+        match self {
+            Expr::Literal(_) | Expr::Column(_) | Expr::QualifiedColumn { .. } | Expr::Subquery(_) | Expr::Exists(_) => {}
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::InSubquery { expr, .. } => expr.visit(f),
+            Expr::Binary { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::Function { args, .. } => args.iter().for_each(|arg| arg.visit(f)),
+            Expr::InList { expr, list, .. } => {
+                expr.visit(f);
+                list.iter().for_each(|item| item.visit(f));
+            }
+            Expr::Between { expr, low, high, .. } => {
+                expr.visit(f);
+                low.visit(f);
+                high.visit(f);
+            }
+            Expr::Like { expr, pattern, escape, .. } => {
+                expr.visit(f);
+                pattern.visit(f);
+                if let Some(escape) = escape {
+                    escape.visit(f);
+                }
+            }
+        }
+    }
+
+    // 运算优先级，数值越大结合越紧
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary { op: BinaryOp::Or, .. } => 1,
+            Expr::Binary { op: BinaryOp::And, .. } => 2,
+            Expr::Unary { op: UnaryOp::Not, .. } => 3,
+            Expr::Binary { op: BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Concat, .. } => 5,
+            Expr::Binary { op: BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo, .. } => 6,
+            Expr::Binary { op: BinaryOp::JsonGet | BinaryOp::JsonGetText, .. } => 7,
+            Expr::Binary { .. }
+            | Expr::IsNull { .. }
+            | Expr::InList { .. }
+            | Expr::InSubquery { .. }
+            | Expr::Between { .. }
+            | Expr::Like { .. } => 4,
+            Expr::Unary { op: UnaryOp::Minus, .. } => 8,
+            Expr::Literal(_)
+            | Expr::Column(_)
+            | Expr::QualifiedColumn { .. }
+            | Expr::Function { .. }
+            | Expr::Subquery(_)
+            | Expr::Exists(_) => 9,
+        }
+    }
+}
+
+// 将表达式还原为 SQL 文本，用作结果列名
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prec = self.precedence();
+        // 左结合：左操作数优先级不低于当前即可省略括号，右操作数需严格更高
+        let left = |expr| Operand(expr, prec);
+        let right = |expr| Operand(expr, prec + 1);
+        let not = |negated: &bool| if *negated { "NOT " } else { "" };
+        match self {
+            Expr::Literal(literal) => write!(f, "{}", literal),
+            Expr::Column(name) => write!(f, "{}", name),
+            Expr::QualifiedColumn { table, name } => write!(f, "{}.{}", table, name),
+            Expr::Unary { op: UnaryOp::Not, expr } => write!(f, "NOT {}", left(expr)),
+            Expr::Unary { op: UnaryOp::Minus, expr } => write!(f, "-{}", left(expr)),
+            Expr::Binary { left: l, op, right: r } => write!(f, "{} {} {}", left(l), op, right(r)),
+            // 带类型的字面量、INTERVAL n unit 和 EXTRACT 按原来的写法输出
+            Expr::Function { name, args, distinct: false } if is_special_syntax(name, args) => match args.as_slice() {
+                [literal] => write!(f, "{} {}", name, literal),
+                [Expr::Literal(Literal::String(field)), source] if name == "EXTRACT" => {
+                    write!(f, "EXTRACT({} FROM {})", field, source)
+                }
+                [amount, Expr::Literal(Literal::String(unit))] => write!(f, "INTERVAL {} {}", Operand(amount, 9), unit),
+                _ => write!(f, "{}", name),
+            },
+            Expr::Function { name, args, distinct } => {
+                write!(f, "{}(", name)?;
+                if *distinct {
+                    write!(f, "DISTINCT ")?;
+                }
+                if name == "COUNT" && args.is_empty() {
+                    write!(f, "*")?;
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::IsNull { expr, negated } => write!(f, "{} IS {}NULL", right(expr), not(negated)),
+            Expr::InList { expr, list, negated } => {
+                write!(f, "{} {}IN (", right(expr), not(negated))?;
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Between { expr, low, high, negated } => write!(
+                f,
+                "{} {}BETWEEN {} AND {}",
+                right(expr),
+                not(negated),
+                right(low),
+                right(high)
+            ),
+            Expr::Like { expr, pattern, escape, negated, case_insensitive } => {
+                write!(
+                    f,
+                    "{} {}{} {}",
+                    right(expr),
+                    not(negated),
+                    if *case_insensitive { "ILIKE" } else { "LIKE" },
+                    right(pattern)
+                )?;
+                match escape {
+                    Some(escape) => write!(f, " ESCAPE {}", right(escape)),
+                    None => Ok(()),
+                }
+            }
+            Expr::Subquery(query) => write!(f, "({})", query),
+            Expr::InSubquery { expr, query, negated } => write!(f, "{} {}IN ({})", right(expr), not(negated), query),
+            Expr::Exists(query) => write!(f, "EXISTS ({})", query),
+        }
+    }
+}
+
+// 是否为带类型的字面量 DATE '...'、INTERVAL n unit、EXTRACT(field FROM x) 或不带括号的 CURRENT_DATE 等，
+// 它们解析为函数调用，输出时还原为原来的写法
+fn is_special_syntax(name: &str, args: &[Expr]) -> bool {
+    match (name, args) {
+        ("DATE" | "TIME" | "TIMESTAMP" | "TIMESTAMPTZ" | "INTERVAL", [Expr::Literal(Literal::String(_))]) => true,
+        ("INTERVAL", [_, Expr::Literal(Literal::String(_))]) => true,
+        ("EXTRACT", [Expr::Literal(Literal::String(field)), _]) => field.chars().all(|c| c.is_ascii_alphabetic()),
+        ("CURRENT_DATE" | "CURRENT_TIME" | "CURRENT_TIMESTAMP", []) => true,
+        _ => false,
+    }
+}
+
+// 作为子表达式输出，优先级低于 min 时加括号
+struct Operand<'a>(&'a Expr, u8);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.precedence() < self.1 {
+            write!(f, "({})", self.0)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+// 将查询还原为 SQL 文本，用于子查询的结果列名
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Select(select) => write!(f, "{}", select),
+            Query::SetOperation(set) => {
+                // 左结合且 INTERSECT 优先：右操作数为集合运算时总要加括号
+                let operand = |f: &mut fmt::Formatter<'_>, query: &Query, is_right: bool| {
+                    let parens = query.has_order_limit()
+                        || match query {
+                            Query::SetOperation(inner) => {
+                                is_right || (set.op == SetOperator::Intersect && inner.op != SetOperator::Intersect)
+                            }
+                            Query::Select(_) => false,
+                        };
+                    if parens {
+                        write!(f, "({})", query)
+                    } else {
+                        write!(f, "{}", query)
+                    }
+                };
+                operand(f, &set.left, false)?;
+                write!(f, " {}{} ", set.op, if set.all { " ALL" } else { "" })?;
+                operand(f, &set.right, true)?;
+                write_order_limit(f, &set.order_by, set.limit, set.offset)
+            }
+        }
+    }
+}
+
+impl Query {
+    fn has_order_limit(&self) -> bool {
+        let (order_by, limit, offset) = match self {
+            Query::Select(select) => (&select.order_by, select.limit, select.offset),
+            Query::SetOperation(set) => (&set.order_by, set.limit, set.offset),
+        };
+        !order_by.is_empty() || limit.is_some() || offset.is_some()
+    }
+}
+
+impl fmt::Display for Select {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SELECT ")?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+        if self.projection.is_empty() {
+            write!(f, "*")?;
+        }
+        write_list(f, &self.projection)?;
+        write!(f, " FROM {}", self.from)?;
+        if let Some(selection) = &self.selection {
+            write!(f, " WHERE {}", selection)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY ")?;
+            write_list(f, &self.group_by)?;
+        }
+        if let Some(having) = &self.having {
+            write!(f, " HAVING {}", having)?;
+        }
+        write_order_limit(f, &self.order_by, self.limit, self.offset)
+    }
+}
+
+impl fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::QualifiedWildcard(table) => write!(f, "{}.*", table),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+            SelectItem::Expr { expr, alias: Some(alias) } => write!(f, "{} AS {}", expr, alias),
+        }
+    }
+}
+
+impl fmt::Display for TableRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableRef::Table { name, alias: None } => write!(f, "{}", name),
+            TableRef::Table { name, alias: Some(alias) } => write!(f, "{} AS {}", name, alias),
+            TableRef::Subquery { query, alias } => write!(f, "({}) AS {}", query, alias),
+            TableRef::Join { left, right, kind, constraint } => {
+                let keyword = match kind {
+                    JoinKind::Inner => "JOIN",
+                    JoinKind::Left => "LEFT JOIN",
+                    JoinKind::Right => "RIGHT JOIN",
+                    JoinKind::Full => "FULL JOIN",
+                    JoinKind::Cross => "CROSS JOIN",
+                };
+                write!(f, "{} {} {}", left, keyword, right)?;
+                match constraint {
+                    JoinConstraint::On(expr) => write!(f, " ON {}", expr),
+                    JoinConstraint::Using(columns) => write!(f, " USING ({})", columns.join(", ")),
+                    JoinConstraint::None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for OrderByExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        match self.nulls_first {
+            Some(true) => write!(f, " NULLS FIRST"),
+            Some(false) => write!(f, " NULLS LAST"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_order_limit(
+    f: &mut fmt::Formatter<'_>,
+    order_by: &[OrderByExpr],
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> fmt::Result {
+    if !order_by.is_empty() {
+        write!(f, " ORDER BY ")?;
+        write_list(f, order_by)?;
+    }
+    if let Some(limit) = limit {
+        write!(f, " LIMIT {}", limit)?;
+    }
+    if let Some(offset) = offset {
+        write!(f, " OFFSET {}", offset)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,372 @@
+// 紧凑的二进制数据文件格式：
+//   文件头    "RSDB" + 格式版本（u16）
+//   数据库    名称、format_version（u32）、wal_sequence（u64）、length_policy（u8）、表数
+//   每张表    表名、结构（列和外键的 JSON 文本）、行数，然后是各行的数据
+//   每行      值的个数，然后是各值：一个字节的类型标记加上按类型编码的内容（见 TAG_*）
+// 整数和浮点数按小端序定长保存，长度和个数按 LEB128 变长保存，文本为 UTF-8
+use crate::datetime::{Date, Interval, Time, Timestamp};
+use crate::decimal::Decimal;
+use crate::part1::{Column, Database, ForeignKey, LengthPolicy, Row, Table};
+use crate::value::Value;
+use std::io;
+
+const MAGIC: &[u8; 4] = b"RSDB";
+// 二进制格式的版本，与 FORMAT_VERSION（值的保存方式）无关
+pub const BINARY_VERSION: u16 = 1;
+
+// 各类型值的标记，写入文件后不能再修改
+const TAG_NULL: u8 = 0;
+const TAG_INT: u8 = 1;
+const TAG_FLOAT: u8 = 2;
+const TAG_BOOL: u8 = 3;
+const TAG_TEXT: u8 = 4;
+const TAG_DECIMAL: u8 = 5;
+const TAG_DATE: u8 = 6;
+const TAG_TIME: u8 = 7;
+const TAG_TIMESTAMP: u8 = 8;
+const TAG_TIMESTAMP_TZ: u8 = 9;
+const TAG_INTERVAL: u8 = 10;
+const TAG_BLOB: u8 = 11;
+const TAG_JSON: u8 = 12;
+
+pub fn encode(db: &Database) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+    put_str(&mut out, &db.name);
+    out.extend_from_slice(&db.format_version.to_le_bytes());
+    out.extend_from_slice(&db.wal_sequence.to_le_bytes());
+    out.push(match db.length_policy {
+        LengthPolicy::Reject => 0,
+        LengthPolicy::Truncate => 1,
+    });
+    put_len(&mut out, db.tables.len());
+    for table in &db.tables {
+        put_str(&mut out, &table.name);
+        let schema = serde_json::to_string(&(&table.columns, &table.foreign_keys)).map_err(io::Error::other)?;
+        put_str(&mut out, &schema);
+        put_len(&mut out, table.rows.len());
+        for row in &table.rows {
+            out.extend_from_slice(&encode_row(&row.values));
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<Database> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("不是二进制数据库文件".to_string()));
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version > BINARY_VERSION {
+        return Err(invalid(format!("文件格式版本 {} 高于当前支持的版本 {}", version, BINARY_VERSION)));
+    }
+    let mut db = Database::new(&reader.string()?);
+    db.format_version = u32::from_le_bytes(reader.array()?);
+    db.wal_sequence = u64::from_le_bytes(reader.array()?);
+    db.length_policy = match reader.byte()? {
+        0 => LengthPolicy::Reject,
+        1 => LengthPolicy::Truncate,
+        other => return Err(invalid(format!("未知的超长文本处理方式 {}", other))),
+    };
+    for _ in 0..reader.len()? {
+        let name = reader.string()?;
+        let (columns, foreign_keys): (Vec<Column>, Vec<ForeignKey>) =
+            serde_json::from_str(&reader.string()?).map_err(|e| invalid(format!("表 '{}' 的结构无效: {}", name, e)))?;
+        let row_count = reader.len()?;
+        let mut rows = Vec::with_capacity(row_count.min(bytes.len()));
+        for _ in 0..row_count {
+            let value_count = reader.len()?;
+            let values = (0..value_count).map(|_| reader.value()).collect::<io::Result<_>>()?;
+            rows.push(Row { values });
+        }
+        db.tables.push(Table { name, columns, rows, foreign_keys });
+    }
+    if reader.pos != bytes.len() {
+        return Err(invalid("文件末尾有多余的数据".to_string()));
+    }
+    Ok(db)
+}
+
+// 单独编码一行的值，分页存储中的行也使用这种格式
+pub fn encode_row(values: &[Value]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_len(&mut out, values.len());
+    for value in values {
+        put_value(&mut out, value);
+    }
+    out
+}
+
+pub fn decode_row(bytes: &[u8]) -> io::Result<Vec<Value>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let count = reader.len()?;
+    let values = (0..count).map(|_| reader.value()).collect::<io::Result<_>>()?;
+    if reader.pos != bytes.len() {
+        return Err(invalid("行末尾有多余的数据".to_string()));
+    }
+    Ok(values)
+}
+
+// 文件是否以二进制格式的文件头开始
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    let mut n = len as u64;
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_len(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+fn put_str(out: &mut Vec<u8>, text: &str) {
+    put_bytes(out, text.as_bytes());
+}
+
+fn put_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(TAG_NULL),
+        Value::Int(i) => {
+            out.push(TAG_INT);
+            out.extend_from_slice(&i.to_le_bytes());
+        }
+        Value::Float(x) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        Value::Bool(b) => out.extend_from_slice(&[TAG_BOOL, *b as u8]),
+        Value::Text(s) => {
+            out.push(TAG_TEXT);
+            put_str(out, s);
+        }
+        Value::Decimal(d) => {
+            out.push(TAG_DECIMAL);
+            out.extend_from_slice(&d.units().to_le_bytes());
+            out.push(d.scale() as u8); // 不超过 MAX_PRECISION
+        }
+        Value::Date(d) => {
+            out.push(TAG_DATE);
+            out.extend_from_slice(&(d.days() as i32).to_le_bytes());
+        }
+        Value::Time(t) => {
+            out.push(TAG_TIME);
+            out.extend_from_slice(&t.micros().to_le_bytes());
+        }
+        Value::Timestamp(ts) | Value::TimestampTz(ts) => {
+            out.push(if matches!(value, Value::Timestamp(_)) { TAG_TIMESTAMP } else { TAG_TIMESTAMP_TZ });
+            out.extend_from_slice(&ts.micros().to_le_bytes());
+        }
+        Value::Interval(i) => {
+            out.push(TAG_INTERVAL);
+            out.extend_from_slice(&i.months.to_le_bytes());
+            out.extend_from_slice(&i.days.to_le_bytes());
+            out.extend_from_slice(&i.micros.to_le_bytes());
+        }
+        Value::Blob(bytes) => {
+            out.push(TAG_BLOB);
+            put_bytes(out, bytes);
+        }
+        Value::Json(j) => {
+            out.push(TAG_JSON);
+            put_str(out, &j.to_string());
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("无效的二进制数据库文件: {}", message))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid(format!("文件在第 {} 字节处意外结束", self.pos)))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(n).map_err(|_| invalid(format!("长度 {} 过大", n)));
+            }
+        }
+        Err(invalid("长度的编码过长".to_string()))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn value(&mut self) -> io::Result<Value> {
+        let tag = self.byte()?;
+        let out_of_range = || invalid(format!("类型标记为 {} 的值超出范围", tag));
+        Ok(match tag {
+            TAG_NULL => Value::Null,
+            TAG_INT => Value::Int(i64::from_le_bytes(self.array()?)),
+            TAG_FLOAT => Value::Float(f64::from_le_bytes(self.array()?)),
+            TAG_BOOL => Value::Bool(self.byte()? != 0),
+            TAG_TEXT => Value::Text(self.string()?),
+            TAG_DECIMAL => {
+                let units = i128::from_le_bytes(self.array()?);
+                Value::Decimal(Decimal::new(units, self.byte()? as u32))
+            }
+            TAG_DATE => {
+                let days = i32::from_le_bytes(self.array()?) as i64;
+                Value::Date(Date::from_days(days).ok_or_else(out_of_range)?)
+            }
+            TAG_TIME => Value::Time(Time::from_micros(i64::from_le_bytes(self.array()?))),
+            TAG_TIMESTAMP | TAG_TIMESTAMP_TZ => {
+                let ts = Timestamp::from_micros(i64::from_le_bytes(self.array()?)).ok_or_else(out_of_range)?;
+                if tag == TAG_TIMESTAMP { Value::Timestamp(ts) } else { Value::TimestampTz(ts) }
+            }
+            TAG_INTERVAL => Value::Interval(Interval {
+                months: i32::from_le_bytes(self.array()?),
+                days: i32::from_le_bytes(self.array()?),
+                micros: i64::from_le_bytes(self.array()?),
+            }),
+            TAG_BLOB => Value::Blob(self.bytes()?.to_vec()),
+            TAG_JSON => Value::Json(serde_json::from_str(&self.string()?).map_err(|e| invalid(e.to_string()))?),
+            other => return Err(invalid(format!("未知的类型标记 {}", other))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1::DataType;
+
+    // 每种类型标记各一个值，包括边界值
+    fn sample_values() -> Vec<Value> {
+        vec![
+            Value::Null,
+            Value::Int(i64::MIN),
+            Value::Float(-1.5e300),
+            Value::Bool(true),
+            Value::Text("中文 text".to_string()),
+            Value::Decimal(Decimal::new(-123456789012345678901234567, 7)),
+            Value::Date(Date::from_days(-719162).unwrap()),
+            Value::Time(Time::from_micros(86_399_999_999)),
+            Value::Timestamp(Timestamp::from_micros(1_700_000_000_123_456).unwrap()),
+            Value::TimestampTz(Timestamp::from_micros(-1).unwrap()),
+            Value::Interval(Interval { months: -14, days: 3, micros: 1_000_001 }),
+            Value::Blob((0..=255).collect()),
+            Value::Json(serde_json::json!({"big": 18446744073709551615u64, "list": [1, "a", null]})),
+        ]
+    }
+
+    fn sample_database() -> Database {
+        let mut db = Database::new("样例");
+        db.wal_sequence = 42;
+        db.length_policy = LengthPolicy::Truncate;
+        let columns = vec![Column::new("id", DataType::Int), Column::new("v", DataType::String(10))];
+        let rows = (0..3).map(|i| Row { values: vec![Value::Int(i), Value::Text(format!("r{}", i))] }).collect();
+        db.tables.push(Table { name: "t".to_string(), columns, rows, foreign_keys: Vec::new() });
+        db
+    }
+
+    #[test]
+    fn every_value_tag_round_trips() {
+        let values = sample_values();
+        for (tag, value) in values.iter().enumerate() {
+            let encoded = encode_row(std::slice::from_ref(value));
+            assert_eq!(encoded[1], tag as u8);
+            assert_eq!(decode_row(&encoded).unwrap(), std::slice::from_ref(value));
+        }
+        let decoded = decode_row(&encode_row(&values)).unwrap();
+        assert_eq!(decoded, values);
+        // DECIMAL 的小数位数和浮点数的符号位也保持不变
+        let (Value::Decimal(d), Value::Decimal(original)) = (&decoded[5], &values[5]) else { unreachable!() };
+        assert_eq!((d.units(), d.scale()), (original.units(), original.scale()));
+        let zero = decode_row(&encode_row(&[Value::Float(-0.0)])).unwrap();
+        assert!(matches!(zero[0], Value::Float(x) if x.to_bits() == (-0.0f64).to_bits()));
+    }
+
+    #[test]
+    fn database_round_trips() {
+        let db = sample_database();
+        let bytes = encode(&db).unwrap();
+        assert!(is_binary(&bytes));
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&db).unwrap());
+        assert_eq!(decoded.wal_sequence, 42);
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let bytes = encode(&sample_database()).unwrap();
+        for len in 0..bytes.len() {
+            let err = decode(&bytes[..len]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "截断到 {} 字节", len);
+        }
+        let row = encode_row(&sample_values());
+        for len in 0..row.len() {
+            assert!(decode_row(&row[..len]).is_err(), "截断到 {} 字节", len);
+        }
+    }
+
+    #[test]
+    fn corrupt_input_is_rejected() {
+        let bytes = encode(&sample_database()).unwrap();
+        let corrupt = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = bytes.clone();
+            f(&mut bytes);
+            decode(&bytes).err().unwrap().to_string()
+        };
+        assert!(corrupt(&|b| b[0] = b'X').contains("不是二进制数据库文件"));
+        assert!(corrupt(&|b| b[4] = 99).contains("高于当前支持的版本"));
+        assert!(corrupt(&|b| b.push(0)).contains("多余的数据"));
+        // 名称的第一个字节改为无效的 UTF-8
+        assert!(corrupt(&|b| b[7] = 0xFF).contains("无效的二进制数据库文件"));
+
+        assert!(decode_row(&[1, 200]).unwrap_err().to_string().contains("未知的类型标记 200"));
+        assert!(decode_row(&[1, TAG_BOOL, 1, 0]).unwrap_err().to_string().contains("多余的数据"));
+        // 超出范围的日期和过长的长度编码
+        let mut date = vec![1, TAG_DATE];
+        date.extend_from_slice(&i32::MAX.to_le_bytes());
+        assert!(decode_row(&date).unwrap_err().to_string().contains("超出范围"));
+        assert!(decode_row(&[0xFF; 11]).unwrap_err().to_string().contains("长度的编码过长"));
+        let mut json = vec![1, TAG_JSON, 3];
+        json.extend_from_slice(b"{x}");
+        assert!(decode_row(&json).is_err());
+    }
+}
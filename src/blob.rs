@@ -0,0 +1,105 @@
+use serde::Serializer;
+
+// BLOB 列未指定大小时的上限（字节）
+pub const DEFAULT_MAX_SIZE: u32 = 65_535;
+// GUI 中预览的字节数
+pub const PREVIEW_BYTES: usize = 16;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// 大写十六进制，不带分隔符
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// 解析十六进制文本，忽略空白，大小写不敏感；位数必须为偶数
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+// 标准 base64 编码（RFC 4648），带 = 填充
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// 解码标准 base64，忽略空白，可以省略末尾的 = 填充
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let unpadded = text.strip_suffix(b"==").or_else(|| text.strip_suffix(b"=")).unwrap_or(&text);
+    if text.len() != unpadded.len() && !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let sextets: Vec<u32> = unpadded
+        .iter()
+        .map(|&c| BASE64_ALPHABET.iter().position(|&a| a == c).map(|p| p as u32))
+        .collect::<Option<_>>()?;
+    if sextets.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(sextets.len() * 3 / 4);
+    for chunk in sextets.chunks(4) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &s)| n | s << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+// 用于显示的摘要：大小与开头若干字节的十六进制，如 "3 字节: 01 02 FF"
+pub fn preview(bytes: &[u8]) -> String {
+    let shown: Vec<String> = bytes.iter().take(PREVIEW_BYTES).map(|b| format!("{:02X}", b)).collect();
+    let more = if bytes.len() > PREVIEW_BYTES { " …" } else { "" };
+    format!("{} 字节: {}{}", bytes.len(), shown.join(" "), more)
+}
+
+// 数据文件中保存为 base64 字符串，读入时按列的类型还原
+pub fn serialize_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode_base64(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1::{Database, DbError};
+
+    #[test]
+    fn hex_and_base64_round_trip() {
+        assert_eq!(parse_hex("DEADbe"), Some(vec![0xde, 0xad, 0xbe]));
+        assert_eq!(parse_hex("ABC"), None);
+        assert_eq!(to_hex(&[0, 255]), "00FF");
+        for bytes in [&b""[..], b"h", b"hi", b"hi!", b"\x00\xff\x10\x80"] {
+            assert_eq!(decode_base64(&encode_base64(bytes)).as_deref(), Some(bytes));
+        }
+        assert_eq!(decode_base64("a*Gk"), None);
+
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE b (x BLOB(3))").unwrap();
+        db.execute("INSERT INTO b VALUES (X'DEADBE')").unwrap();
+        assert!(matches!(db.execute("INSERT INTO b VALUES (X'00010203')"), Err(DbError::TypeMismatch(_))));
+        let result = db.execute("SELECT HEX(x), TO_BASE64(x), LENGTH(x), HEX(FROM_BASE64('aGk=')), HEX(x || X'00') FROM b").unwrap();
+        let values: Vec<String> = result.rows[0].values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(values, ["DEADBE", "3q2+", "3", "6869", "DEADBE00"]);
+        // 数据文件中保存为 base64
+        assert!(serde_json::to_string(&db).unwrap().contains(r#""3q2+""#));
+    }
+}
@@ -0,0 +1,257 @@
+// 分页文件中的 B+ 树索引，把 u64 的键映射到 u64 的值，同一个键可以对应多个值。
+// 各项按（键，值）排序且互不相同。叶页的布局：类型（u8）、右侧的下一个叶页（u32）、项数（u16），
+// 随后是各项的键和值（各 u64）。内部页的布局：类型（u8）、最左侧的子页（u32）、分隔项数（u16），
+// 随后每项为分隔项的键和值（各 u64）及其右侧的子页（u32），右侧子页中的项都不小于分隔项。
+// 根页的页号始终不变：根页分裂时把原来的内容移到新页，根页改为指向两半的内部页，
+// 因此目录中记录的根页不必随插入更新。删除只从叶页中去掉项，不合并页
+use crate::pager::{invalid, read_u16, read_u32, write_u16, write_u32, PageId, Pager, PAGE_INDEX_INNER, PAGE_INDEX_LEAF, PAGE_SIZE};
+use std::io;
+
+const HEADER: usize = 7;
+const LEAF_ENTRY: usize = 16;
+const INNER_ENTRY: usize = 20;
+const LEAF_CAPACITY: usize = (PAGE_SIZE - HEADER) / LEAF_ENTRY;
+const INNER_CAPACITY: usize = (PAGE_SIZE - HEADER) / INNER_ENTRY;
+
+// 索引中的一项：键和值
+pub type Entry = (u64, u64);
+
+enum Node {
+    Leaf { next: PageId, entries: Vec<Entry> },
+    // children 比 separators 多一个
+    Inner { children: Vec<PageId>, separators: Vec<Entry> },
+}
+
+// 创建空索引，返回根页
+pub fn create(pager: &mut Pager) -> io::Result<PageId> {
+    pager.allocate(PAGE_INDEX_LEAF)
+}
+
+pub fn insert(pager: &mut Pager, root: PageId, entry: Entry) -> io::Result<()> {
+    let Some((separator, right)) = insert_into(pager, root, entry)? else {
+        return Ok(());
+    };
+    // 根页分裂：左半部分移到新页，根页改为内部页
+    let left = pager.allocate(PAGE_INDEX_LEAF)?;
+    let data = pager.page(root)?.to_vec();
+    pager.page_mut(left)?.copy_from_slice(&data);
+    store(pager, root, &Node::Inner { children: vec![left, right], separators: vec![separator] })
+}
+
+// 删除一项，返回该项是否存在
+pub fn remove(pager: &mut Pager, root: PageId, entry: Entry) -> io::Result<bool> {
+    let leaf = find_leaf(pager, root, entry)?;
+    let Node::Leaf { next, mut entries } = load(pager, leaf)? else {
+        unreachable!("find_leaf 返回叶页");
+    };
+    match entries.binary_search(&entry) {
+        Ok(i) => {
+            entries.remove(i);
+            store(pager, leaf, &Node::Leaf { next, entries })?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+// 键对应的全部值，按值排序
+pub fn find(pager: &mut Pager, root: PageId, key: u64) -> io::Result<Vec<u64>> {
+    let mut values = Vec::new();
+    let mut page = find_leaf(pager, root, (key, 0))?;
+    // 同一个键的项可能跨越多个叶页，叶页也可能因删除而为空
+    let page_count = pager.page_count() as usize;
+    for _ in 0..page_count {
+        let Node::Leaf { next, entries } = load(pager, page)? else {
+            return Err(invalid(format!("第 {} 页不是索引的叶页", page)));
+        };
+        let start = entries.partition_point(|e| e.0 < key);
+        let found = entries[start..].iter().take_while(|e| e.0 == key).map(|e| e.1);
+        values.extend(found);
+        if entries.last().is_some_and(|e| e.0 > key) || next == 0 {
+            return Ok(values);
+        }
+        page = next;
+    }
+    Err(invalid("索引的叶页链过长".to_string()))
+}
+
+// 释放索引的全部页
+pub fn free(pager: &mut Pager, root: PageId) -> io::Result<()> {
+    if let Node::Inner { children, .. } = load(pager, root)? {
+        for child in children {
+            free(pager, child)?;
+        }
+    }
+    pager.free(root)
+}
+
+// 插入到以 id 为根的子树，页分裂时返回右半部分的第一项和新页
+fn insert_into(pager: &mut Pager, id: PageId, entry: Entry) -> io::Result<Option<(Entry, PageId)>> {
+    match load(pager, id)? {
+        Node::Leaf { next, mut entries } => {
+            let Err(i) = entries.binary_search(&entry) else {
+                return Ok(None);
+            };
+            entries.insert(i, entry);
+            if entries.len() <= LEAF_CAPACITY {
+                store(pager, id, &Node::Leaf { next, entries })?;
+                return Ok(None);
+            }
+            let right_entries = entries.split_off(entries.len() / 2);
+            let separator = right_entries[0];
+            let right = pager.allocate(PAGE_INDEX_LEAF)?;
+            store(pager, right, &Node::Leaf { next, entries: right_entries })?;
+            store(pager, id, &Node::Leaf { next: right, entries })?;
+            Ok(Some((separator, right)))
+        }
+        Node::Inner { mut children, mut separators } => {
+            let i = separators.partition_point(|s| *s <= entry);
+            let Some((separator, right)) = insert_into(pager, children[i], entry)? else {
+                return Ok(None);
+            };
+            separators.insert(i, separator);
+            children.insert(i + 1, right);
+            if separators.len() <= INNER_CAPACITY {
+                store(pager, id, &Node::Inner { children, separators })?;
+                return Ok(None);
+            }
+            // 中间的分隔项移到上一层
+            let mid = separators.len() / 2;
+            let right_separators = separators.split_off(mid + 1);
+            let up = separators.pop().expect("分隔项不为空");
+            let right_children = children.split_off(mid + 1);
+            let right = pager.allocate(PAGE_INDEX_INNER)?;
+            store(pager, right, &Node::Inner { children: right_children, separators: right_separators })?;
+            store(pager, id, &Node::Inner { children, separators })?;
+            Ok(Some((up, right)))
+        }
+    }
+}
+
+// 可能含有 entry 的叶页；树的高度不超过文件的页数，以免损坏的文件造成死循环
+fn find_leaf(pager: &mut Pager, root: PageId, entry: Entry) -> io::Result<PageId> {
+    let mut id = root;
+    for _ in 0..pager.page_count() {
+        match load(pager, id)? {
+            Node::Leaf { .. } => return Ok(id),
+            Node::Inner { children, separators } => id = children[separators.partition_point(|s| *s <= entry)],
+        }
+    }
+    Err(invalid("索引的层数过多".to_string()))
+}
+
+fn load(pager: &mut Pager, id: PageId) -> io::Result<Node> {
+    let page = pager.page(id)?;
+    let count = read_u16(page, 5) as usize;
+    let corrupt = || invalid(format!("第 {} 页不是有效的索引页", id));
+    match page[0] {
+        PAGE_INDEX_LEAF if count <= LEAF_CAPACITY => {
+            let entries = (0..count)
+                .map(|i| {
+                    let at = HEADER + i * LEAF_ENTRY;
+                    (read_u64(page, at), read_u64(page, at + 8))
+                })
+                .collect();
+            Ok(Node::Leaf { next: read_u32(page, 1), entries })
+        }
+        PAGE_INDEX_INNER if count > 0 && count <= INNER_CAPACITY => {
+            let mut children = vec![read_u32(page, 1)];
+            let mut separators = Vec::with_capacity(count);
+            for i in 0..count {
+                let at = HEADER + i * INNER_ENTRY;
+                separators.push((read_u64(page, at), read_u64(page, at + 8)));
+                children.push(read_u32(page, at + 16));
+            }
+            Ok(Node::Inner { children, separators })
+        }
+        _ => Err(corrupt()),
+    }
+}
+
+fn store(pager: &mut Pager, id: PageId, node: &Node) -> io::Result<()> {
+    let page = pager.page_mut(id)?;
+    page.fill(0);
+    match node {
+        Node::Leaf { next, entries } => {
+            page[0] = PAGE_INDEX_LEAF;
+            write_u32(page, 1, *next);
+            write_u16(page, 5, entries.len() as u16);
+            for (i, &(key, value)) in entries.iter().enumerate() {
+                let at = HEADER + i * LEAF_ENTRY;
+                write_u64(page, at, key);
+                write_u64(page, at + 8, value);
+            }
+        }
+        Node::Inner { children, separators } => {
+            page[0] = PAGE_INDEX_INNER;
+            write_u32(page, 1, children[0]);
+            write_u16(page, 5, separators.len() as u16);
+            for (i, (&(key, value), &child)) in separators.iter().zip(&children[1..]).enumerate() {
+                let at = HEADER + i * INNER_ENTRY;
+                write_u64(page, at, key);
+                write_u64(page, at + 8, value);
+                write_u32(page, at + 16, child);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_u64(page: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(page[offset..offset + 8].try_into().expect("8 字节"))
+}
+
+fn write_u64(page: &mut [u8], offset: usize, value: u64) {
+    page[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pager::journal_path;
+    use std::fs;
+
+    #[test]
+    fn entries_survive_splits_and_removal() {
+        let path = std::env::temp_dir().join(format!("sql_homework_btree_{}.rspg", std::process::id()));
+        let mut pager = Pager::create(&path, 8).unwrap();
+        let root = create(&mut pager).unwrap();
+        // 每个键三个值，键按乱序插入，足以让根页分裂两次
+        let keys: Vec<u64> = (0..15_000u64).map(|i| i * 7919 % 15_000).collect();
+        for &key in &keys {
+            for value in 0..3 {
+                insert(&mut pager, root, (key, value)).unwrap();
+            }
+        }
+        let Node::Inner { children, .. } = load(&mut pager, root).unwrap() else { panic!("根页应已分裂") };
+        assert!(matches!(load(&mut pager, children[0]).unwrap(), Node::Inner { .. }));
+        assert_eq!(find(&mut pager, root, 12_345).unwrap(), [0, 1, 2]);
+        assert!(find(&mut pager, root, 15_000).unwrap().is_empty());
+
+        for &key in &keys {
+            if key % 2 == 0 {
+                assert!(remove(&mut pager, root, (key, 1)).unwrap());
+            } else {
+                for value in 0..3 {
+                    remove(&mut pager, root, (key, value)).unwrap();
+                }
+            }
+        }
+        assert!(!remove(&mut pager, root, (1, 0)).unwrap());
+        assert_eq!(find(&mut pager, root, 12_344).unwrap(), [0, 2]);
+        assert!(find(&mut pager, root, 12_345).unwrap().is_empty());
+        pager.flush().unwrap();
+
+        // 释放后的页全部回到空闲页链表，新建的索引复用它们
+        let pages = pager.page_count();
+        free(&mut pager, root).unwrap();
+        let root = create(&mut pager).unwrap();
+        for key in 0..15_000 {
+            insert(&mut pager, root, (key, 0)).unwrap();
+        }
+        assert_eq!(pager.page_count(), pages);
+        drop(pager);
+        fs::remove_file(&path).unwrap();
+        let _ = fs::remove_file(journal_path(&path));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::aggregate::contains_aggregate;
+use crate::ast::Expr;
+use crate::eval::{eval, resolve_columns, row_values, table_scope, truth, Scope};
+use crate::parser::parse_expression;
+use crate::part1::{Column, Constraint, DbError, LengthPolicy, Row, Table};
+use crate::value::{fit_length, values_key, Value};
+use std::collections::HashSet;
+
+impl Column {
+    // 计算列的默认值并转换为列类型，没有 DEFAULT 时为 NULL
+    pub fn default_value(&self) -> Result<Value, DbError> {
+        match &self.default {
+            Some(sql) => eval(&parse_expression(sql)?, &Scope::empty())?.cast(&self.data_type),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+impl Table {
+    // 主键列的下标，多列时构成复合主键
+    pub fn primary_key(&self) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_primary_key)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // 把写入的行转换为各列的类型，无法转换时报错；超过声明长度的文本按 policy 拒绝或截断，
+    // CHAR 以空格补足。返回被截断的值的个数
+    pub fn conform_row(&self, row: &mut Row, policy: LengthPolicy) -> Result<usize, DbError> {
+        row.values.resize(self.columns.len(), Value::Null);
+        let mut truncated = 0;
+        for (col, value) in self.columns.iter().zip(row.values.iter_mut()) {
+            *value = std::mem::replace(value, Value::Null)
+                .convert(&col.data_type)
+                .map_err(|e| match e {
+                    DbError::TypeMismatch(msg) => DbError::TypeMismatch(format!("列 '{}': {}", col.name, msg)),
+                    e => e,
+                })?;
+            let Value::Text(text) = value else {
+                continue;
+            };
+            let (fitted, lost) = fit_length(text, &col.data_type);
+            if lost {
+                if policy == LengthPolicy::Reject {
+                    let constraint = Constraint::Length(col.data_type.clone());
+                    return Err(self.violation(constraint, col.name.clone(), text.clone()));
+                }
+                truncated += 1;
+            }
+            *text = fitted;
+        }
+        Ok(truncated)
+    }
+
+    // 建表和添加列时检查 CHECK 条件：只能引用本表的列，不能使用聚合函数和子查询
+    pub(crate) fn validate_checks(&self) -> Result<(), DbError> {
+        let scope_columns = table_scope(self, &self.name);
+        for col in &self.columns {
+            let Some(sql) = &col.check else {
+                continue;
+            };
+            let check = parse_expression(sql)?;
+            let mut subquery = false;
+            check.visit(&mut |expr| {
+                subquery |= matches!(expr, Expr::Subquery(_) | Expr::Exists(_) | Expr::InSubquery { .. })
+            });
+            let forbidden = if contains_aggregate(&check) {
+                "聚合函数"
+            } else if subquery {
+                "子查询"
+            } else {
+                resolve_columns(&check, &scope_columns, None)?;
+                continue;
+            };
+            return Err(DbError::InvalidQuery(format!(
+                "列 '{}' 的 CHECK 约束中不能使用{}",
+                col.name, forbidden
+            )));
+        }
+        Ok(())
+    }
+
+    // 检查写入的行：NOT NULL（主键列隐含）、CHECK，以及主键和 UNIQUE 列不与其余行及彼此重复。
+    // replaced 为将被 new_rows 替换的行下标，不参与重复比较
+    pub fn check_constraints(&self, new_rows: &[&Row], replaced: &[usize]) -> Result<(), DbError> {
+        let checks: Vec<(usize, Expr)> = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, col)| col.check.as_ref().map(|sql| (i, sql)))
+            .map(|(i, sql)| Ok((i, parse_expression(sql)?)))
+            .collect::<Result<_, DbError>>()?;
+        let scope_columns = table_scope(self, &self.name);
+
+        for row in new_rows {
+            let values = row_values(self, row);
+            for (col, value) in self.columns.iter().zip(&values) {
+                if (col.not_null || col.is_primary_key) && value.is_null() {
+                    return Err(self.violation(Constraint::NotNull, col.name.clone(), "NULL".to_string()));
+                }
+            }
+            // CHECK 条件为 NULL 时视为满足
+            let scope = Scope {
+                columns: &scope_columns,
+                values: &values,
+                ..Scope::empty()
+            };
+            for (i, check) in &checks {
+                if truth(&eval(check, &scope)?)? == Some(false) {
+                    let col = &self.columns[*i];
+                    let constraint = Constraint::Check(col.check.clone().unwrap_or_default());
+                    return Err(self.violation(constraint, col.name.clone(), values[*i].to_string()));
+                }
+            }
+        }
+
+        let replaced: HashSet<usize> = replaced.iter().copied().collect();
+        let key = self.primary_key();
+        if !key.is_empty() {
+            self.check_unique(&key, Constraint::PrimaryKey, new_rows, &replaced)?;
+        }
+        for (i, col) in self.columns.iter().enumerate() {
+            if col.unique {
+                self.check_unique(&[i], Constraint::Unique, new_rows, &replaced)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 检查所有行，用于修改表结构之后
+    pub fn validate_constraints(&self) -> Result<(), DbError> {
+        let rows: Vec<&Row> = self.rows.iter().collect();
+        let all: Vec<usize> = (0..self.rows.len()).collect();
+        self.check_constraints(&rows, &all)
+    }
+
+    // columns 上的值不能重复，含 NULL 的行不参与比较
+    fn check_unique(
+        &self,
+        columns: &[usize],
+        constraint: Constraint,
+        new_rows: &[&Row],
+        replaced: &HashSet<usize>,
+    ) -> Result<(), DbError> {
+        let mut seen: HashSet<_> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !replaced.contains(i))
+            .map(|(_, row)| self.key_values(row, columns))
+            .filter(|values| !values.iter().any(Value::is_null))
+            .map(|values| values_key(&values))
+            .collect();
+        for row in new_rows {
+            let values = self.key_values(row, columns);
+            if values.iter().any(Value::is_null) {
+                continue;
+            }
+            if !seen.insert(values_key(&values)) {
+                let names: Vec<&str> = columns.iter().map(|&i| self.columns[i].name.as_str()).collect();
+                let shown: Vec<String> = values.iter().map(Value::to_string).collect();
+                return Err(self.violation(constraint, names.join(", "), shown.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    // 行在 columns 上的值，用于比较键
+    pub(crate) fn key_values(&self, row: &Row, columns: &[usize]) -> Vec<Value> {
+        columns
+            .iter()
+            .map(|&i| Value::from_stored(row.values.get(i), &self.columns[i].data_type))
+            .collect()
+    }
+
+    // CHECK 条件中对列的引用随列一起改名
+    pub(crate) fn rename_check_references(&mut self, name: &str, new_name: &str) -> Result<(), DbError> {
+        let table = self.name.clone();
+        for col in &mut self.columns {
+            if let Some(sql) = &col.check {
+                let mut check = parse_expression(sql)?;
+                check.visit_mut(&mut |expr| {
+                    if refers_to(expr, &table, name) {
+                        if let Expr::Column(column) | Expr::QualifiedColumn { name: column, .. } = expr {
+                            *column = new_name.to_string();
+                        }
+                    }
+                });
+                col.check = Some(check.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // 其他列的 CHECK 条件引用了第 idx 列时不能删除它，列自身的 CHECK 随列一起删除
+    pub(crate) fn check_drop_check_references(&self, idx: usize) -> Result<(), DbError> {
+        let name = &self.columns[idx].name;
+        for (i, col) in self.columns.iter().enumerate().filter(|(i, _)| *i != idx) {
+            let Some(sql) = &col.check else {
+                continue;
+            };
+            let mut found = false;
+            parse_expression(sql)?.visit(&mut |expr| found |= refers_to(expr, &self.name, name));
+            if found {
+                return Err(DbError::InvalidQuery(format!(
+                    "列 '{}' 被列 '{}' 的 CHECK 约束引用，不能删除",
+                    name, self.columns[i].name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn violation(&self, constraint: Constraint, column: String, value: String) -> DbError {
+        DbError::ConstraintViolation {
+            constraint,
+            table: self.name.clone(),
+            column,
+            value,
+        }
+    }
+}
+
+// 主键只能以一种方式定义一次：单列上的 PRIMARY KEY，或表级的 PRIMARY KEY (col, ...)
+pub(crate) fn multiple_primary_keys(table: &str) -> DbError {
+    DbError::InvalidQuery(format!(
+        "表 '{}' 定义了多个主键，复合主键请使用 PRIMARY KEY (col, ...)",
+        table
+    ))
+}
+
+// 表达式是否为对表 table 中 name 列的引用
+fn refers_to(expr: &Expr, table: &str, name: &str) -> bool {
+    match expr {
+        Expr::Column(column) => column.eq_ignore_ascii_case(name),
+        Expr::QualifiedColumn { table: t, name: column } => t.eq_ignore_ascii_case(table) && column.eq_ignore_ascii_case(name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::part1::{Constraint, Database, DbError};
+
+    fn database() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (x INT)").unwrap();
+        db
+    }
+
+    #[test]
+    fn primary_key_rejects_duplicates_and_null() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE k (a INT, b VARCHAR(5), PRIMARY KEY (a, b))").unwrap();
+        db.execute("INSERT INTO k VALUES (1, 'x'), (1, 'y')").unwrap();
+        let err = db.execute("INSERT INTO k VALUES (2, 'z'), (1, 'x')").unwrap_err();
+        assert!(matches!(err, DbError::ConstraintViolation { constraint: Constraint::PrimaryKey, .. }));
+        let err = db.execute("INSERT INTO k VALUES (NULL, 'x')").unwrap_err();
+        assert!(matches!(err, DbError::ConstraintViolation { constraint: Constraint::NotNull, .. }));
+        assert!(db.execute("UPDATE k SET b = 'x'").is_err());
+        // 失败的语句不留下任何修改
+        assert_eq!(db.read_rows("k").unwrap().len(), 2);
+        db.execute("UPDATE k SET a = a + 1").unwrap();
+    }
+
+    #[test]
+    fn check_is_validated_when_table_is_created() {
+        let mut db = database();
+        let err = db.execute("CREATE TABLE d (a INT CHECK (nope > 1))").unwrap_err();
+        assert!(matches!(err, DbError::ColumnNotFound(_)));
+        for check in ["COUNT(*) > 0", "a IN (SELECT x FROM t)", "EXISTS (SELECT x FROM t)", "(SELECT MAX(x) FROM t) > a"] {
+            let sql = format!("CREATE TABLE d (a INT CHECK ({}))", check);
+            assert!(matches!(db.execute(&sql), Err(DbError::InvalidQuery(_))), "{}", sql);
+        }
+        // 被拒绝的定义不会留下表
+        assert!(db.table("d").is_err());
+        db.execute("CREATE TABLE d (a INT, b INT CHECK (d.a < b))").unwrap();
+        db.execute("INSERT INTO d VALUES (1, 2)").unwrap();
+    }
+
+    #[test]
+    fn check_is_validated_when_column_is_added() {
+        let mut db = database();
+        assert!(db.execute("ALTER TABLE t ADD COLUMN y INT CHECK (nope = 1)").is_err());
+        assert!(db.execute("ALTER TABLE t ADD COLUMN y INT CHECK (SUM(x) > 0)").is_err());
+        assert_eq!(db.table("t").unwrap().columns.len(), 1);
+        db.execute("ALTER TABLE t ADD COLUMN y INT CHECK (y > x)").unwrap();
+    }
+
+    #[test]
+    fn column_used_by_another_check_cannot_be_dropped() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE cc (a INT, b INT CHECK (a < b), c INT CHECK (c > 0))").unwrap();
+        let err = db.execute("ALTER TABLE cc DROP COLUMN a").unwrap_err();
+        assert!(matches!(err, DbError::InvalidQuery(_)));
+        assert_eq!(db.table("cc").unwrap().columns.len(), 3);
+        // 只被自身 CHECK 引用的列可以删除
+        db.execute("ALTER TABLE cc DROP COLUMN c").unwrap();
+        db.execute("INSERT INTO cc VALUES (1, 2)").unwrap();
+    }
+
+    #[test]
+    fn second_primary_key_cannot_be_added() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE e (id INT PRIMARY KEY)").unwrap();
+        db.execute("INSERT INTO e VALUES (1)").unwrap();
+        let err = db.execute("ALTER TABLE e ADD COLUMN x INT PRIMARY KEY").unwrap_err();
+        let create = db.execute("CREATE TABLE f (id INT PRIMARY KEY, x INT PRIMARY KEY)").unwrap_err();
+        assert_eq!(err.to_string().replace("'e'", "'f'"), create.to_string());
+        assert!(db.execute("INSERT INTO e VALUES (1)").is_err());
+    }
+}
@@ -0,0 +1,625 @@
+use serde::{Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+pub const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+pub const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+// 比较时间间隔时一个月按 30 天计
+const DAYS_PER_MONTH: i64 = 30;
+
+thread_local! {
+    // 正在执行的语句的当前时间，同一语句中的 NOW() 取值相同
+    static STATEMENT_TIME: Cell<Option<Timestamp>> = const { Cell::new(None) };
+}
+
+// 以 now 作为当前时间执行 f，结束后恢复原来的设置
+pub fn with_statement_time<R>(now: Timestamp, f: impl FnOnce() -> R) -> R {
+    let previous = STATEMENT_TIME.replace(Some(now));
+    let result = f();
+    STATEMENT_TIME.set(previous);
+    result
+}
+// 支持公元 1 年至 9999 年，以便按 ISO-8601 的四位年份显示
+const MIN_YEAR: i32 = 1;
+const MAX_YEAR: i32 = 9999;
+
+// 日期，保存为自 1970-01-01 起的天数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(i32);
+
+// 一天中的时刻，保存为自零点起的微秒数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time(i64);
+
+// 日期和时刻，保存为自 1970-01-01 00:00:00 起的微秒数；带时区的时间戳为 UTC 时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+// 时间间隔。月数和天数单独保存，因为一个月的天数和一天的长度（对日期而言）并不固定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Date {
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(MIN_YEAR..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) {
+            return None;
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date(days_from_civil(year, month, day)))
+    }
+
+    // 自 1970-01-01 起的天数对应的日期，超出 1 到 9999 年时为 None
+    pub fn from_days(days: i64) -> Option<Date> {
+        // 先按天数比较，超出 i32 附近的天数换算为年份时会溢出
+        let range = days_from_civil(MIN_YEAR, 1, 1)..=days_from_civil(MAX_YEAR, 12, 31);
+        i32::try_from(days).ok().filter(|days| range.contains(days)).map(Date)
+    }
+
+    // 自 1970-01-01 起的天数
+    pub fn days(self) -> i64 {
+        self.0 as i64
+    }
+
+    pub fn ymd(self) -> (i32, u32, u32) {
+        civil_from_days(self.0)
+    }
+
+    pub fn add_days(self, days: i64) -> Option<Date> {
+        Date::from_days(self.days().checked_add(days)?)
+    }
+
+    // 加上若干个月，日超过目标月份的天数时取该月最后一天，如 1 月 31 日加一个月为 2 月 28 日或 29 日
+    pub fn add_months(self, months: i64) -> Option<Date> {
+        let (year, month, day) = self.ymd();
+        let total = (year as i64 * 12 + month as i64 - 1).checked_add(months)?;
+        let year = i32::try_from(total.div_euclid(12)).ok()?;
+        let month = total.rem_euclid(12) as u32 + 1;
+        if !(MIN_YEAR..=MAX_YEAR).contains(&year) {
+            return None;
+        }
+        Date::from_ymd(year, month, day.min(days_in_month(year, month)))
+    }
+
+    // 星期几，0 为星期日
+    pub fn weekday(self) -> u32 {
+        (self.days() + 4).rem_euclid(7) as u32
+    }
+
+    // 一年中的第几天，从 1 开始
+    pub fn day_of_year(self) -> u32 {
+        let (year, _, _) = self.ymd();
+        (self.0 - days_from_civil(year, 1, 1)) as u32 + 1
+    }
+
+    // ISO-8601 周数：每周从星期一开始，包含该年第一个星期四的周为第 1 周
+    pub fn iso_week(self) -> u32 {
+        let iso_weekday = (self.weekday() + 6) % 7; // 0 为星期一
+        let thursday = Date(self.0 - iso_weekday as i32 + 3);
+        (thursday.day_of_year() - 1) / 7 + 1
+    }
+}
+
+impl Time {
+    pub fn from_hms_micro(hour: u32, minute: u32, second: u32, micro: u32) -> Option<Time> {
+        if hour >= 24 || minute >= 60 || second >= 60 || micro as i64 >= MICROS_PER_SECOND {
+            return None;
+        }
+        let micros = hour as i64 * MICROS_PER_HOUR
+            + minute as i64 * MICROS_PER_MINUTE
+            + second as i64 * MICROS_PER_SECOND
+            + micro as i64;
+        Some(Time(micros))
+    }
+
+    // 超出一天的部分绕回，如 23:00 加两小时为 01:00
+    pub fn from_micros(micros: i64) -> Time {
+        Time(micros.rem_euclid(MICROS_PER_DAY))
+    }
+
+    pub fn micros(self) -> i64 {
+        self.0
+    }
+
+    pub fn hour(self) -> i64 {
+        self.0 / MICROS_PER_HOUR
+    }
+
+    pub fn minute(self) -> i64 {
+        self.0 % MICROS_PER_HOUR / MICROS_PER_MINUTE
+    }
+
+    // 秒数中的微秒部分，含整秒
+    pub fn second_micros(self) -> i64 {
+        self.0 % MICROS_PER_MINUTE
+    }
+}
+
+impl Timestamp {
+    pub fn new(date: Date, time: Time) -> Timestamp {
+        Timestamp(date.days() * MICROS_PER_DAY + time.micros())
+    }
+
+    pub fn from_micros(micros: i64) -> Option<Timestamp> {
+        Date::from_days(micros.div_euclid(MICROS_PER_DAY))?;
+        Some(Timestamp(micros))
+    }
+
+    // 当前的 UTC 时间；语句执行期间为语句开始的时间，见 with_statement_time
+    pub fn now() -> Timestamp {
+        if let Some(now) = STATEMENT_TIME.get() {
+            return now;
+        }
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Timestamp(elapsed.as_micros() as i64)
+    }
+
+    pub fn micros(self) -> i64 {
+        self.0
+    }
+
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+
+    pub fn time(self) -> Time {
+        Time(self.0.rem_euclid(MICROS_PER_DAY))
+    }
+
+    // 依次加上月数、天数和微秒数
+    pub fn add_interval(self, interval: Interval) -> Option<Timestamp> {
+        let date = self.date().add_months(interval.months as i64)?;
+        let micros = (date.days() * MICROS_PER_DAY + self.time().micros())
+            .checked_add((interval.days as i64).checked_mul(MICROS_PER_DAY)?)?
+            .checked_add(interval.micros)?;
+        Timestamp::from_micros(micros)
+    }
+
+    // 两个时间点之差，整天的部分计入天数
+    pub fn since(self, earlier: Timestamp) -> Option<Interval> {
+        let diff = self.0.checked_sub(earlier.0)?;
+        Some(Interval {
+            months: 0,
+            days: i32::try_from(diff / MICROS_PER_DAY).ok()?,
+            micros: diff % MICROS_PER_DAY,
+        })
+    }
+
+    // 截断到 unit（YEAR、QUARTER、MONTH、WEEK、DAY、HOUR、MINUTE、SECOND），WEEK 截断到星期一
+    pub fn truncate(self, unit: &str) -> Option<Timestamp> {
+        let date = self.date();
+        let (year, month, _) = date.ymd();
+        let micros = self.time().micros();
+        let truncated = match unit {
+            "YEAR" => Timestamp::new(Date::from_ymd(year, 1, 1)?, Time(0)),
+            "QUARTER" => Timestamp::new(Date::from_ymd(year, (month - 1) / 3 * 3 + 1, 1)?, Time(0)),
+            "MONTH" => Timestamp::new(Date::from_ymd(year, month, 1)?, Time(0)),
+            "WEEK" => Timestamp::new(date.add_days(-(((date.weekday() + 6) % 7) as i64))?, Time(0)),
+            "DAY" => Timestamp::new(date, Time(0)),
+            "HOUR" => Timestamp::new(date, Time(micros - micros % MICROS_PER_HOUR)),
+            "MINUTE" => Timestamp::new(date, Time(micros - micros % MICROS_PER_MINUTE)),
+            "SECOND" => Timestamp::new(date, Time(micros - micros % MICROS_PER_SECOND)),
+            _ => return None,
+        };
+        Some(truncated)
+    }
+
+    // 解析 ISO-8601 时间，如 2024-01-31T08:30:00、2024-01-31 08:30:00.5+08:00，只有日期时为零点。
+    // with_time_zone 时按偏移换算为 UTC，没有偏移的视为 UTC；否则忽略偏移
+    pub fn parse(text: &str, with_time_zone: bool) -> Option<Timestamp> {
+        let text = text.trim();
+        let date_end = text.find(['T', 't', ' ']).unwrap_or(text.len());
+        let date: Date = text[..date_end].parse().ok()?;
+        let rest = text.get(date_end + 1..).unwrap_or("").trim();
+        if rest.is_empty() {
+            return Some(Timestamp::new(date, Time(0)));
+        }
+        let (time, offset) = match rest.find(['Z', 'z', '+', '-']) {
+            Some(i) => (rest[..i].trim(), parse_offset(&rest[i..])?),
+            None => (rest, 0),
+        };
+        let timestamp = Timestamp::new(date, time.parse().ok()?);
+        if with_time_zone {
+            Timestamp::from_micros(timestamp.0 - offset)
+        } else {
+            Some(timestamp)
+        }
+    }
+}
+
+// 时区偏移 Z、+08、+08:00 或 +0800，返回微秒数
+fn parse_offset(text: &str) -> Option<i64> {
+    if text.eq_ignore_ascii_case("Z") {
+        return Some(0);
+    }
+    let sign = match text.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits: String = text[1..].chars().filter(|&c| c != ':').collect();
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i64>().ok()?, 0),
+        4 => (digits[..2].parse::<i64>().ok()?, digits[2..].parse::<i64>().ok()?),
+        _ => return None,
+    };
+    if hours > 15 || minutes >= 60 {
+        return None;
+    }
+    Some(sign * (hours * MICROS_PER_HOUR + minutes * MICROS_PER_MINUTE))
+}
+
+impl Interval {
+    // amount 个 unit，unit 为 YEAR、QUARTER、MONTH、WEEK、DAY、HOUR、MINUTE、SECOND（大小写不敏感，可用复数）
+    pub fn from_unit(amount: i64, unit: &str) -> Option<Interval> {
+        let unit = unit.to_uppercase();
+        let unit = unit.strip_suffix('S').unwrap_or(&unit);
+        let months = |n: i64| Some(Interval { months: i32::try_from(amount.checked_mul(n)?).ok()?, ..Interval::default() });
+        let days = |n: i64| Some(Interval { days: i32::try_from(amount.checked_mul(n)?).ok()?, ..Interval::default() });
+        let micros = |n: i64| Some(Interval { micros: amount.checked_mul(n)?, ..Interval::default() });
+        match unit {
+            "YEAR" => months(12),
+            "QUARTER" => months(3),
+            "MONTH" | "MON" => months(1),
+            "WEEK" => days(7),
+            "DAY" => days(1),
+            "HOUR" => micros(MICROS_PER_HOUR),
+            "MINUTE" | "MIN" => micros(MICROS_PER_MINUTE),
+            "SECOND" | "SEC" => micros(MICROS_PER_SECOND),
+            _ => None,
+        }
+    }
+
+    // 换算为微秒数，一个月按 30 天计，用于比较
+    pub fn total_micros(self) -> i128 {
+        let days = self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128;
+        days * MICROS_PER_DAY as i128 + self.micros as i128
+    }
+
+    pub fn checked_add(self, other: Interval) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_add(other.months)?,
+            days: self.days.checked_add(other.days)?,
+            micros: self.micros.checked_add(other.micros)?,
+        })
+    }
+
+    pub fn checked_sub(self, other: Interval) -> Option<Interval> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_neg(self) -> Option<Interval> {
+        Some(Interval {
+            months: self.months.checked_neg()?,
+            days: self.days.checked_neg()?,
+            micros: self.micros.checked_neg()?,
+        })
+    }
+
+    pub fn checked_mul(self, n: i64) -> Option<Interval> {
+        Some(Interval {
+            months: i32::try_from((self.months as i64).checked_mul(n)?).ok()?,
+            days: i32::try_from((self.days as i64).checked_mul(n)?).ok()?,
+            micros: self.micros.checked_mul(n)?,
+        })
+    }
+
+    // 各部分分别相除，除不尽的月数按 30 天、天数按 24 小时计入下一部分
+    pub fn checked_div(self, n: i64) -> Option<Interval> {
+        let months = self.months as i64;
+        let days = (self.days as i64).checked_add(months.checked_rem(n)? * DAYS_PER_MONTH)?;
+        let micros = self.micros.checked_add((days % n).checked_mul(MICROS_PER_DAY)?)?;
+        Some(Interval {
+            months: i32::try_from(months / n).ok()?,
+            days: i32::try_from(days / n).ok()?,
+            micros: micros / n,
+        })
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// 公历日期与自 1970-01-01 起天数的换算，按 400 年的周期计算（Howard Hinnant 的算法）
+fn days_from_civil(year: i32, month: u32, day: u32) -> i32 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400) as u32;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era as i32 - 719_468
+}
+
+fn civil_from_days(days: i32) -> (i32, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097) as u32;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // 从三月开始计
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era as i32 + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[derive(Debug)]
+pub struct ParseDateTimeError;
+
+// YYYY-MM-DD
+impl FromStr for Date {
+    type Err = ParseDateTimeError;
+
+    fn from_str(text: &str) -> Result<Date, ParseDateTimeError> {
+        let parts: Vec<&str> = text.trim().split('-').collect();
+        let [year, month, day] = parts.as_slice() else {
+            return Err(ParseDateTimeError);
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(ParseDateTimeError);
+        }
+        let number = |s: &str| s.parse::<u32>().map_err(|_| ParseDateTimeError);
+        Date::from_ymd(number(year)? as i32, number(month)?, number(day)?).ok_or(ParseDateTimeError)
+    }
+}
+
+// HH:MM[:SS[.ffffff]]，秒的小数部分最多保留到微秒
+impl FromStr for Time {
+    type Err = ParseDateTimeError;
+
+    fn from_str(text: &str) -> Result<Time, ParseDateTimeError> {
+        let text = text.trim();
+        let (clock, fraction) = text.split_once('.').unwrap_or((text, ""));
+        let parts: Vec<&str> = clock.split(':').collect();
+        if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|p| p.len() != 2) {
+            return Err(ParseDateTimeError);
+        }
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) || (parts.len() == 2 && text.contains('.')) {
+            return Err(ParseDateTimeError);
+        }
+        let number = |s: &str| s.parse::<u32>().map_err(|_| ParseDateTimeError);
+        let second = parts.get(2).map_or(Ok(0), |s| number(s))?;
+        let micro = format!("{:0<6}", &fraction[..fraction.len().min(6)]);
+        Time::from_hms_micro(number(parts[0])?, number(parts[1])?, second, number(&micro)?).ok_or(ParseDateTimeError)
+    }
+}
+
+// ISO-8601 的时长 P1Y2M3DT4H5M6.5S，或 1 year 2 months 3 days 04:05:06 这样的写法
+impl FromStr for Interval {
+    type Err = ParseDateTimeError;
+
+    fn from_str(text: &str) -> Result<Interval, ParseDateTimeError> {
+        let text = text.trim();
+        let interval = match text.strip_prefix(['P', 'p']) {
+            Some(iso) => parse_iso_duration(iso),
+            None => parse_verbose_interval(text),
+        };
+        interval.ok_or(ParseDateTimeError)
+    }
+}
+
+fn parse_iso_duration(text: &str) -> Option<Interval> {
+    let (date_part, time_part) = match text.split_once(['T', 't']) {
+        Some((date, time)) if !time.is_empty() => (date, Some(time)),
+        Some(_) => return None,
+        None => (text, None),
+    };
+    let mut interval = Interval::default();
+    let mut rest = date_part;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let amount: i64 = rest[..end].parse().ok()?;
+        let unit = match rest.as_bytes()[end].to_ascii_uppercase() {
+            b'Y' => "YEAR",
+            b'M' => "MONTH",
+            b'W' => "WEEK",
+            b'D' => "DAY",
+            _ => return None,
+        };
+        interval = interval.checked_add(Interval::from_unit(amount, unit)?)?;
+        rest = &rest[end + 1..];
+    }
+    let mut rest = time_part.unwrap_or("");
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let amount = &rest[..end];
+        let part = match rest.as_bytes()[end].to_ascii_uppercase() {
+            b'H' => Interval::from_unit(amount.parse().ok()?, "HOUR")?,
+            b'M' => Interval::from_unit(amount.parse().ok()?, "MINUTE")?,
+            b'S' => Interval { micros: parse_seconds(amount)?, ..Interval::default() },
+            _ => return None,
+        };
+        interval = interval.checked_add(part)?;
+        rest = &rest[end + 1..];
+    }
+    Some(interval)
+}
+
+// 若干个“数量 单位”，末尾可以有 [-]HH:MM[:SS[.ffffff]]
+fn parse_verbose_interval(text: &str) -> Option<Interval> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return None;
+    }
+    let mut interval = Interval::default();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        if word.contains(':') {
+            if i + 1 != words.len() {
+                return None;
+            }
+            let (negative, clock) = match word.strip_prefix('-') {
+                Some(clock) => (true, clock),
+                None => (false, word),
+            };
+            let micros = clock.parse::<Time>().ok()?.micros();
+            let micros = if negative { -micros } else { micros };
+            interval = interval.checked_add(Interval { micros, ..Interval::default() })?;
+            break;
+        }
+        let unit = words.get(i + 1)?;
+        let part = if unit.to_uppercase().starts_with("SEC") {
+            Interval { micros: parse_seconds(word)?, ..Interval::default() }
+        } else {
+            Interval::from_unit(word.parse().ok()?, unit)?
+        };
+        interval = interval.checked_add(part)?;
+        i += 2;
+    }
+    Some(interval)
+}
+
+// 可以带小数的秒数，换算为微秒
+fn parse_seconds(text: &str) -> Option<i64> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if whole.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let micro: i64 = format!("{:0<6}", &fraction[..fraction.len().min(6)]).parse().ok()?;
+    let micros = whole.parse::<i64>().ok()?.checked_mul(MICROS_PER_SECOND)?.checked_add(micro)?;
+    Some(if negative { -micros } else { micros })
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+// HH:MM:SS，有小数秒时去掉末尾的 0
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.second_micros();
+        write!(f, "{:02}:{:02}:{:02}", self.hour(), self.minute(), seconds / MICROS_PER_SECOND)?;
+        write_fraction(f, seconds % MICROS_PER_SECOND)
+    }
+}
+
+// YYYY-MM-DDTHH:MM:SS，带时区的时间戳由 Value 加上 Z 后缀
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}T{}", self.date(), self.time())
+    }
+}
+
+// ISO-8601 时长，如 P1Y2M3DT4H5M6.5S，各部分可以为负，零长度为 PT0S
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Interval::default() {
+            return write!(f, "PT0S");
+        }
+        write!(f, "P")?;
+        let (years, months) = (self.months / 12, self.months % 12);
+        for (amount, unit) in [(years as i64, "Y"), (months as i64, "M"), (self.days as i64, "D")] {
+            if amount != 0 {
+                write!(f, "{}{}", amount, unit)?;
+            }
+        }
+        if self.micros == 0 {
+            return Ok(());
+        }
+        write!(f, "T")?;
+        let hours = self.micros / MICROS_PER_HOUR;
+        let minutes = self.micros % MICROS_PER_HOUR / MICROS_PER_MINUTE;
+        let seconds = self.micros % MICROS_PER_MINUTE;
+        for (amount, unit) in [(hours, "H"), (minutes, "M")] {
+            if amount != 0 {
+                write!(f, "{}{}", amount, unit)?;
+            }
+        }
+        if seconds != 0 {
+            let sign = if seconds < 0 { "-" } else { "" };
+            write!(f, "{}{}", sign, seconds.abs() / MICROS_PER_SECOND)?;
+            write_fraction(f, seconds.abs() % MICROS_PER_SECOND)?;
+            write!(f, "S")?;
+        }
+        Ok(())
+    }
+}
+
+// 秒的小数部分，去掉末尾的 0
+fn write_fraction(f: &mut fmt::Formatter<'_>, micros: i64) -> fmt::Result {
+    if micros == 0 {
+        return Ok(());
+    }
+    let digits = format!("{:06}", micros);
+    write!(f, ".{}", digits.trim_end_matches('0'))
+}
+
+// 数据文件中保存为 ISO-8601 字符串
+macro_rules! serialize_as_string {
+    ($($t:ty),*) => {
+        $(impl Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        })*
+    };
+}
+
+serialize_as_string!(Date, Time, Timestamp, Interval);
+
+// 带时区的时间戳加上 Z 后缀
+pub fn serialize_utc<S: Serializer>(timestamp: &Timestamp, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{}Z", timestamp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1::Database;
+
+    #[test]
+    fn calendar_arithmetic_and_date_functions() {
+        let date: Date = "2024-01-31".parse().unwrap();
+        assert_eq!(date.add_months(1).unwrap().to_string(), "2024-02-29");
+        assert_eq!(date.add_days(1).unwrap().to_string(), "2024-02-01");
+        assert!("2024-02-30".parse::<Date>().is_err());
+        assert_eq!(Timestamp::parse("2024-01-31 08:30+08:00", true).unwrap().to_string(), "2024-01-31T00:30:00");
+
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE e (d DATE, ts TIMESTAMP)").unwrap();
+        db.execute("INSERT INTO e VALUES ('2024-01-31', '2024-05-06 07:08:09')").unwrap();
+        let result = db
+            .execute(
+                "SELECT d + 1, DATE '2024-03-01' - d, d + INTERVAL 1 MONTH, EXTRACT(YEAR FROM ts), \
+                 DATE_TRUNC('month', ts), ts - TIMESTAMP '2024-05-05 07:00:00' FROM e",
+            )
+            .unwrap();
+        let values: Vec<String> = result.rows[0].values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(values[..5], ["2024-02-01", "30", "2024-02-29", "2024", "2024-05-01T00:00:00"]);
+        assert!(db.execute("INSERT INTO e VALUES ('2024-02-30', NULL)").is_err());
+        // 同一语句中的 NOW() 取值相同
+        let now = Timestamp::new(date, Time::from_hms_micro(12, 0, 0, 0).unwrap());
+        let result = db.execute_at("SELECT NOW(), CURRENT_DATE FROM e", now).unwrap();
+        assert_eq!(result.rows[0].values[1].to_string(), "2024-01-31");
+    }
+}
@@ -0,0 +1,264 @@
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// DECIMAL 的最大精度，i128 可以完整表示 38 位十进制数
+pub const MAX_PRECISION: u32 = 38;
+// 除法结果在被除数的小数位数之上多保留的位数
+const DIVISION_EXTRA_SCALE: u32 = 4;
+
+// 精确的十进制定点数，值为 units / 10^scale。运算溢出时返回 None，舍入一律四舍五入（远离零）
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Decimal {
+        Decimal { units, scale }
+    }
+
+    pub fn from_i64(i: i64) -> Decimal {
+        Decimal { units: i as i128, scale: 0 }
+    }
+
+    // 按浮点数的最短十进制表示转换，非有限值或超出范围时为 None
+    pub fn from_f64(x: f64) -> Option<Decimal> {
+        if !x.is_finite() {
+            return None;
+        }
+        x.to_string().parse().ok()
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    // 没有小数部分时转换为整数
+    pub fn to_i64(self) -> Option<i64> {
+        let divisor = pow10(self.scale)?;
+        if self.units % divisor != 0 {
+            return None;
+        }
+        i64::try_from(self.units / divisor).ok()
+    }
+
+    // 去掉小数点后的整数，值为 units / 10^scale
+    pub fn units(self) -> i128 {
+        self.units
+    }
+
+    pub fn scale(self) -> u32 {
+        self.scale
+    }
+
+    // 整数部分与小数部分的总位数
+    pub fn precision(self) -> u32 {
+        let digits = self.units.unsigned_abs().checked_ilog10().map_or(1, |d| d + 1);
+        digits.max(self.scale)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.units == 0
+    }
+
+    // 调整为 scale 位小数：位数多时四舍五入，位数少时补零
+    pub fn round_to(self, scale: u32) -> Option<Decimal> {
+        let units = match scale.cmp(&self.scale) {
+            Ordering::Equal => self.units,
+            Ordering::Greater => self.units.checked_mul(pow10(scale - self.scale)?)?,
+            Ordering::Less => match pow10(self.scale - scale) {
+                Some(divisor) => divide_rounded(self.units, divisor)?,
+                // 舍去的位数超过 i128 的范围，结果必然为 0
+                None => 0,
+            },
+        };
+        Some(Decimal { units, scale })
+    }
+
+    // 去掉小数部分末尾的 0，用于比较键
+    pub fn normalized(self) -> Decimal {
+        let mut value = self;
+        while value.scale > 0 && value.units % 10 == 0 {
+            value.units /= 10;
+            value.scale -= 1;
+        }
+        value
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = align(self, other)?;
+        Some(Decimal { units: a.checked_add(b)?, scale })
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = align(self, other)?;
+        Some(Decimal { units: a.checked_sub(b)?, scale })
+    }
+
+    // 结果的小数位数为两者之和，超过 MAX_PRECISION 时舍入
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let product = Decimal {
+            units: self.units.checked_mul(other.units)?,
+            scale: self.scale + other.scale,
+        };
+        product.round_to(product.scale.min(MAX_PRECISION))
+    }
+
+    // 结果比被除数多保留 DIVISION_EXTRA_SCALE 位小数，除数为 0 时为 None
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.units == 0 {
+            return None;
+        }
+        let scale = (self.scale + DIVISION_EXTRA_SCALE).clamp(other.scale.min(MAX_PRECISION), MAX_PRECISION);
+        let numerator = self.units.checked_mul(pow10(scale + other.scale - self.scale)?)?;
+        Some(Decimal { units: divide_rounded(numerator, other.units)?, scale })
+    }
+
+    // 余数的符号与被除数相同，除数为 0 时为 None
+    pub fn checked_rem(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = align(self, other)?;
+        Some(Decimal { units: a.checked_rem(b)?, scale })
+    }
+
+    pub fn checked_abs(self) -> Option<Decimal> {
+        Some(Decimal { units: self.units.checked_abs()?, scale: self.scale })
+    }
+}
+
+// 把两个数调整到相同的小数位数
+fn align(a: Decimal, b: Decimal) -> Option<(i128, i128, u32)> {
+    let scale = a.scale.max(b.scale);
+    let a_units = a.units.checked_mul(pow10(scale - a.scale)?)?;
+    let b_units = b.units.checked_mul(pow10(scale - b.scale)?)?;
+    Some((a_units, b_units, scale))
+}
+
+fn pow10(exponent: u32) -> Option<i128> {
+    10i128.checked_pow(exponent)
+}
+
+// 整数除法，按四舍五入（远离零）取整
+fn divide_rounded(numerator: i128, divisor: i128) -> Option<i128> {
+    let quotient = numerator.checked_div(divisor)?;
+    let remainder = (numerator % divisor).unsigned_abs();
+    if remainder >= divisor.unsigned_abs() - remainder {
+        let away = if (numerator < 0) == (divisor < 0) { 1 } else { -1 };
+        quotient.checked_add(away)
+    } else {
+        Some(quotient)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let (a, b) = (self.normalized(), other.normalized());
+        match align(a, b) {
+            Some((a, b, _)) => a.cmp(&b),
+            // 对齐时溢出说明两者的数量级相差很大，按浮点数比较即可
+            None => a.to_f64().total_cmp(&b.to_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.units.unsigned_abs().to_string();
+        let sign = if self.units < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseDecimalError;
+
+// 接受 [+-]digits[.digits] 形式的文本，不接受指数
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    fn from_str(text: &str) -> Result<Decimal, ParseDecimalError> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if int_part.is_empty() && frac_part.is_empty() || !all_digits(int_part) || !all_digits(frac_part) {
+            return Err(ParseDecimalError);
+        }
+        let mut units: i128 = 0;
+        for b in int_part.bytes().chain(frac_part.bytes()) {
+            units = units
+                .checked_mul(10)
+                .and_then(|u| u.checked_add((b - b'0') as i128))
+                .ok_or(ParseDecimalError)?;
+        }
+        let scale = u32::try_from(frac_part.len()).map_err(|_| ParseDecimalError)?;
+        let value = Decimal {
+            units: if negative { -units } else { units },
+            scale,
+        };
+        value.round_to(scale.min(MAX_PRECISION)).ok_or(ParseDecimalError)
+    }
+}
+
+// 数据文件中保存为字符串，以免经过 JSON 数字时损失精度
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1::{Database, DbError};
+    use crate::value::Value;
+
+    fn decimal(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn decimal_arithmetic_is_exact() {
+        assert_eq!(decimal("0.1").checked_add(decimal("0.2")), Some(decimal("0.3")));
+        assert_eq!(decimal("1.005").round_to(2), Some(decimal("1.01")));
+        assert_eq!(decimal("-1.005").round_to(2), Some(decimal("-1.01")));
+        assert_eq!(decimal("1").checked_div(decimal("3.0")).map(|d| d.to_string()), Some("0.3333".to_string()));
+        assert_eq!(decimal("2.50").cmp(&decimal("2.5")), Ordering::Equal);
+        assert!(decimal("1").checked_div(decimal("0")).is_none());
+        assert!("1e3".parse::<Decimal>().is_err());
+
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE d (x DECIMAL(4,2), f DOUBLE)").unwrap();
+        db.execute("INSERT INTO d VALUES (1.005, 0.1)").unwrap();
+        assert!(matches!(db.execute("INSERT INTO d VALUES (123.4, 0)"), Err(DbError::TypeMismatch(_))));
+        let row = &db.execute("SELECT x, x + 0.2, f + 0.2e0 FROM d").unwrap().rows[0];
+        assert_eq!(row.values[0], Value::Decimal(decimal("1.01")));
+        assert_eq!(row.values[1].to_string(), "1.21");
+        assert_eq!(row.values[2], Value::Float(0.1 + 0.2));
+    }
+}
@@ -0,0 +1,844 @@
+use crate::ast::*;
+use crate::executor::QueryOutput;
+use crate::part1::{DataType, Database, DbError, Row, Table};
+use crate::datetime::{Interval, Time, Timestamp, MICROS_PER_DAY, MICROS_PER_HOUR, MICROS_PER_MINUTE};
+use crate::blob;
+use crate::decimal::Decimal;
+use crate::json;
+use crate::value::{parse_bool, parse_number, to_decimal, to_f64, Value};
+use std::cmp::Ordering;
+
+// 求值时可见的列
+#[derive(Debug, Clone)]
+pub struct ScopeColumn {
+    pub table: Option<String>,        // 限定名：表名或别名
+    pub name: String,
+    pub data_type: Option<DataType>,  // 计算得到的列没有声明类型
+    pub qualified_only: bool,         // 只能通过 table.name 引用，如 USING 连接中被合并的列
+}
+
+// 一行数据及其列信息，表达式中的列引用在这里解析
+#[derive(Clone, Copy)]
+pub struct Scope<'a> {
+    pub columns: &'a [ScopeColumn],
+    pub values: &'a [Value],
+    pub outer: Option<&'a Scope<'a>>, // 外层查询的当前行，相关子查询通过它引用外层的列
+    pub db: Option<&'a Database>,     // 求值子查询时使用，为空时不能使用子查询
+}
+
+impl<'a> Scope<'a> {
+    // 不含任何列的作用域，用于 VALUES 等不引用列的表达式
+    pub fn empty() -> Scope<'static> {
+        Scope {
+            columns: &[],
+            values: &[],
+            outer: None,
+            db: None,
+        }
+    }
+
+    // 本层找不到的列再到外层查找
+    fn lookup(&self, table: Option<&str>, name: &str) -> Result<&'a Value, DbError> {
+        match (resolve_column(self.columns, table, name), self.outer) {
+            (Ok(i), _) => Ok(&self.values[i]),
+            (Err(DbError::ColumnNotFound(_)), Some(outer)) => outer.lookup(table, name),
+            (Err(e), _) => Err(e),
+        }
+    }
+}
+
+// 在求值之前解析表达式中的全部列引用（不进入子查询），本层找不到的列再到外层查找。
+// 找不到的列总会报错，而不取决于表中有没有行、AND/OR 是否短路
+pub fn resolve_columns(expr: &Expr, columns: &[ScopeColumn], outer: Option<&Scope>) -> Result<(), DbError> {
+    let mut result = Ok(());
+    expr.visit(&mut |expr| {
+        let (table, name) = match expr {
+            Expr::Column(name) => (None, name),
+            Expr::QualifiedColumn { table, name } => (Some(table.as_str()), name),
+            _ => return,
+        };
+        if result.is_ok() {
+            result = match (resolve_column(columns, table, name), outer) {
+                (Err(DbError::ColumnNotFound(_)), Some(outer)) => outer.lookup(table, name).map(|_| ()),
+                (found, _) => found.map(|_| ()),
+            };
+        }
+    });
+    result
+}
+
+// 在作用域中解析列引用，完全匹配优先，其次大小写不敏感匹配；
+// 未限定的列名同时出现在多个表中时报歧义
+pub fn resolve_column(columns: &[ScopeColumn], table: Option<&str>, name: &str) -> Result<usize, DbError> {
+    let find = |eq: fn(&str, &str) -> bool| -> Vec<usize> {
+        columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                eq(&c.name, name)
+                    && match table {
+                        Some(t) => c.table.as_deref().is_some_and(|ct| eq(ct, t)),
+                        None => !c.qualified_only,
+                    }
+            })
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let mut found = find(|a, b| a == b);
+    if found.is_empty() {
+        found = find(|a, b| a.eq_ignore_ascii_case(b));
+    }
+    let display = match table {
+        Some(t) => format!("{}.{}", t, name),
+        None => name.to_string(),
+    };
+    match found.as_slice() {
+        [] => Err(DbError::ColumnNotFound(display)),
+        [i] => Ok(*i),
+        _ => Err(DbError::InvalidQuery(format!("列引用 '{}' 不明确", display))),
+    }
+}
+
+// 表的列作为作用域列，qualifier 为表名或别名
+pub fn table_scope(table: &Table, qualifier: &str) -> Vec<ScopeColumn> {
+    table
+        .columns
+        .iter()
+        .map(|col| ScopeColumn {
+            table: Some(qualifier.to_string()),
+            name: col.name.clone(),
+            data_type: Some(col.data_type.clone()),
+            qualified_only: false,
+        })
+        .collect()
+}
+
+// 读取表中的一行，旧数据中缺少的值视为 NULL
+pub fn row_values(table: &Table, row: &Row) -> Vec<Value> {
+    table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, col)| Value::from_stored(row.values.get(i), &col.data_type))
+        .collect()
+}
+
+pub fn eval(expr: &Expr, scope: &Scope) -> Result<Value, DbError> {
+    match expr {
+        Expr::Literal(literal) => eval_literal(literal),
+        Expr::Column(name) => scope.lookup(None, name).cloned(),
+        Expr::QualifiedColumn { table, name } => scope.lookup(Some(table), name).cloned(),
+        Expr::Unary { op: UnaryOp::Not, expr } => Ok(match truth(&eval(expr, scope)?)? {
+            Some(b) => Value::Bool(!b),
+            None => Value::Null,
+        }),
+        Expr::Unary { op: UnaryOp::Minus, expr } => match eval(expr, scope)? {
+            Value::Interval(interval) => interval
+                .checked_neg()
+                .map(Value::Interval)
+                .ok_or_else(|| DbError::Arithmetic(format!("-{} 溢出", interval))),
+            value => arithmetic(&Value::Int(0), BinaryOp::Minus, &value),
+        },
+        Expr::Binary { left, op: BinaryOp::And, right } => {
+            let left = truth(&eval(left, scope)?)?;
+            if left == Some(false) {
+                return Ok(Value::Bool(false));
+            }
+            Ok(match (left, truth(&eval(right, scope)?)?) {
+                (_, Some(false)) => Value::Bool(false),
+                (Some(true), Some(true)) => Value::Bool(true),
+                _ => Value::Null,
+            })
+        }
+        Expr::Binary { left, op: BinaryOp::Or, right } => {
+            let left = truth(&eval(left, scope)?)?;
+            if left == Some(true) {
+                return Ok(Value::Bool(true));
+            }
+            Ok(match (left, truth(&eval(right, scope)?)?) {
+                (_, Some(true)) => Value::Bool(true),
+                (Some(false), Some(false)) => Value::Bool(false),
+                _ => Value::Null,
+            })
+        }
+        Expr::Binary { left, op: BinaryOp::Concat, right } => {
+            let left = eval(left, scope)?;
+            let right = eval(right, scope)?;
+            match (left, right) {
+                (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                (Value::Blob(mut left), Value::Blob(right)) => {
+                    left.extend(right);
+                    Ok(Value::Blob(left))
+                }
+                (left, right) => Ok(Value::Text(format!("{}{}", left, right))),
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            let left = eval(left, scope)?;
+            let right = eval(right, scope)?;
+            match op {
+                BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+                    arithmetic(&left, *op, &right)
+                }
+                BinaryOp::JsonGet | BinaryOp::JsonGetText => json_access(&left, *op, &right),
+                _ => compare(&left, *op, &right),
+            }
+        }
+        Expr::Function { name, .. } if is_aggregate(name) => {
+            Err(DbError::Unsupported(format!("聚合函数 {} 不能用在此处", name)))
+        }
+        Expr::Function { name, args, .. } => {
+            let args = args.iter().map(|arg| eval(arg, scope)).collect::<Result<Vec<_>, _>>()?;
+            call_function(name, &args)
+        }
+        Expr::IsNull { expr, negated } => Ok(Value::Bool(eval(expr, scope)?.is_null() != *negated)),
+        Expr::InList { expr, list, negated } => {
+            let value = eval(expr, scope)?;
+            let mut saw_null = false;
+            for item in list {
+                match compare(&value, BinaryOp::Eq, &eval(item, scope)?)? {
+                    Value::Bool(true) => return Ok(Value::Bool(!negated)),
+                    Value::Null => saw_null = true,
+                    _ => {}
+                }
+            }
+            // 未命中且列表中有 NULL 时结果未知
+            Ok(if saw_null { Value::Null } else { Value::Bool(*negated) })
+        }
+        Expr::Between { expr, low, high, negated } => {
+            let value = eval(expr, scope)?;
+            let lower = compare(&value, BinaryOp::GtEq, &eval(low, scope)?)?;
+            let upper = compare(&value, BinaryOp::LtEq, &eval(high, scope)?)?;
+            let within = match (truth(&lower)?, truth(&upper)?) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            };
+            Ok(match within {
+                Some(b) => Value::Bool(b != *negated),
+                None => Value::Null,
+            })
+        }
+        Expr::Like { expr, pattern, escape, negated, case_insensitive } => {
+            let value = eval(expr, scope)?;
+            let pattern = eval(pattern, scope)?;
+            let escape = match escape {
+                Some(escape) => eval(escape, scope)?,
+                None => Value::Text(String::new()),
+            };
+            if value.is_null() || pattern.is_null() || escape.is_null() {
+                return Ok(Value::Null);
+            }
+            let (mut text, mut pattern, mut escape) = (value.to_string(), pattern.to_string(), escape.to_string());
+            if *case_insensitive {
+                text = text.to_lowercase();
+                pattern = pattern.to_lowercase();
+                escape = escape.to_lowercase();
+            }
+            let mut chars = escape.chars();
+            let escape = match (chars.next(), chars.next()) {
+                (escape, None) => escape,
+                _ => return Err(DbError::InvalidQuery(format!("ESCAPE 必须是单个字符，而不是 '{}'", escape))),
+            };
+            Ok(Value::Bool(like(&text, &pattern, escape)? != *negated))
+        }
+        Expr::Subquery(query) => {
+            let output = single_column(subquery(query, scope)?, "标量子查询")?;
+            match output.rows.len() {
+                0 => Ok(Value::Null),
+                1 => Ok(output.rows.into_iter().next().and_then(|row| row.into_iter().next()).unwrap_or(Value::Null)),
+                n => Err(DbError::InvalidQuery(format!("标量子查询返回了 {} 行，最多只能返回一行", n))),
+            }
+        }
+        Expr::InSubquery { expr, query, negated } => {
+            let value = eval(expr, scope)?;
+            let output = single_column(subquery(query, scope)?, "IN 子查询")?;
+            let mut saw_null = false;
+            for row in &output.rows {
+                match compare(&value, BinaryOp::Eq, &row[0])? {
+                    Value::Bool(true) => return Ok(Value::Bool(!negated)),
+                    Value::Null => saw_null = true,
+                    _ => {}
+                }
+            }
+            Ok(if saw_null { Value::Null } else { Value::Bool(*negated) })
+        }
+        Expr::Exists(query) => Ok(Value::Bool(!subquery(query, scope)?.rows.is_empty())),
+    }
+}
+
+// 以当前行为外层作用域执行子查询
+fn subquery(query: &Query, scope: &Scope) -> Result<QueryOutput, DbError> {
+    match scope.db {
+        Some(db) => db.run_query(query, Some(scope)),
+        None => Err(DbError::Unsupported("此处不能使用子查询".to_string())),
+    }
+}
+
+fn single_column(output: QueryOutput, what: &str) -> Result<QueryOutput, DbError> {
+    if output.columns.len() == 1 {
+        Ok(output)
+    } else {
+        Err(DbError::InvalidQuery(format!("{}只能返回一列，实际返回 {} 列", what, output.columns.len())))
+    }
+}
+
+// 判断行是否满足条件，NULL 视为不满足
+pub fn is_true(expr: &Expr, scope: &Scope) -> Result<bool, DbError> {
+    Ok(truth(&eval(expr, scope)?)? == Some(true))
+}
+
+fn eval_literal(literal: &Literal) -> Result<Value, DbError> {
+    Ok(match literal {
+        Literal::Null => Value::Null,
+        Literal::Bool(b) => Value::Bool(*b),
+        // 整数为 Int，小数为精确的 Decimal，带指数的为 Float
+        Literal::Number(n) => parse_number(n).unwrap_or_else(|| Value::Text(n.clone())),
+        Literal::String(s) => Value::Text(s.clone()),
+        Literal::Blob(bytes) => Value::Blob(bytes.clone()),
+    })
+}
+
+// 取得布尔值，NULL 表示未知
+pub(crate) fn truth(value: &Value) -> Result<Option<bool>, DbError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Bool(b) => Ok(Some(*b)),
+        Value::Text(s) => parse_bool(s)
+            .map(Some)
+            .ok_or_else(|| DbError::TypeMismatch(format!("'{}' 不是布尔值", s))),
+        other => Err(DbError::TypeMismatch(format!("'{}' 不是布尔值", other))),
+    }
+}
+
+fn compare(left: &Value, op: BinaryOp, right: &Value) -> Result<Value, DbError> {
+    let ordering = match left.compare(right)? {
+        Some(ordering) => ordering,
+        None => return Ok(Value::Null),
+    };
+    let result = match op {
+        BinaryOp::Eq => ordering == Ordering::Equal,
+        BinaryOp::NotEq => ordering != Ordering::Equal,
+        BinaryOp::Lt => ordering == Ordering::Less,
+        BinaryOp::LtEq => ordering != Ordering::Greater,
+        BinaryOp::Gt => ordering == Ordering::Greater,
+        BinaryOp::GtEq => ordering != Ordering::Less,
+        _ => unreachable!("{} 不是比较运算符", op),
+    };
+    Ok(Value::Bool(result))
+}
+
+// 算术运算：两个整数时做整数运算，有浮点数时按浮点运算，否则按精确的 Decimal 运算；
+// 文本操作数先尝试转换为数字
+pub fn arithmetic(left: &Value, op: BinaryOp, right: &Value) -> Result<Value, DbError> {
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+    if left.is_temporal() || right.is_temporal() {
+        return temporal_arithmetic(left, op, right);
+    }
+    let (left, right) = (as_number(left)?, as_number(right)?);
+    let overflow = || DbError::Arithmetic(format!("{} {} {} 溢出", left, op, right));
+    let divide_by_zero = || DbError::Arithmetic("除数不能为零".to_string());
+    match (&left, &right) {
+        (Value::Int(a), Value::Int(b)) => {
+            let result = match op {
+                BinaryOp::Plus => a.checked_add(*b),
+                BinaryOp::Minus => a.checked_sub(*b),
+                BinaryOp::Multiply => a.checked_mul(*b),
+                BinaryOp::Divide | BinaryOp::Modulo if *b == 0 => return Err(divide_by_zero()),
+                BinaryOp::Divide => a.checked_div(*b),
+                BinaryOp::Modulo => a.checked_rem(*b),
+                _ => unreachable!("{} 不是算术运算符", op),
+            };
+            result.map(Value::Int).ok_or_else(overflow)
+        }
+        (Value::Float(_), _) | (_, Value::Float(_)) => {
+            let (a, b) = (to_f64(&left).unwrap_or(f64::NAN), to_f64(&right).unwrap_or(f64::NAN));
+            let result = match op {
+                BinaryOp::Plus => a + b,
+                BinaryOp::Minus => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide | BinaryOp::Modulo if b == 0.0 => return Err(divide_by_zero()),
+                BinaryOp::Divide => a / b,
+                BinaryOp::Modulo => a % b,
+                _ => unreachable!("{} 不是算术运算符", op),
+            };
+            if result.is_finite() {
+                Ok(Value::Float(result))
+            } else {
+                Err(overflow())
+            }
+        }
+        _ => {
+            let (Some(a), Some(b)) = (to_decimal(&left), to_decimal(&right)) else {
+                unreachable!("as_number 只返回数字");
+            };
+            let result = match op {
+                BinaryOp::Plus => a.checked_add(b),
+                BinaryOp::Minus => a.checked_sub(b),
+                BinaryOp::Multiply => a.checked_mul(b),
+                BinaryOp::Divide | BinaryOp::Modulo if b.is_zero() => return Err(divide_by_zero()),
+                BinaryOp::Divide => a.checked_div(b),
+                BinaryOp::Modulo => a.checked_rem(b),
+                _ => unreachable!("{} 不是算术运算符", op),
+            };
+            result.map(Value::Decimal).ok_or_else(overflow)
+        }
+    }
+}
+
+// 日期时间的运算：日期加减天数，时间点加减时间间隔，两个时间点相减得到间隔（日期相减得到天数），
+// 日期加时刻得到时间戳，时间间隔之间加减、与整数相乘或相除
+fn temporal_arithmetic(left: &Value, op: BinaryOp, right: &Value) -> Result<Value, DbError> {
+    use BinaryOp::{Divide, Minus, Multiply, Plus};
+    // 减去时间间隔即加上其相反数
+    let signed = |interval: &Interval| if op == Minus { interval.checked_neg() } else { Some(*interval) };
+    let result = match (left, op, right) {
+        (Value::Date(d), Plus, Value::Int(n)) | (Value::Int(n), Plus, Value::Date(d)) => {
+            d.add_days(*n).map(Value::Date)
+        }
+        (Value::Date(d), Minus, Value::Int(n)) => n.checked_neg().and_then(|n| d.add_days(n)).map(Value::Date),
+        (Value::Date(a), Minus, Value::Date(b)) => Some(Value::Int(a.days() - b.days())),
+        (Value::Date(d), Plus, Value::Time(t)) | (Value::Time(t), Plus, Value::Date(d)) => {
+            Some(Value::Timestamp(Timestamp::new(*d, *t)))
+        }
+        // 不含时分秒的间隔加在日期上仍为日期，否则为时间戳
+        (Value::Date(d), Plus | Minus, Value::Interval(i)) | (Value::Interval(i), Plus, Value::Date(d)) => {
+            signed(i).and_then(|i| match i.micros {
+                0 => d.add_months(i.months as i64)?.add_days(i.days as i64).map(Value::Date),
+                _ => Timestamp::new(*d, Time::from_micros(0)).add_interval(i).map(Value::Timestamp),
+            })
+        }
+        (Value::Timestamp(ts), Plus | Minus, Value::Interval(i)) | (Value::Interval(i), Plus, Value::Timestamp(ts)) => {
+            signed(i).and_then(|i| ts.add_interval(i)).map(Value::Timestamp)
+        }
+        (Value::TimestampTz(ts), Plus | Minus, Value::Interval(i))
+        | (Value::Interval(i), Plus, Value::TimestampTz(ts)) => {
+            signed(i).and_then(|i| ts.add_interval(i)).map(Value::TimestampTz)
+        }
+        (Value::Timestamp(a), Minus, Value::Timestamp(b)) | (Value::TimestampTz(a), Minus, Value::TimestampTz(b)) => {
+            a.since(*b).map(Value::Interval)
+        }
+        // 时刻只加减间隔中的时分秒，超出一天的部分绕回
+        (Value::Time(t), Plus | Minus, Value::Interval(i)) | (Value::Interval(i), Plus, Value::Time(t)) => {
+            signed(i).and_then(|i| Some(Value::Time(Time::from_micros(t.micros().checked_add(i.micros)?))))
+        }
+        (Value::Time(a), Minus, Value::Time(b)) => Some(Value::Interval(Interval {
+            micros: a.micros() - b.micros(),
+            ..Interval::default()
+        })),
+        (Value::Interval(a), Plus, Value::Interval(b)) => a.checked_add(*b).map(Value::Interval),
+        (Value::Interval(a), Minus, Value::Interval(b)) => a.checked_sub(*b).map(Value::Interval),
+        (Value::Interval(i), Multiply, Value::Int(n)) | (Value::Int(n), Multiply, Value::Interval(i)) => {
+            i.checked_mul(*n).map(Value::Interval)
+        }
+        (Value::Interval(_), Divide, Value::Int(0)) => return Err(DbError::Arithmetic("除数不能为零".to_string())),
+        (Value::Interval(i), Divide, Value::Int(n)) => i.checked_div(*n).map(Value::Interval),
+        _ => {
+            return Err(DbError::TypeMismatch(format!(
+                "无法计算 {} '{}' {} {} '{}'",
+                left.type_name(),
+                left,
+                op,
+                right.type_name(),
+                right
+            )))
+        }
+    };
+    result.ok_or_else(|| DbError::Arithmetic(format!("{} {} {} 超出范围", left, op, right)))
+}
+
+// 转换为 Int、Float 或 Decimal
+fn as_number(value: &Value) -> Result<Value, DbError> {
+    match value {
+        Value::Int(_) | Value::Float(_) | Value::Decimal(_) => Ok(value.clone()),
+        Value::Text(s) => parse_number(s).ok_or_else(|| DbError::TypeMismatch(format!("'{}' 不是数字", s.trim()))),
+        other => Err(DbError::TypeMismatch(format!("{} '{}' 不是数字", other.type_name(), other))),
+    }
+}
+
+fn as_int(value: &Value) -> Result<i64, DbError> {
+    match as_number(value)? {
+        Value::Int(i) => Ok(i),
+        other => Err(DbError::TypeMismatch(format!("'{}' 不是整数", other))),
+    }
+}
+
+// 是否为聚合函数名
+pub fn is_aggregate(name: &str) -> bool {
+    matches!(name, "COUNT" | "SUM" | "AVG" | "MIN" | "MAX" | "STRING_AGG" | "GROUP_CONCAT")
+}
+
+// 标量函数
+fn call_function(name: &str, args: &[Value]) -> Result<Value, DbError> {
+    let arity = |expected: usize| {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(DbError::TypeMismatch(format!("函数 {} 需要 {} 个参数，实际为 {}", name, expected, args.len())))
+        }
+    };
+    // COALESCE 之外的函数遇到 NULL 参数都返回 NULL
+    if name != "COALESCE" && args.iter().any(Value::is_null) {
+        return Ok(Value::Null);
+    }
+
+    match name {
+        "UPPER" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string().to_uppercase()))
+        }
+        "LOWER" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string().to_lowercase()))
+        }
+        "TRIM" => {
+            arity(1)?;
+            Ok(Value::Text(args[0].to_string().trim().to_string()))
+        }
+        // 文本按字符计，二进制数据按字节计
+        "LENGTH" => {
+            arity(1)?;
+            match &args[0] {
+                Value::Blob(bytes) => Ok(Value::Int(bytes.len() as i64)),
+                value => Ok(Value::Int(value.to_string().chars().count() as i64)),
+            }
+        }
+        "ABS" => {
+            arity(1)?;
+            let overflow = || DbError::Arithmetic(format!("ABS({}) 溢出", args[0]));
+            match as_number(&args[0])? {
+                Value::Int(n) => n.checked_abs().map(Value::Int).ok_or_else(overflow),
+                Value::Decimal(d) => d.checked_abs().map(Value::Decimal).ok_or_else(overflow),
+                n => Ok(Value::Float(to_f64(&n).unwrap_or(f64::NAN).abs())),
+            }
+        }
+        "ROUND" => {
+            if args.len() != 1 && args.len() != 2 {
+                return Err(DbError::TypeMismatch(format!("函数 {} 需要 1 或 2 个参数", name)));
+            }
+            // 保留 digits 位小数，四舍五入（远离零）
+            let digits = match args.get(1) {
+                Some(digits) => u32::try_from(as_int(digits)?)
+                    .map_err(|_| DbError::TypeMismatch(format!("{} 的小数位数不能为负数", name)))?,
+                None => 0,
+            };
+            let overflow = || DbError::Arithmetic(format!("ROUND({}) 溢出", args[0]));
+            match as_number(&args[0])? {
+                Value::Int(n) => Ok(Value::Int(n)),
+                Value::Decimal(d) => d
+                    .round_to(digits.min(d.scale()))
+                    .map(Value::Decimal)
+                    .ok_or_else(overflow),
+                n => {
+                    let x = to_f64(&n).unwrap_or(f64::NAN);
+                    let factor = 10f64.powi(digits.min(308) as i32);
+                    let rounded = (x * factor).round() / factor;
+                    Ok(Value::Float(if rounded.is_finite() { rounded } else { x }))
+                }
+            }
+        }
+        "SUBSTR" | "SUBSTRING" => {
+            if args.len() != 2 && args.len() != 3 {
+                return Err(DbError::TypeMismatch(format!("函数 {} 需要 2 或 3 个参数", name)));
+            }
+            // 起始位置从 1 开始，按字符计数
+            let text: Vec<char> = args[0].to_string().chars().collect();
+            let start = as_int(&args[1])?.max(1) as usize - 1;
+            let len = match args.get(2) {
+                Some(len) => as_int(len)?.max(0) as usize,
+                None => text.len(),
+            };
+            Ok(Value::Text(text.iter().skip(start).take(len).collect()))
+        }
+        "COALESCE" => Ok(args.iter().find(|v| !v.is_null()).cloned().unwrap_or(Value::Null)),
+        // 当前时间按 UTC 计
+        "NOW" | "CURRENT_TIMESTAMP" => {
+            arity(0)?;
+            Ok(Value::TimestampTz(Timestamp::now()))
+        }
+        "CURRENT_DATE" => {
+            arity(0)?;
+            Ok(Value::Date(Timestamp::now().date()))
+        }
+        "CURRENT_TIME" => {
+            arity(0)?;
+            Ok(Value::Time(Timestamp::now().time()))
+        }
+        // DATE '2024-01-31' 等带类型的字面量也解析为这些函数
+        "DATE" | "TIME" | "TIMESTAMP" | "TIMESTAMPTZ" => {
+            arity(1)?;
+            let data_type = match name {
+                "DATE" => DataType::Date,
+                "TIME" => DataType::Time,
+                "TIMESTAMP" => DataType::Timestamp,
+                _ => DataType::TimestampTz,
+            };
+            args[0].clone().convert(&data_type)
+        }
+        // INTERVAL '1 day' 或 INTERVAL 3 HOUR
+        "INTERVAL" => match args {
+            [text] => text.clone().convert(&DataType::Interval),
+            [amount, unit] => Interval::from_unit(as_int(amount)?, &unit.to_string())
+                .map(Value::Interval)
+                .ok_or_else(|| DbError::TypeMismatch(format!("无效的时间间隔 {} {}", amount, unit))),
+            _ => Err(DbError::TypeMismatch(format!("函数 {} 需要 1 或 2 个参数", name))),
+        },
+        "DATE_ADD" | "DATE_SUB" => {
+            arity(2)?;
+            let op = if name == "DATE_ADD" { BinaryOp::Plus } else { BinaryOp::Minus };
+            arithmetic(&args[0], op, &args[1])
+        }
+        "DATE_TRUNC" => {
+            arity(2)?;
+            let unit = args[0].to_string().to_uppercase();
+            let truncate = |ts: Timestamp| {
+                ts.truncate(&unit)
+                    .ok_or_else(|| DbError::TypeMismatch(format!("{} 不支持的单位 '{}'", name, args[0])))
+            };
+            match to_temporal(&args[1])? {
+                Value::Date(d) => Ok(Value::Date(truncate(Timestamp::new(d, Time::from_micros(0)))?.date())),
+                Value::Timestamp(ts) => Ok(Value::Timestamp(truncate(ts)?)),
+                Value::TimestampTz(ts) => Ok(Value::TimestampTz(truncate(ts)?)),
+                other => Err(DbError::TypeMismatch(format!("{} '{}' 不是日期或时间戳", other.type_name(), other))),
+            }
+        }
+        // EXTRACT(YEAR FROM d) 解析为 EXTRACT('YEAR', d)
+        "EXTRACT" | "DATE_PART" => {
+            arity(2)?;
+            extract(&args[0].to_string().to_uppercase(), &to_temporal(&args[1])?)
+        }
+        // 文本按 UTF-8 编码
+        "TO_BASE64" | "HEX" => {
+            arity(1)?;
+            let bytes = match &args[0] {
+                Value::Blob(bytes) => bytes.clone(),
+                value => value.to_string().into_bytes(),
+            };
+            Ok(Value::Text(if name == "HEX" { blob::to_hex(&bytes) } else { blob::encode_base64(&bytes) }))
+        }
+        "FROM_BASE64" | "UNHEX" => {
+            arity(1)?;
+            let text = args[0].to_string();
+            let bytes = if name == "UNHEX" { blob::parse_hex(&text) } else { blob::decode_base64(&text) };
+            bytes
+                .map(Value::Blob)
+                .ok_or_else(|| DbError::TypeMismatch(format!("{} 的参数 '{}' 不是有效的编码", name, text)))
+        }
+        // 路径不存在时为 NULL
+        "JSON_EXTRACT" => {
+            arity(2)?;
+            let document = json::as_json(&args[0])?;
+            Ok(match json::extract(&document, &args[1].to_string())? {
+                Some(found) => Value::Json(found.clone()),
+                None => Value::Null,
+            })
+        }
+        // JSON_ARRAY_LENGTH(json[, path])，目标不是数组时为 NULL
+        "JSON_ARRAY_LENGTH" => {
+            if args.len() != 1 && args.len() != 2 {
+                return Err(DbError::TypeMismatch(format!("函数 {} 需要 1 或 2 个参数，实际为 {}", name, args.len())));
+            }
+            let document = json::as_json(&args[0])?;
+            let target = match args.get(1) {
+                Some(path) => json::extract(&document, &path.to_string())?,
+                None => Some(&*document),
+            };
+            Ok(match target.and_then(|t| t.as_array()) {
+                Some(array) => Value::Int(array.len() as i64),
+                None => Value::Null,
+            })
+        }
+        _ => Err(DbError::Unsupported(format!("未知函数 {}", name))),
+    }
+}
+
+// json -> key 取得 JSON 值，json ->> key 取得对应的 SQL 值（字符串不带引号）；键不存在时为 NULL
+fn json_access(document: &Value, op: BinaryOp, key: &Value) -> Result<Value, DbError> {
+    if document.is_null() || key.is_null() {
+        return Ok(Value::Null);
+    }
+    let document = json::as_json(document)?;
+    Ok(match json::get(&document, key)? {
+        Some(found) if op == BinaryOp::JsonGetText => json::to_sql(found),
+        Some(found) => Value::Json(found.clone()),
+        None => Value::Null,
+    })
+}
+
+// 文本按时间戳解析，其余值不变
+fn to_temporal(value: &Value) -> Result<Value, DbError> {
+    match value {
+        Value::Text(_) => value.clone().convert(&DataType::Timestamp),
+        value => Ok(value.clone()),
+    }
+}
+
+// 从日期时间值中取出 field，秒和 EPOCH 为精确到微秒的 Decimal，其余为整数
+fn extract(field: &str, value: &Value) -> Result<Value, DbError> {
+    let unsupported = || DbError::TypeMismatch(format!("无法从 {} '{}' 中提取 {}", value.type_name(), value, field));
+    let seconds = |micros: i128| Value::Decimal(Decimal::new(micros, 6).normalized());
+    if let Value::Interval(i) = value {
+        return Ok(match field {
+            "YEAR" => Value::Int(i.months as i64 / 12),
+            "MONTH" => Value::Int(i.months as i64 % 12),
+            "DAY" => Value::Int(i.days as i64),
+            "HOUR" => Value::Int(i.micros / MICROS_PER_HOUR),
+            "MINUTE" => Value::Int(i.micros % MICROS_PER_HOUR / MICROS_PER_MINUTE),
+            "SECOND" => seconds((i.micros % MICROS_PER_MINUTE) as i128),
+            "EPOCH" => seconds(i.total_micros()),
+            _ => return Err(unsupported()),
+        });
+    }
+    // 日期视为当天零点
+    let (date, time) = match value {
+        Value::Date(d) => (Some(*d), Time::from_micros(0)),
+        Value::Time(t) => (None, *t),
+        Value::Timestamp(ts) | Value::TimestampTz(ts) => (Some(ts.date()), ts.time()),
+        _ => return Err(unsupported()),
+    };
+    let result = match (field, date) {
+        ("YEAR", Some(d)) => d.ymd().0 as i64,
+        ("QUARTER", Some(d)) => (d.ymd().1 as i64 - 1) / 3 + 1,
+        ("MONTH", Some(d)) => d.ymd().1 as i64,
+        ("WEEK", Some(d)) => d.iso_week() as i64,
+        ("DAY", Some(d)) => d.ymd().2 as i64,
+        ("DOW", Some(d)) => d.weekday() as i64,
+        ("DOY", Some(d)) => d.day_of_year() as i64,
+        ("HOUR", _) => time.hour(),
+        ("MINUTE", _) => time.minute(),
+        ("SECOND", _) => return Ok(seconds(time.second_micros() as i128)),
+        ("EPOCH", _) => {
+            let days = date.map_or(0, |d| d.days());
+            return Ok(seconds(days as i128 * MICROS_PER_DAY as i128 + time.micros() as i128));
+        }
+        _ => return Err(unsupported()),
+    };
+    Ok(Value::Int(result))
+}
+
+// LIKE 模式中的一项
+#[derive(Clone, Copy, PartialEq)]
+enum LikeItem {
+    Any,        // %
+    One,        // _
+    Char(char), // 普通字符，或转义后的 %、_ 和转义字符本身
+}
+
+// LIKE 匹配：% 匹配任意长度字符，_ 匹配单个字符；escape 之后的一个字符按原样匹配
+fn like(text: &str, pattern: &str, escape: Option<char>) -> Result<bool, DbError> {
+    let text: Vec<char> = text.chars().collect();
+    let mut items = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        items.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(escaped) => LikeItem::Char(escaped),
+                None => {
+                    return Err(DbError::InvalidQuery(format!(
+                        "LIKE 模式 '{}' 不能以转义字符结尾",
+                        pattern
+                    )))
+                }
+            },
+            '%' => LikeItem::Any,
+            '_' => LikeItem::One,
+            c => LikeItem::Char(c),
+        });
+    }
+    // matched[j] 表示模式的前 j 项能否匹配当前已处理的文本前缀
+    let mut matched = vec![false; items.len() + 1];
+    matched[0] = true;
+    for j in 1..=items.len() {
+        matched[j] = matched[j - 1] && items[j - 1] == LikeItem::Any;
+    }
+    for &c in &text {
+        let mut next = vec![false; items.len() + 1];
+        for j in 1..=items.len() {
+            next[j] = match items[j - 1] {
+                LikeItem::Any => next[j - 1] || matched[j],
+                LikeItem::One => matched[j - 1],
+                LikeItem::Char(p) => matched[j - 1] && p == c,
+            };
+        }
+        matched = next;
+    }
+    Ok(matched[items.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::part1::{Database, DbError};
+
+    fn database() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (n INT)").unwrap();
+        db.execute("CREATE TABLE u (n INT, k INT)").unwrap();
+        db
+    }
+
+    fn not_found(db: &mut Database, sql: &str) {
+        match db.execute(sql) {
+            Err(DbError::ColumnNotFound(_)) => {}
+            other => panic!("{}: {:?}", sql, other),
+        }
+    }
+
+    #[test]
+    fn unknown_columns_are_reported_on_empty_tables() {
+        let mut db = database();
+        for sql in [
+            "SELECT n FROM t WHERE nope = 1",
+            "DELETE FROM t WHERE nope = 1",
+            "UPDATE t SET n = nope",
+            "UPDATE t SET n = 1 WHERE nope IS NULL",
+            "SELECT n FROM t ORDER BY nope",
+            "SELECT n FROM t GROUP BY nope",
+            "SELECT COUNT(*) FROM t HAVING MAX(nope) > 1",
+            "SELECT * FROM t JOIN u ON t.n = u.nope",
+            "SELECT x.n FROM (SELECT nope AS n FROM t) AS x",
+            "SELECT n FROM t UNION SELECT n FROM u ORDER BY k",
+        ] {
+            not_found(&mut db, sql);
+        }
+    }
+
+    #[test]
+    fn unknown_columns_are_reported_despite_short_circuit() {
+        let mut db = database();
+        db.execute("INSERT INTO t VALUES (1), (2)").unwrap();
+        not_found(&mut db, "SELECT n FROM t WHERE n = 1 OR nope = 1");
+        not_found(&mut db, "SELECT n FROM t WHERE n > 0 OR nope = 1");
+        // 子查询即使从未执行也会被检查，其中可以引用外层的列
+        not_found(&mut db, "SELECT n FROM t WHERE n > 0 OR EXISTS (SELECT nope FROM u)");
+        not_found(&mut db, "SELECT n FROM t WHERE n > 0 OR n IN (SELECT k FROM u WHERE u.k = t.nope)");
+        let result = db.execute("SELECT n FROM t WHERE n > 1 OR n IN (SELECT k FROM u WHERE u.n = t.n)").unwrap();
+        assert_eq!(result.rows.len(), 1);
+    }
+
+    fn strings(db: &mut Database, sql: &str) -> Vec<String> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values[0].to_string()).collect()
+    }
+
+    #[test]
+    fn like_escape_matches_literal_wildcards() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE p (s VARCHAR(20))").unwrap();
+        db.execute("INSERT INTO p VALUES ('100%'), ('100'), ('a_b'), ('axb')").unwrap();
+        assert_eq!(strings(&mut db, "SELECT s FROM p WHERE s LIKE '%!%' ESCAPE '!'"), ["100%"]);
+        assert_eq!(strings(&mut db, "SELECT s FROM p WHERE s LIKE 'a!_b' ESCAPE '!'"), ["a_b"]);
+        assert_eq!(strings(&mut db, "SELECT s FROM p WHERE s LIKE 'a_b'"), ["a_b", "axb"]);
+        assert_eq!(strings(&mut db, "SELECT s FROM p WHERE s ILIKE 'A!_B' ESCAPE '!'"), ["a_b"]);
+        // 转义字符本身重复两次时按原样匹配
+        assert_eq!(strings(&mut db, "SELECT s FROM p WHERE '1!' LIKE '_!!' ESCAPE '!' AND s = '100'"), ["100"]);
+        assert!(db.execute("SELECT s FROM p WHERE s LIKE 'a!' ESCAPE '!'").is_err());
+        assert!(db.execute("SELECT s FROM p WHERE s LIKE 'a' ESCAPE '!!'").is_err());
+
+        // 保存为 SQL 文本的 CHECK 条件保留 ESCAPE
+        db.execute("CREATE TABLE c (code VARCHAR(10) CHECK (code LIKE 'X!_%' ESCAPE '!'))").unwrap();
+        db.execute("INSERT INTO c VALUES ('X_1')").unwrap();
+        assert!(db.execute("INSERT INTO c VALUES ('XY1')").is_err());
+    }
+}
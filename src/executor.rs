@@ -0,0 +1,942 @@
+use crate::aggregate::{collect_aggregates, contains_aggregate, grouped_scope, rewrite, Groups};
+use crate::ast::*;
+use crate::eval::{eval, is_true, resolve_column, resolve_columns, row_values, table_scope, Scope, ScopeColumn};
+use crate::join::{build_relation, relation_columns};
+use crate::constraints::multiple_primary_keys;
+use crate::part1::{Column, DataType, Database, DbError, QueryResult, ResultColumn, Row, Table};
+use crate::value::{fit_length, values_key, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+impl Database {
+    // 执行已解析的语句
+    pub(crate) fn execute_statement(&mut self, statement: Statement) -> Result<QueryResult, DbError> {
+        match statement {
+            Statement::Query(query) => self.execute_query(&query),
+            Statement::Insert(insert) => self.execute_insert(insert),
+            Statement::Update(update) => self.execute_update(update),
+            Statement::Delete(delete) => self.execute_delete(delete),
+            Statement::CreateTable(create) => self.execute_create_table(create),
+            Statement::DropTable(drop) => self.execute_drop_table(drop),
+            Statement::AlterTable(alter) => self.execute_alter_table(alter),
+        }
+    }
+
+    fn execute_query(&self, query: &Query) -> Result<QueryResult, DbError> {
+        self.resolve_query(query, None)?;
+        let output = self.run_query(query, None)?;
+        let rows: Vec<Row> = output
+            .rows
+            .into_iter()
+            .map(|values| Row {
+                values: values
+                    .into_iter()
+                    .zip(&output.columns)
+                    .map(|(value, col)| output_value(value, col.data_type.as_ref()))
+                    .collect(),
+            })
+            .collect();
+        Ok(QueryResult {
+            columns: output.columns,
+            message: format!("共 {} 行", rows.len()),
+            rows,
+            affected_rows: 0,
+        })
+    }
+
+    // 在执行之前解析查询中的全部列引用，包括派生表、ON 条件和各层子查询，不读取任何行。
+    // 找不到的列总会报错，而不取决于表中有没有行或条件是否短路。返回结果列
+    pub(crate) fn resolve_query(&self, query: &Query, outer: Option<&Scope>) -> Result<Vec<ResultColumn>, DbError> {
+        match query {
+            Query::Select(select) => {
+                let scope_columns = relation_columns(self, &select.from, outer)?;
+                let (exprs, columns) = projection(select, &scope_columns, outer)?;
+                let sort_inputs = sort_inputs(&select.order_by, &columns)?;
+                let exprs = select
+                    .selection
+                    .iter()
+                    .chain(&exprs)
+                    .chain(&select.group_by)
+                    .chain(&select.having)
+                    .chain(&sort_inputs);
+                for expr in exprs {
+                    self.resolve_expr(expr, &scope_columns, outer)?;
+                }
+                Ok(columns)
+            }
+            Query::SetOperation(set) => {
+                let columns = self.resolve_query(&set.left, outer)?;
+                self.resolve_query(&set.right, outer)?;
+                let scope_columns = output_scope(&columns);
+                for expr in &sort_inputs(&set.order_by, &columns)? {
+                    self.resolve_expr(expr, &scope_columns, outer)?;
+                }
+                Ok(columns)
+            }
+        }
+    }
+
+    // 解析表达式中的列引用，其中的子查询以 columns 所在的行为外层解析
+    pub(crate) fn resolve_expr(&self, expr: &Expr, columns: &[ScopeColumn], outer: Option<&Scope>) -> Result<(), DbError> {
+        resolve_columns(expr, columns, outer)?;
+        let mut subqueries = Vec::new();
+        expr.visit(&mut |expr| {
+            if let Expr::Subquery(query) | Expr::Exists(query) | Expr::InSubquery { query, .. } = expr {
+                subqueries.push(query);
+            }
+        });
+        let values = vec![Value::Null; columns.len()];
+        let scope = Scope {
+            columns,
+            values: &values,
+            outer,
+            db: Some(self),
+        };
+        for query in subqueries {
+            self.resolve_query(query, Some(&scope))?;
+        }
+        Ok(())
+    }
+
+    // 执行查询，outer 为外层查询的当前行，用于相关子查询
+    pub(crate) fn run_query(&self, query: &Query, outer: Option<&Scope>) -> Result<QueryOutput, DbError> {
+        match query {
+            Query::Select(select) => self.run_select(select, outer),
+            Query::SetOperation(set) => self.run_set_operation(set, outer),
+        }
+    }
+
+    fn run_select(&self, select: &Select, outer: Option<&Scope>) -> Result<QueryOutput, DbError> {
+        let relation = build_relation(self, &select.from, outer)?;
+        let mut rows = Vec::new();
+        let columns = self.select_rows(select, relation.columns, relation.rows.into_iter().map(Ok), outer, &mut |output, keys| {
+            rows.push((output, keys));
+            Ok(())
+        })?;
+        Ok(QueryOutput {
+            columns,
+            rows: sort_and_limit(rows, &select.order_by, select.offset, select.limit),
+        })
+    }
+
+    // 对 FROM 子句的各行依次做 WHERE 过滤、分组和聚合、HAVING、投影和 DISTINCT，
+    // 每个结果行连同它的排序键交给 sink，排序和 OFFSET、LIMIT 由调用者完成。
+    // 行逐个读取，除各组的中间结果和 DISTINCT 见过的值以外不在内存中保留，分页存储借此处理超出内存的表
+    pub(crate) fn select_rows(
+        &self,
+        select: &Select,
+        scope_columns: Vec<ScopeColumn>,
+        rows: impl Iterator<Item = Result<Vec<Value>, DbError>>,
+        outer: Option<&Scope>,
+        sink: &mut OutputSink,
+    ) -> Result<Vec<ResultColumn>, DbError> {
+        let env = Scope {
+            outer,
+            db: Some(self),
+            ..Scope::empty()
+        };
+        if select.selection.as_ref().is_some_and(contains_aggregate) {
+            return Err(DbError::InvalidQuery("WHERE 子句中不能使用聚合函数".to_string()));
+        }
+        let (mut exprs, columns) = projection(select, &scope_columns, outer)?;
+
+        // ORDER BY 中的列名和序号优先匹配输出列，其余按输入行求值
+        let mut sort_keys: Vec<SortKey> = select
+            .order_by
+            .iter()
+            .map(|order| sort_key(&order.expr, &columns))
+            .collect::<Result<_, _>>()?;
+
+        // 有 GROUP BY、HAVING 或聚合函数时先分组，之后的表达式改写为引用分组结果
+        let mut having = select.having.clone();
+        let sort_exprs = || {
+            sort_keys.iter().filter_map(|key| match key {
+                SortKey::Input(expr) => Some(expr),
+                SortKey::Output(_) => None,
+            })
+        };
+        let grouped = !select.group_by.is_empty()
+            || having.is_some()
+            || exprs.iter().chain(sort_exprs()).any(contains_aggregate);
+        let mut aggregates = Vec::new();
+        let mut output_columns = scope_columns.clone();
+        if grouped {
+            if select.group_by.iter().any(contains_aggregate) {
+                return Err(DbError::InvalidQuery("GROUP BY 子句中不能使用聚合函数".to_string()));
+            }
+            for expr in exprs.iter().chain(having.iter()).chain(sort_exprs()) {
+                collect_aggregates(expr, &mut aggregates)?;
+            }
+            output_columns = grouped_scope(&select.group_by, &scope_columns, aggregates.len());
+            let rewrite = |expr: &Expr| rewrite(expr, &select.group_by, &aggregates, &scope_columns);
+            exprs = exprs.iter().map(rewrite).collect::<Result<_, _>>()?;
+            having = having.as_ref().map(rewrite).transpose()?;
+            for key in &mut sort_keys {
+                if let SortKey::Input(expr) = key {
+                    *expr = rewrite(expr)?;
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut emit = |values: &[Value]| -> Result<(), DbError> {
+            let scope = Scope { columns: &output_columns, values, ..env };
+            if !matches(having.as_ref(), &scope)? {
+                return Ok(());
+            }
+            let output = exprs
+                .iter()
+                .map(|expr| eval(expr, &scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            if select.distinct && !seen.insert(values_key(&output)) {
+                return Ok(());
+            }
+            let keys = sort_keys
+                .iter()
+                .map(|key| match key {
+                    SortKey::Output(i) => Ok(output[*i].clone()),
+                    SortKey::Input(expr) => eval(expr, &scope),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            sink(output, keys)
+        };
+
+        let mut groups = if grouped {
+            Some(Groups::new(&select.group_by, &aggregates, &scope_columns)?)
+        } else {
+            None
+        };
+        for values in rows {
+            let values = values?;
+            let scope = Scope { columns: &scope_columns, values: &values, ..env };
+            if !matches(select.selection.as_ref(), &scope)? {
+                continue;
+            }
+            match &mut groups {
+                Some(groups) => groups.add(&values, &env)?,
+                None => emit(&values)?,
+            }
+        }
+        if let Some(groups) = groups {
+            for values in groups.finish()? {
+                emit(&values)?;
+            }
+        }
+        Ok(columns)
+    }
+
+    // 集合运算：两侧列数必须相同，对应列的类型必须兼容，结果列名取左侧
+    fn run_set_operation(&self, set: &SetOperation, outer: Option<&Scope>) -> Result<QueryOutput, DbError> {
+        let left = self.run_query(&set.left, outer)?;
+        let right = self.run_query(&set.right, outer)?;
+        if left.columns.len() != right.columns.len() {
+            return Err(DbError::InvalidQuery(format!(
+                "{} 两侧的列数不同：左侧 {} 列，右侧 {} 列",
+                set.op,
+                left.columns.len(),
+                right.columns.len()
+            )));
+        }
+
+        let mut columns = left.columns;
+        for (i, (l, r)) in columns.iter_mut().zip(&right.columns).enumerate() {
+            if let (Some(a), Some(b)) = (column_kind(l, &left.rows, i), column_kind(r, &right.rows, i)) {
+                if a != b {
+                    return Err(DbError::TypeMismatch(format!(
+                        "{} 第 {} 列的类型不兼容：{} 与 {}",
+                        set.op,
+                        i + 1,
+                        a,
+                        b
+                    )));
+                }
+            }
+            if l.data_type != r.data_type {
+                l.data_type = None;
+            }
+        }
+
+        // 不带 ALL 时结果去重；带 ALL 时 INTERSECT/EXCEPT 按出现次数匹配
+        let mut rows = Vec::new();
+        let mut seen = HashSet::new();
+        match set.op {
+            SetOperator::Union => {
+                for values in left.rows.into_iter().chain(right.rows) {
+                    if set.all || seen.insert(values_key(&values)) {
+                        rows.push(values);
+                    }
+                }
+            }
+            SetOperator::Intersect | SetOperator::Except => {
+                let mut right_counts: HashMap<_, usize> = HashMap::new();
+                for values in &right.rows {
+                    *right_counts.entry(values_key(values)).or_default() += 1;
+                }
+                for values in left.rows {
+                    let key = values_key(&values);
+                    let in_right = match right_counts.get_mut(&key) {
+                        Some(count) if *count > 0 => {
+                            if set.all {
+                                *count -= 1;
+                            }
+                            true
+                        }
+                        _ => false,
+                    };
+                    let keep = in_right == (set.op == SetOperator::Intersect);
+                    if keep && (set.all || seen.insert(key)) {
+                        rows.push(values);
+                    }
+                }
+            }
+        }
+
+        // 排序键只能引用输出列
+        let scope_columns = output_scope(&columns);
+        let sort_keys = set
+            .order_by
+            .iter()
+            .map(|order| sort_key(&order.expr, &columns))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut output_rows = Vec::with_capacity(rows.len());
+        for values in rows {
+            let scope = Scope {
+                columns: &scope_columns,
+                values: &values,
+                outer,
+                db: Some(self),
+            };
+            let keys = sort_keys
+                .iter()
+                .map(|key| match key {
+                    SortKey::Output(i) => Ok(values[*i].clone()),
+                    SortKey::Input(expr) => eval(expr, &scope),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            output_rows.push((values, keys));
+        }
+
+        Ok(QueryOutput {
+            columns,
+            rows: sort_and_limit(output_rows, &set.order_by, set.offset, set.limit),
+        })
+    }
+
+    // 先只读地求出新行（表达式中可能有子查询），再写入表
+    fn execute_insert(&mut self, insert: Insert) -> Result<QueryResult, DbError> {
+        let table = self.table(&insert.table)?;
+        let scope = Scope {
+            db: Some(self),
+            ..Scope::empty()
+        };
+
+        // 每个 VALUES 项对应的列下标
+        let targets: Vec<usize> = match &insert.columns {
+            Some(names) => names
+                .iter()
+                .map(|name| table.column_index(name))
+                .collect::<Result<_, _>>()?,
+            None => (0..table.columns.len()).collect(),
+        };
+        // 未指定的列取默认值
+        let defaults: Vec<Value> = table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, col)| if targets.contains(&i) { Ok(Value::Null) } else { col.default_value() })
+            .collect::<Result<_, _>>()?;
+
+        for expr in insert.rows.iter().flatten() {
+            self.resolve_expr(expr, &[], None)?;
+        }
+        let mut new_rows = Vec::with_capacity(insert.rows.len());
+        let mut truncated = 0;
+        for exprs in &insert.rows {
+            if exprs.len() != targets.len() {
+                return Err(DbError::ColumnCountMismatch {
+                    expected: targets.len(),
+                    found: exprs.len(),
+                });
+            }
+            let mut values = defaults.clone();
+            for (expr, &idx) in exprs.iter().zip(&targets) {
+                values[idx] = eval(expr, &scope)?;
+            }
+            let mut row = Row { values };
+            truncated += table.conform_row(&mut row, self.length_policy)?;
+            new_rows.push(row);
+        }
+
+        let rows: Vec<&Row> = new_rows.iter().collect();
+        table.check_constraints(&rows, &[])?;
+        self.check_references(table, &rows, &[])?;
+
+        let count = new_rows.len();
+        self.table_mut(&insert.table)?.rows.extend(new_rows);
+        Ok(QueryResult {
+            affected_rows: count,
+            message: with_truncation_warning(format!("已插入 {} 条记录", count), truncated),
+            ..Default::default()
+        })
+    }
+
+    fn execute_update(&mut self, update: Update) -> Result<QueryResult, DbError> {
+        let table = self.table(&update.table)?;
+        let assignments: Vec<(usize, &Expr)> = update
+            .assignments
+            .iter()
+            .map(|(name, expr)| Ok((table.column_index(name)?, expr)))
+            .collect::<Result<_, DbError>>()?;
+
+        let scope_columns = table_scope(table, &table.name);
+        for expr in update.selection.iter().chain(assignments.iter().map(|(_, expr)| *expr)) {
+            self.resolve_expr(expr, &scope_columns, None)?;
+        }
+        let mut updated = Vec::new();
+        let mut truncated = 0;
+        for (i, row) in table.rows.iter().enumerate() {
+            let values = row_values(table, row);
+            let scope = Scope {
+                columns: &scope_columns,
+                values: &values,
+                outer: None,
+                db: Some(self),
+            };
+            if matches(update.selection.as_ref(), &scope)? {
+                let mut new_row = row.clone();
+                new_row.values.resize(table.columns.len(), Value::Null);
+                for (idx, expr) in &assignments {
+                    new_row.values[*idx] = eval(expr, &scope)?;
+                }
+                truncated += table.conform_row(&mut new_row, self.length_policy)?;
+                updated.push((i, new_row));
+            }
+        }
+
+        let indices: Vec<usize> = updated.iter().map(|(i, _)| *i).collect();
+        let rows: Vec<&Row> = updated.iter().map(|(_, row)| row).collect();
+        table.check_constraints(&rows, &indices)?;
+
+        let count = updated.len();
+        self.update_rows(&update.table, updated)?;
+        Ok(QueryResult {
+            affected_rows: count,
+            message: with_truncation_warning(format!("已更新 {} 条记录", count), truncated),
+            ..Default::default()
+        })
+    }
+
+    fn execute_delete(&mut self, delete: Delete) -> Result<QueryResult, DbError> {
+        let table = self.table(&delete.table)?;
+        let scope_columns = table_scope(table, &table.name);
+        if let Some(selection) = &delete.selection {
+            self.resolve_expr(selection, &scope_columns, None)?;
+        }
+        let mut deleted = Vec::new();
+        for (i, row) in table.rows.iter().enumerate() {
+            let values = row_values(table, row);
+            let scope = Scope {
+                columns: &scope_columns,
+                values: &values,
+                outer: None,
+                db: Some(self),
+            };
+            if matches(delete.selection.as_ref(), &scope)? {
+                deleted.push(i);
+            }
+        }
+
+        let count = deleted.len();
+        self.delete_rows(&delete.table, &deleted)?;
+        Ok(QueryResult {
+            affected_rows: count,
+            message: format!("已删除 {} 条记录", count),
+            ..Default::default()
+        })
+    }
+
+    fn execute_create_table(&mut self, create: CreateTable) -> Result<QueryResult, DbError> {
+        if create.if_not_exists && self.table(&create.name).is_ok() {
+            return Ok(QueryResult {
+                message: format!("表 '{}' 已存在，已跳过", create.name),
+                ..Default::default()
+            });
+        }
+        // 主键只能以一种方式定义：单列上的 PRIMARY KEY，或表级的 PRIMARY KEY (col, ...)
+        let column_keys = create.columns.iter().filter(|def| def.primary_key).count();
+        if column_keys > 1 || (column_keys == 1 && !create.primary_key.is_empty()) {
+            return Err(multiple_primary_keys(&create.name));
+        }
+        if create.columns.is_empty() {
+            return Err(DbError::InvalidQuery(format!("表 '{}' 至少需要一列", create.name)));
+        }
+        let mut foreign_keys = create.foreign_keys;
+        foreign_keys.extend(create.columns.iter().filter_map(|def| def.references.clone()));
+        let mut columns: Vec<Column> = create
+            .columns
+            .into_iter()
+            .map(column_from_def)
+            .collect::<Result<_, _>>()?;
+        for name in &create.primary_key {
+            let column = columns
+                .iter_mut()
+                .find(|c| c.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| DbError::ColumnNotFound(name.clone()))?;
+            if column.is_primary_key {
+                return Err(DbError::InvalidQuery(format!("主键中的列 '{}' 重复", name)));
+            }
+            column.is_primary_key = true;
+        }
+        let mut table = Table {
+            name: create.name.clone(),
+            columns,
+            rows: Vec::new(),
+            foreign_keys: Vec::new(),
+        };
+        table.validate_checks()?;
+        for fk in foreign_keys {
+            let fk = self.resolve_foreign_key(&table, fk)?;
+            table.foreign_keys.push(fk);
+        }
+        self.create_table(table)?;
+        Ok(QueryResult {
+            message: format!("已创建表 {}", create.name),
+            ..Default::default()
+        })
+    }
+
+    fn execute_drop_table(&mut self, drop: DropTable) -> Result<QueryResult, DbError> {
+        match self.drop_table(&drop.name) {
+            Ok(table) => Ok(QueryResult {
+                message: format!("已删除表 {}", table.name),
+                ..Default::default()
+            }),
+            Err(DbError::TableNotFound(_)) if drop.if_exists => Ok(QueryResult {
+                message: format!("表 '{}' 不存在，已跳过", drop.name),
+                ..Default::default()
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn execute_alter_table(&mut self, alter: AlterTable) -> Result<QueryResult, DbError> {
+        let message = match alter.action {
+            AlterAction::AddColumn(def) => {
+                // 修改表结构在副本上进行，所有行仍满足约束时才替换原表
+                let references = def.references.clone();
+                let column = column_from_def(def)?;
+                let mut table = self.table(&alter.name)?.clone();
+                let message = format!("已在表 {} 中添加列 {}", table.name, column.name);
+                table.add_column(column)?;
+                table.validate_checks()?;
+                table.validate_constraints()?;
+                if let Some(fk) = references {
+                    let fk = self.resolve_foreign_key(&table, fk)?;
+                    table.foreign_keys.push(fk);
+                }
+                self.replace_table(&alter.name, table)?;
+                message
+            }
+            AlterAction::DropColumn(name) => {
+                self.check_drop_column(&alter.name, &name)?;
+                let mut table = self.table(&alter.name)?.clone();
+                let column = table.drop_column(&name)?;
+                table.validate_constraints()?;
+                let message = format!("已从表 {} 中删除列 {}", table.name, column.name);
+                self.replace_table(&alter.name, table)?;
+                message
+            }
+            AlterAction::RenameColumn { name, new_name } => {
+                let table = self.table_mut(&alter.name)?;
+                table.rename_column(&name, &new_name)?;
+                let table_name = table.name.clone();
+                self.rename_column_references(&table_name, &name, &new_name);
+                format!("已将表 {} 的列 {} 重命名为 {}", table_name, name, new_name)
+            }
+            AlterAction::RenameTable(new_name) => {
+                self.rename_table(&alter.name, &new_name)?;
+                format!("已将表 {} 重命名为 {}", alter.name, new_name)
+            }
+            AlterAction::AlterColumnType { name, data_type } => {
+                let mut table = self.table(&alter.name)?.clone();
+                let message = format!("已将表 {} 的列 {} 修改为 {} 类型", table.name, name, data_type);
+                table.alter_column_type(&name, data_type)?;
+                table.validate_constraints()?;
+                self.replace_table(&alter.name, table)?;
+                message
+            }
+        };
+        Ok(QueryResult {
+            message,
+            ..Default::default()
+        })
+    }
+}
+
+// 接收 SELECT 的结果行及其排序键
+pub(crate) type OutputSink<'a> = dyn FnMut(Vec<Value>, Vec<Value>) -> Result<(), DbError> + 'a;
+
+// 查询求值的中间结果，行中仍是带类型的值
+pub(crate) struct QueryOutput {
+    pub(crate) columns: Vec<ResultColumn>,
+    pub(crate) rows: Vec<Vec<Value>>,
+}
+
+// 由列定义得到表结构中的列，DEFAULT 和 CHECK 以 SQL 文本保存
+fn column_from_def(def: ColumnDef) -> Result<Column, DbError> {
+    let column = Column {
+        is_primary_key: def.primary_key,
+        not_null: def.not_null,
+        unique: def.unique,
+        default: def.default.map(|expr| expr.to_string()),
+        check: def.check.map(|expr| expr.to_string()),
+        ..Column::new(&def.name, def.data_type)
+    };
+    // 默认值必须能转换为列类型
+    column.default_value()?;
+    Ok(column)
+}
+
+// 展开选择列表，旧语法 SELECT FROM table 等同于 SELECT *。得到各输出列的表达式和列信息
+fn projection(
+    select: &Select,
+    scope_columns: &[ScopeColumn],
+    outer: Option<&Scope>,
+) -> Result<(Vec<Expr>, Vec<ResultColumn>), DbError> {
+    let wildcard = [SelectItem::Wildcard];
+    let projection: &[SelectItem] = if select.projection.is_empty() {
+        &wildcard
+    } else {
+        &select.projection
+    };
+    let mut exprs = Vec::new();
+    let mut columns = Vec::new();
+    for item in projection {
+        match item {
+            SelectItem::Wildcard => {
+                for col in scope_columns.iter().filter(|c| !c.qualified_only) {
+                    exprs.push(column_expr(col));
+                    columns.push(ResultColumn {
+                        name: col.name.clone(),
+                        data_type: col.data_type.clone(),
+                    });
+                }
+            }
+            SelectItem::QualifiedWildcard(table) => {
+                let matching: Vec<&ScopeColumn> = scope_columns
+                    .iter()
+                    .filter(|c| c.table.as_deref().is_some_and(|t| t.eq_ignore_ascii_case(table)))
+                    .collect();
+                if matching.is_empty() {
+                    return Err(DbError::TableNotFound(table.clone()));
+                }
+                for col in matching {
+                    exprs.push(column_expr(col));
+                    columns.push(ResultColumn {
+                        name: col.name.clone(),
+                        data_type: col.data_type.clone(),
+                    });
+                }
+            }
+            SelectItem::Expr { expr, alias } => {
+                // 引用外层查询的列在本层解析不到，类型未知
+                let position = match expr {
+                    Expr::Column(name) => resolve_column(scope_columns, None, name).map(Some),
+                    Expr::QualifiedColumn { table, name } => resolve_column(scope_columns, Some(table), name).map(Some),
+                    _ => Ok(None),
+                };
+                let data_type = match position {
+                    Ok(position) => position.and_then(|i| scope_columns[i].data_type.clone()),
+                    Err(DbError::ColumnNotFound(_)) if outer.is_some() => None,
+                    Err(e) => return Err(e),
+                };
+                let name = match (alias, expr) {
+                    (Some(alias), _) => alias.clone(),
+                    (None, Expr::QualifiedColumn { name, .. }) => name.clone(),
+                    (None, expr) => expr.to_string(),
+                };
+                columns.push(ResultColumn { name, data_type });
+                exprs.push(expr.clone());
+            }
+        }
+    }
+    Ok((exprs, columns))
+}
+
+// 结果中 CHAR 列的值以空格补足声明的长度
+pub(crate) fn output_value(value: Value, data_type: Option<&DataType>) -> Value {
+    match (value, data_type) {
+        (Value::Text(text), Some(data_type @ DataType::Char(len))) if text.chars().count() < *len as usize => {
+            Value::Text(fit_length(&text, data_type).0)
+        }
+        (value, _) => value,
+    }
+}
+
+// 有值被截断时在执行摘要后附加警告
+pub(crate) fn with_truncation_warning(message: String, truncated: usize) -> String {
+    if truncated == 0 {
+        message
+    } else {
+        format!("{}（警告: {} 个值超过列的长度限制，已被截断）", message, truncated)
+    }
+}
+
+// 引用作用域中某一列的表达式，有限定名时使用限定名以免歧义
+fn column_expr(col: &ScopeColumn) -> Expr {
+    match &col.table {
+        Some(table) => Expr::QualifiedColumn {
+            table: table.clone(),
+            name: col.name.clone(),
+        },
+        None => Expr::Column(col.name.clone()),
+    }
+}
+
+// ORDER BY 排序键的来源
+enum SortKey {
+    Output(usize), // 输出列下标
+    Input(Expr),   // 在输入行上求值的表达式
+}
+
+// ORDER BY 中的列名和序号优先匹配输出列，其余按输入行求值
+fn sort_key(expr: &Expr, columns: &[ResultColumn]) -> Result<SortKey, DbError> {
+    match expr {
+        Expr::Literal(Literal::Number(n)) => match n.parse::<usize>() {
+            Ok(i) if i >= 1 && i <= columns.len() => Ok(SortKey::Output(i - 1)),
+            _ => Err(DbError::ColumnNotFound(format!("ORDER BY {}", n))),
+        },
+        Expr::Column(name) => Ok(columns
+            .iter()
+            .position(|c| c.name == *name)
+            .or_else(|| columns.iter().position(|c| c.name.eq_ignore_ascii_case(name)))
+            .map(SortKey::Output)
+            .unwrap_or_else(|| SortKey::Input(expr.clone()))),
+        expr => Ok(SortKey::Input(expr.clone())),
+    }
+}
+
+// ORDER BY 中按输入行求值的表达式
+fn sort_inputs(order_by: &[OrderByExpr], columns: &[ResultColumn]) -> Result<Vec<Expr>, DbError> {
+    let mut inputs = Vec::new();
+    for order in order_by {
+        if let SortKey::Input(expr) = sort_key(&order.expr, columns)? {
+            inputs.push(expr);
+        }
+    }
+    Ok(inputs)
+}
+
+// 以输出列作为作用域，用于集合运算的 ORDER BY
+fn output_scope(columns: &[ResultColumn]) -> Vec<ScopeColumn> {
+    columns
+        .iter()
+        .map(|c| ScopeColumn {
+            table: None,
+            name: c.name.clone(),
+            data_type: c.data_type.clone(),
+            qualified_only: false,
+        })
+        .collect()
+}
+
+// 按排序键排序后应用 OFFSET 和 LIMIT
+fn sort_and_limit(
+    mut rows: Vec<(Vec<Value>, Vec<Value>)>,
+    order_by: &[OrderByExpr],
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Vec<Vec<Value>> {
+    if !order_by.is_empty() {
+        rows.sort_by(|(_, a), (_, b)| compare_keys(order_by, a, b));
+    }
+    rows.into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|(values, _)| values)
+        .collect()
+}
+
+// 集合运算中列的类型类别；计算列按实际值推断，全为 NULL 时与任何类型兼容
+fn column_kind(column: &ResultColumn, rows: &[Vec<Value>], i: usize) -> Option<&'static str> {
+    match &column.data_type {
+        Some(DataType::Int | DataType::Float | DataType::Double | DataType::Decimal(..)) => Some("数值"),
+        Some(DataType::Bool) => Some("布尔"),
+        Some(DataType::Char(_) | DataType::String(_)) => Some("文本"),
+        Some(DataType::Date | DataType::Timestamp | DataType::TimestampTz) => Some("日期"),
+        Some(DataType::Time) => Some("时间"),
+        Some(DataType::Interval) => Some("时间间隔"),
+        Some(DataType::Json) => Some("JSON"),
+        Some(DataType::Blob(_)) => Some("二进制"),
+        None => rows.iter().map(|values| &values[i]).find(|v| !v.is_null()).map(|v| match v {
+            Value::Int(_) | Value::Float(_) | Value::Decimal(_) => "数值",
+            Value::Bool(_) => "布尔",
+            Value::Date(_) | Value::Timestamp(_) | Value::TimestampTz(_) => "日期",
+            Value::Time(_) => "时间",
+            Value::Interval(_) => "时间间隔",
+            Value::Json(_) => "JSON",
+            Value::Blob(_) => "二进制",
+            Value::Null | Value::Text(_) => "文本",
+        }),
+    }
+}
+
+// 按 ORDER BY 依次比较各排序键
+pub(crate) fn compare_keys(order_by: &[OrderByExpr], a: &[Value], b: &[Value]) -> Ordering {
+    for (order, (a, b)) in order_by.iter().zip(a.iter().zip(b)) {
+        let nulls_first = order.nulls_first.unwrap_or(order.descending);
+        let ordering = match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if order.descending => b.sort_cmp(a),
+            (false, false) => a.sort_cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+// 判断行是否满足 WHERE 条件，没有条件时总是满足
+fn matches(selection: Option<&Expr>, scope: &Scope) -> Result<bool, DbError> {
+    match selection {
+        None => Ok(true),
+        Some(expr) => is_true(expr, scope),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (n INT, f DOUBLE, d DECIMAL(5,2))").unwrap();
+        db.execute("INSERT INTO t VALUES (1, 1.0, 1.00), (2, 2.5, 2.50), (1, 2, 2)").unwrap();
+        db
+    }
+
+    fn column(db: &mut Database, sql: &str) -> Vec<String> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values[0].to_string()).collect()
+    }
+
+    // 各行的值以 | 连接
+    fn rows(db: &mut Database, sql: &str) -> Vec<String> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("|")).collect()
+    }
+
+    fn people() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE p (id INT PRIMARY KEY, name VARCHAR(10), age INT)").unwrap();
+        db.execute("INSERT INTO p VALUES (1, 'ann', 30), (2, 'bob', 20), (3, 'cy', NULL), (4, 'dee', 20)").unwrap();
+        db.execute("CREATE TABLE o (pid INT, item VARCHAR(10))").unwrap();
+        db.execute("INSERT INTO o VALUES (1, 'x'), (1, 'y'), (9, 'z')").unwrap();
+        db
+    }
+
+    #[test]
+    fn projection_names_columns_by_alias_or_expression() {
+        let mut db = people();
+        let result = db.execute("SELECT name AS who, age + 1, UPPER(name), p.* FROM p WHERE id = 1").unwrap();
+        let names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["who", "age + 1", "UPPER(name)", "id", "name", "age"]);
+        assert_eq!(rows(&mut db, "SELECT name AS who, age + 1, UPPER(name) FROM p WHERE id = 1"), ["ann|31|ANN"]);
+        assert_eq!(column(&mut db, "SELECT DISTINCT age FROM p ORDER BY age"), ["20", "30", "NULL"]);
+        assert!(matches!(db.execute("SELECT nope FROM p"), Err(DbError::ColumnNotFound(_))));
+    }
+
+    #[test]
+    fn order_by_limit_and_offset() {
+        let mut db = people();
+        assert_eq!(column(&mut db, "SELECT id FROM p ORDER BY age DESC NULLS LAST, id LIMIT 2 OFFSET 1"), ["2", "4"]);
+        assert_eq!(column(&mut db, "SELECT id FROM p ORDER BY age, id DESC"), ["4", "2", "1", "3"]);
+        assert_eq!(column(&mut db, "SELECT id FROM p ORDER BY age NULLS FIRST, id"), ["3", "2", "4", "1"]);
+        // 按别名、序号和不在选择列表中的列排序
+        assert_eq!(column(&mut db, "SELECT name AS n FROM p ORDER BY n DESC LIMIT 1"), ["dee"]);
+        assert_eq!(column(&mut db, "SELECT name, id FROM p ORDER BY 2 DESC LIMIT 1"), ["dee"]);
+        assert_eq!(column(&mut db, "SELECT name FROM p ORDER BY id DESC OFFSET 3"), ["ann"]);
+        assert!(db.execute("SELECT id FROM p ORDER BY 3").is_err());
+    }
+
+    #[test]
+    fn set_operations_replace_the_and_syntax() {
+        let mut db = people();
+        assert_eq!(column(&mut db, "SELECT age FROM p UNION SELECT pid FROM o"), ["30", "20", "NULL", "1", "9"]);
+        assert_eq!(column(&mut db, "SELECT age FROM p UNION ALL SELECT pid FROM o").len(), 7);
+        assert_eq!(column(&mut db, "SELECT age FROM p EXCEPT SELECT age FROM p WHERE id = 2"), ["30", "NULL"]);
+        assert_eq!(column(&mut db, "SELECT pid FROM o INTERSECT ALL SELECT id FROM p"), ["1"]);
+        assert_eq!(column(&mut db, "SELECT id FROM p UNION SELECT pid FROM o ORDER BY id DESC LIMIT 2"), ["9", "4"]);
+        assert!(matches!(db.execute("SELECT id, name FROM p UNION SELECT pid FROM o"), Err(DbError::InvalidQuery(_))));
+        let err = db.execute("SELECT FROM p AND o").unwrap_err();
+        assert!(err.to_string().contains("UNION ALL"), "{}", err);
+    }
+
+    #[test]
+    fn subqueries_in_expressions_and_from() {
+        let mut db = people();
+        assert_eq!(column(&mut db, "SELECT name FROM p WHERE id IN (SELECT pid FROM o)"), ["ann"]);
+        assert_eq!(column(&mut db, "SELECT name FROM p WHERE NOT EXISTS (SELECT * FROM o WHERE o.pid = p.id) ORDER BY id"), ["bob", "cy", "dee"]);
+        assert_eq!(column(&mut db, "SELECT (SELECT COUNT(*) FROM o WHERE o.pid = p.id) FROM p ORDER BY id"), ["2", "0", "0", "0"]);
+        assert_eq!(
+            rows(&mut db, "SELECT s.a, s.n FROM (SELECT age AS a, COUNT(*) AS n FROM p GROUP BY age) AS s ORDER BY s.a"),
+            ["20|2", "30|1", "NULL|1"]
+        );
+        // 标量子查询最多返回一行
+        assert!(matches!(db.execute("SELECT (SELECT id FROM p) FROM o"), Err(DbError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn create_and_drop_table() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (a INT, b VARCHAR(5), PRIMARY KEY (a, b))").unwrap();
+        assert!(db.table("t").unwrap().columns.iter().all(|c| c.is_primary_key));
+        assert_eq!(db.execute("CREATE TABLE T (x INT)").unwrap_err(), DbError::TableExists("T".to_string()));
+        db.execute("CREATE TABLE IF NOT EXISTS t (x INT)").unwrap();
+        assert_eq!(db.table("t").unwrap().columns.len(), 2);
+        assert!(db.execute("CREATE TABLE u (a INT, a INT)").is_err());
+        db.execute("DROP TABLE t").unwrap();
+        assert!(matches!(db.execute("DROP TABLE t"), Err(DbError::TableNotFound(_))));
+        db.execute("DROP TABLE IF EXISTS t").unwrap();
+    }
+
+    #[test]
+    fn alter_table_keeps_rows_in_step_with_columns() {
+        let mut db = people();
+        db.execute("ALTER TABLE p ADD COLUMN score INT DEFAULT 5").unwrap();
+        assert_eq!(column(&mut db, "SELECT score FROM p WHERE id = 1"), ["5"]);
+        db.execute("ALTER TABLE p DROP COLUMN age").unwrap();
+        db.execute("ALTER TABLE p RENAME COLUMN name TO nm").unwrap();
+        db.execute("ALTER TABLE p RENAME TO q").unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM q WHERE id = 2"), ["2|bob|5"]);
+        // 无法转换的值使整条语句失败，列的类型不变
+        assert!(matches!(db.execute("ALTER TABLE q ALTER COLUMN nm TYPE INT"), Err(DbError::TypeMismatch(_))));
+        db.execute("ALTER TABLE q ALTER COLUMN score TYPE VARCHAR(3)").unwrap();
+        assert_eq!(db.table("q").unwrap().columns[2].data_type, DataType::String(3));
+        assert!(db.execute("ALTER TABLE q DROP COLUMN nope").is_err());
+    }
+
+    #[test]
+    fn set_operations_match_equal_numbers_of_different_types() {
+        let mut db = database();
+        assert_eq!(column(&mut db, "SELECT n FROM t INTERSECT SELECT f FROM t"), ["1", "2"]);
+        assert!(column(&mut db, "SELECT n FROM t EXCEPT SELECT f FROM t").is_empty());
+        assert_eq!(column(&mut db, "SELECT n FROM t UNION SELECT f FROM t"), ["1", "2", "2.5"]);
+        assert_eq!(column(&mut db, "SELECT n FROM t INTERSECT SELECT d FROM t"), ["1", "2"]);
+        assert_eq!(column(&mut db, "SELECT f FROM t EXCEPT SELECT d FROM t"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn distinct_merges_equal_numbers_of_different_types() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE j (doc JSON)").unwrap();
+        db.execute(r#"INSERT INTO j VALUES ('{"a": 1}'), ('{"a": 1.0}'), ('{"a": 2.50}'), ('{"a": 2.5}'), ('{"a": -0.0}'), ('{"a": 0}')"#)
+            .unwrap();
+        assert_eq!(column(&mut db, "SELECT DISTINCT doc->>'a' FROM j"), ["1", "2.5", "-0"]);
+    }
+}
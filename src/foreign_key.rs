@@ -0,0 +1,517 @@
+use crate::part1::{Constraint, DataType, Database, DbError, ForeignKey, ReferentialAction, Row, Table};
+use crate::value::{values_key, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+impl Database {
+    // 校验并规范化 table 上的外键：列名改为表中的写法，省略被引用列时取被引用表的主键。
+    // table 可以是尚未加入数据库的新表，外键引用它自身时以它作为被引用表
+    pub(crate) fn resolve_foreign_key(&self, table: &Table, fk: ForeignKey) -> Result<ForeignKey, DbError> {
+        let parent = self.parent_table(table, &fk)?;
+        let columns = column_indices(table, &fk.columns)?;
+        let ref_columns = if fk.ref_columns.is_empty() {
+            let key = parent.primary_key();
+            if key.is_empty() {
+                return Err(DbError::InvalidQuery(format!(
+                    "表 '{}' 没有主键，外键需要指定引用的列",
+                    parent.name
+                )));
+            }
+            key
+        } else {
+            column_indices(parent, &fk.ref_columns)?
+        };
+        if columns.len() != ref_columns.len() {
+            return Err(DbError::InvalidQuery(format!(
+                "外键的列数 ({}) 与被引用的列数 ({}) 不同",
+                columns.len(),
+                ref_columns.len()
+            )));
+        }
+        // 被引用的值必须能唯一确定一行
+        let mut sorted = ref_columns.clone();
+        sorted.sort_unstable();
+        let unique = sorted == parent.primary_key() || (sorted.len() == 1 && parent.columns[sorted[0]].unique);
+        let ref_names: Vec<String> = ref_columns.iter().map(|&i| parent.columns[i].name.clone()).collect();
+        if !unique {
+            return Err(DbError::InvalidQuery(format!(
+                "外键引用的列 ({}) 不是表 '{}' 的主键或 UNIQUE 列",
+                ref_names.join(", "),
+                parent.name
+            )));
+        }
+        for (&c, &r) in columns.iter().zip(&ref_columns) {
+            let (col, ref_col) = (&table.columns[c], &parent.columns[r]);
+            if !comparable(&col.data_type, &ref_col.data_type) {
+                return Err(DbError::TypeMismatch(format!(
+                    "外键列 '{}' 的类型 {} 与被引用列 '{}' 的类型 {} 不兼容",
+                    col.name, col.data_type, ref_col.name, ref_col.data_type
+                )));
+            }
+        }
+        Ok(ForeignKey {
+            columns: columns.iter().map(|&i| table.columns[i].name.clone()).collect(),
+            ref_table: parent.name.clone(),
+            ref_columns: ref_names,
+            ..fk
+        })
+    }
+
+    // 写入 table 的新行在各外键上的值必须存在于被引用的表中，含 NULL 的值不检查。
+    // replaced 为将被 new_rows 替换的行下标，引用自身的外键以写入后的行为准
+    pub(crate) fn check_references(&self, table: &Table, new_rows: &[&Row], replaced: &[usize]) -> Result<(), DbError> {
+        let replaced: HashSet<usize> = replaced.iter().copied().collect();
+        for fk in &table.foreign_keys {
+            let parent = self.parent_table(table, fk)?;
+            let self_reference = std::ptr::eq(parent, table);
+            let columns = column_indices(table, &fk.columns)?;
+            let ref_columns = column_indices(parent, &fk.ref_columns)?;
+            let mut keys: HashSet<_> = parent
+                .rows
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !(self_reference && replaced.contains(i)))
+                .map(|(_, row)| values_key(&parent.key_values(row, &ref_columns)))
+                .collect();
+            if self_reference {
+                keys.extend(new_rows.iter().map(|row| values_key(&parent.key_values(row, &ref_columns))));
+            }
+            for row in new_rows {
+                let values = table.key_values(row, &columns);
+                if !values.iter().any(Value::is_null) && !keys.contains(&values_key(&values)) {
+                    return Err(table.violation(
+                        Constraint::ForeignKey(parent.name.clone()),
+                        fk.columns.join(", "),
+                        shown(&values),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 检查所有外键的定义和数据，用于加载数据库文件之后
+    pub fn validate_foreign_keys(&self) -> Result<(), DbError> {
+        self.tables.iter().try_for_each(|table| self.validate_table_references(table))
+    }
+
+    // 检查 table 自身的外键以及引用 table 的外键
+    fn validate_table_references(&self, table: &Table) -> Result<(), DbError> {
+        let rows: Vec<&Row> = table.rows.iter().collect();
+        for fk in &table.foreign_keys {
+            self.resolve_foreign_key(table, fk.clone())?;
+        }
+        self.check_references(table, &rows, &[])?;
+        for (child, fk) in self.referencing(&table.name) {
+            if !std::ptr::eq(child, table) {
+                self.resolve_foreign_key(child, fk.clone())?;
+                self.check_references(child, &child.rows.iter().collect::<Vec<_>>(), &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    // 用修改结构后的副本替换表，涉及它的外键不再成立时保留原表
+    pub(crate) fn replace_table(&mut self, name: &str, table: Table) -> Result<(), DbError> {
+        let slot = self.table_mut(name)?;
+        let old = std::mem::replace(slot, table);
+        let name = old.name.clone();
+        if let Err(e) = self.validate_table_references(self.table(&name)?) {
+            *self.table_mut(&name)? = old;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // 所有引用 table 的外键及其所在的表，包括引用自身的外键
+    pub(crate) fn referencing<'a>(&'a self, table: &'a str) -> impl Iterator<Item = (&'a Table, &'a ForeignKey)> + 'a {
+        self.tables.iter().flat_map(move |child| {
+            child
+                .foreign_keys
+                .iter()
+                .filter(move |fk| fk.ref_table.eq_ignore_ascii_case(table))
+                .map(move |fk| (child, fk))
+        })
+    }
+
+    // 被其他表的外键引用的表不能删除
+    pub(crate) fn check_drop_table(&self, table: &str) -> Result<(), DbError> {
+        let name = &self.table(table)?.name;
+        match self.referencing(name).find(|(child, _)| !child.name.eq_ignore_ascii_case(name)) {
+            Some((child, _)) => Err(DbError::InvalidQuery(format!(
+                "表 '{}' 被表 '{}' 的外键引用，不能删除",
+                name, child.name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // 外键中用到的列和被外键引用的列不能删除
+    pub(crate) fn check_drop_column(&self, table: &str, column: &str) -> Result<(), DbError> {
+        let table = self.table(table)?;
+        let column = &table.columns[table.column_index(column)?].name;
+        if table.foreign_keys.iter().any(|fk| fk.columns.contains(column)) {
+            return Err(DbError::InvalidQuery(format!("列 '{}' 用于外键约束，不能删除", column)));
+        }
+        if let Some((child, _)) = self.referencing(&table.name).find(|(_, fk)| fk.ref_columns.contains(column)) {
+            return Err(DbError::InvalidQuery(format!(
+                "列 '{}' 被表 '{}' 的外键引用，不能删除",
+                column, child.name
+            )));
+        }
+        Ok(())
+    }
+
+    // 外键中的列名随列一起改名
+    pub(crate) fn rename_column_references(&mut self, table: &str, name: &str, new_name: &str) {
+        let rename = |columns: &mut Vec<String>| {
+            for column in columns.iter_mut().filter(|c| c.eq_ignore_ascii_case(name)) {
+                *column = new_name.to_string();
+            }
+        };
+        for child in &mut self.tables {
+            let own = child.name.eq_ignore_ascii_case(table);
+            for fk in &mut child.foreign_keys {
+                if own {
+                    rename(&mut fk.columns);
+                }
+                if fk.ref_table.eq_ignore_ascii_case(table) {
+                    rename(&mut fk.ref_columns);
+                }
+            }
+        }
+    }
+
+    // 删除表中的行，并按 ON DELETE 动作处理引用它们的行；任何一步失败时数据库保持不变
+    pub(crate) fn delete_rows(&mut self, table: &str, indices: &[usize]) -> Result<(), DbError> {
+        let changes = indices.iter().map(|&i| (i, None)).collect();
+        self.atomically(table, false, |db| db.cascade(table, changes))
+    }
+
+    // 用已通过本表约束检查的新行替换表中的行，并按 ON UPDATE 动作处理引用旧键值的行。
+    // 本表的外键在级联之后再检查：引用自身的行（如 (5, 5)）此时已随 ON UPDATE CASCADE 指向新的键值
+    pub(crate) fn update_rows(&mut self, table: &str, updated: Vec<(usize, Row)>) -> Result<(), DbError> {
+        let changes = updated.into_iter().map(|(i, row)| (i, Some(row))).collect();
+        self.atomically(table, true, |db| db.cascade(table, changes))
+    }
+
+    // 表被外键引用时其他表可能被连带修改，checks_own 时本表的外键在修改之后检查，失败后都需要恢复到修改之前
+    fn atomically(
+        &mut self,
+        table: &str,
+        checks_own: bool,
+        f: impl FnOnce(&mut Database) -> Result<(), DbError>,
+    ) -> Result<(), DbError> {
+        let table = self.table(table)?;
+        let name = table.name.clone();
+        let guarded = (checks_own && !table.foreign_keys.is_empty()) || self.referencing(&name).next().is_some();
+        let snapshot = guarded.then(|| self.tables.clone());
+        let result = f(self);
+        if let (Err(_), Some(tables)) = (&result, snapshot) {
+            self.tables = tables;
+        }
+        result
+    }
+
+    // 删除（新行为 None）或修改 table 中的行，连同外键动作引起的所有变化一起写入各表，
+    // 之后检查被连带修改的行的约束和所有修改过的行的外键
+    fn cascade(&mut self, table: &str, changes: Vec<(usize, Option<Row>)>) -> Result<(), DbError> {
+        let own: HashSet<usize> = changes.iter().filter(|(_, row)| row.is_some()).map(|(i, _)| *i).collect();
+        let (statement, deleted, mut updated) = {
+            let table = self.table(table)?;
+            let mut cascade = Cascade::new(self);
+            let changes = changes.into_iter().filter_map(|(i, row)| cascade.set(table, i, row)).collect();
+            cascade.pending.push_back((table, changes));
+            cascade.run()?;
+            (table.name.clone(), cascade.deleted, cascade.updated)
+        };
+
+        let mut written = Vec::new();
+        for name in deleted.keys().chain(updated.keys()).cloned().collect::<BTreeSet<_>>() {
+            let deleted = deleted.get(&name);
+            let mut rows = updated.remove(&name).unwrap_or_default();
+            let table = self.table_mut(&name)?;
+            let mut indices = Vec::new();
+            let mut kept = Vec::with_capacity(table.rows.len());
+            for (i, row) in std::mem::take(&mut table.rows).into_iter().enumerate() {
+                if deleted.is_some_and(|deleted| deleted.contains(&i)) {
+                    continue;
+                }
+                match rows.remove(&i) {
+                    Some(new_row) => {
+                        indices.push((kept.len(), table.name == statement && own.contains(&i)));
+                        kept.push(new_row);
+                    }
+                    None => kept.push(row),
+                }
+            }
+            table.rows = kept;
+            written.push((name, indices));
+        }
+
+        for (table, indices) in written {
+            let table = self.table(&table)?;
+            let cascaded: Vec<usize> = indices.iter().filter(|(_, own)| !own).map(|(i, _)| *i).collect();
+            let rows: Vec<&Row> = cascaded.iter().map(|&i| &table.rows[i]).collect();
+            table.check_constraints(&rows, &cascaded)?;
+            let rows: Vec<&Row> = indices.iter().map(|&(i, _)| &table.rows[i]).collect();
+            self.check_references(table, &rows, &[])?;
+        }
+        Ok(())
+    }
+
+    // 外键的被引用表，引用自身时为 table
+    fn parent_table<'a>(&'a self, table: &'a Table, fk: &ForeignKey) -> Result<&'a Table, DbError> {
+        if fk.ref_table.eq_ignore_ascii_case(&table.name) {
+            Ok(table)
+        } else {
+            self.table(&fk.ref_table)
+        }
+    }
+}
+
+type Key = Vec<(&'static str, String)>;
+
+// 行的变化：旧行和新行，删除时新行为 None
+type Change = (Row, Option<Row>);
+
+// 一条语句连带的所有删除和修改。先在数据库不变的情况下用工作表逐批算出，再由 Database::cascade 一起写入；
+// 各表按用到的列建立 键值 -> 行下标 的索引，已删除的行从索引中移除，不会再被处理
+struct Cascade<'a> {
+    db: &'a Database,
+    deleted: HashMap<String, BTreeSet<usize>>,
+    updated: HashMap<String, BTreeMap<usize, Row>>,
+    indexes: HashMap<(String, Vec<usize>), HashMap<Key, BTreeSet<usize>>>,
+    // 待处理的被引用表及其中行的变化
+    pending: VecDeque<(&'a Table, Vec<Change>)>,
+}
+
+impl<'a> Cascade<'a> {
+    fn new(db: &'a Database) -> Self {
+        Cascade {
+            db,
+            deleted: HashMap::new(),
+            updated: HashMap::new(),
+            indexes: HashMap::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), DbError> {
+        while let Some((parent, changes)) = self.pending.pop_front() {
+            let references: Vec<_> = self.db.referencing(&parent.name).collect();
+            for (child, fk) in references {
+                self.referential_action(parent, child, fk, &changes)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 行的当前值，已删除时为 None
+    fn row<'r>(&'r self, table: &'r Table, i: usize) -> Option<&'r Row> {
+        if self.deleted.get(&table.name).is_some_and(|deleted| deleted.contains(&i)) {
+            return None;
+        }
+        self.updated.get(&table.name).and_then(|rows| rows.get(&i)).or(Some(&table.rows[i]))
+    }
+
+    // table 中当前各行按 columns 上的值建立的索引
+    fn index(&mut self, table: &Table, columns: &[usize]) -> &HashMap<Key, BTreeSet<usize>> {
+        let id = (table.name.clone(), columns.to_vec());
+        if !self.indexes.contains_key(&id) {
+            let mut index: HashMap<Key, BTreeSet<usize>> = HashMap::new();
+            for i in 0..table.rows.len() {
+                if let Some(row) = self.row(table, i) {
+                    index.entry(values_key(&table.key_values(row, columns))).or_default().insert(i);
+                }
+            }
+            self.indexes.insert(id.clone(), index);
+        }
+        &self.indexes[&id]
+    }
+
+    // 把 table 的第 i 行改为 new（删除时为 None）并同步索引，返回这一变化；行已删除时返回 None
+    fn set(&mut self, table: &Table, i: usize, new: Option<Row>) -> Option<Change> {
+        let old = self.row(table, i)?.clone();
+        for ((_, columns), index) in self.indexes.iter_mut().filter(|((name, _), _)| *name == table.name) {
+            let key = values_key(&table.key_values(&old, columns));
+            if let Some(rows) = index.get_mut(&key) {
+                rows.remove(&i);
+                if rows.is_empty() {
+                    index.remove(&key);
+                }
+            }
+            if let Some(row) = &new {
+                index.entry(values_key(&table.key_values(row, columns))).or_default().insert(i);
+            }
+        }
+        let updated = self.updated.entry(table.name.clone()).or_default();
+        match &new {
+            Some(row) => {
+                updated.insert(i, row.clone());
+            }
+            None => {
+                updated.remove(&i);
+                self.deleted.entry(table.name.clone()).or_default().insert(i);
+            }
+        }
+        Some((old, new))
+    }
+
+    // 按外键 fk 的动作处理 child 中引用了 parent 已不存在的键值的行
+    fn referential_action(
+        &mut self,
+        parent: &'a Table,
+        child: &'a Table,
+        fk: &ForeignKey,
+        changes: &[Change],
+    ) -> Result<(), DbError> {
+        let ref_columns = column_indices(parent, &fk.ref_columns)?;
+        let columns = column_indices(child, &fk.columns)?;
+
+        // 旧键值 -> 新键值（删除时为 None），修改后仍存在的键值不受影响
+        let mut lost = Vec::new();
+        for (old, new) in changes {
+            let key = parent.key_values(old, &ref_columns);
+            let key_id = values_key(&key);
+            if !key.iter().any(Value::is_null) && !self.index(parent, &ref_columns).contains_key(&key_id) {
+                lost.push((key_id, new.as_ref().map(|row| parent.key_values(row, &ref_columns))));
+            }
+        }
+
+        let mut effects = Vec::new();
+        for (key, new_key) in lost {
+            let Some(rows) = self.index(child, &columns).get(&key) else {
+                continue;
+            };
+            for i in rows.clone() {
+                let row = self.row(child, i).expect("索引中只有未删除的行");
+                let action = if new_key.is_some() { fk.on_update } else { fk.on_delete };
+                let replacement: Vec<Value> = match (action, &new_key) {
+                    (ReferentialAction::Restrict, _) => {
+                        return Err(parent.violation(
+                            Constraint::Referenced(child.name.clone()),
+                            fk.ref_columns.join(", "),
+                            shown(&child.key_values(row, &columns)),
+                        ))
+                    }
+                    (ReferentialAction::Cascade, None) => {
+                        effects.push((i, None));
+                        continue;
+                    }
+                    (ReferentialAction::Cascade, Some(key)) => key.clone(),
+                    (ReferentialAction::SetNull, _) => vec![Value::Null; columns.len()],
+                    (ReferentialAction::SetDefault, _) => columns
+                        .iter()
+                        .map(|&c| child.columns[c].default_value())
+                        .collect::<Result<_, _>>()?,
+                };
+                let mut new_row = row.clone();
+                new_row.values.resize(child.columns.len(), Value::Null);
+                for (&c, value) in columns.iter().zip(replacement) {
+                    new_row.values[c] = value;
+                }
+                child.conform_row(&mut new_row, self.db.length_policy)?;
+                effects.push((i, Some(new_row)));
+            }
+        }
+
+        let changes: Vec<_> = effects.into_iter().filter_map(|(i, row)| self.set(child, i, row)).collect();
+        if !changes.is_empty() {
+            self.pending.push_back((child, changes));
+        }
+        Ok(())
+    }
+}
+
+fn column_indices(table: &Table, names: &[String]) -> Result<Vec<usize>, DbError> {
+    names.iter().map(|name| table.column_index(name)).collect()
+}
+
+// 外键两侧的值按相同的规则比较
+fn comparable(a: &DataType, b: &DataType) -> bool {
+    matches!(
+        (a, b),
+        (DataType::Int, DataType::Int)
+            | (DataType::Float | DataType::Double, DataType::Float | DataType::Double)
+            | (DataType::Decimal(..), DataType::Decimal(..))
+            | (DataType::Date, DataType::Date)
+            | (DataType::Time, DataType::Time)
+            | (DataType::Timestamp, DataType::Timestamp)
+            | (DataType::TimestampTz, DataType::TimestampTz)
+            | (DataType::Interval, DataType::Interval)
+            | (DataType::Blob(_), DataType::Blob(_))
+            | (DataType::Bool, DataType::Bool)
+            | (DataType::Char(_) | DataType::String(_), DataType::Char(_) | DataType::String(_))
+    )
+}
+
+fn shown(values: &[Value]) -> String {
+    values.iter().map(Value::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: usize = 3000;
+
+    // 自引用的链：第 i 行引用第 i - 1 行
+    fn chain(on_delete: &str) -> Database {
+        let mut db = Database::new("test");
+        db.execute(&format!("CREATE TABLE s (x INT PRIMARY KEY, parent INT REFERENCES s(x) ON DELETE {})", on_delete))
+            .unwrap();
+        let values: Vec<String> = (0..DEPTH)
+            .map(|i| if i == 0 { "(0, NULL)".to_string() } else { format!("({}, {})", i, i - 1) })
+            .collect();
+        db.execute(&format!("INSERT INTO s VALUES {}", values.join(", "))).unwrap();
+        db
+    }
+
+    fn count(db: &mut Database, table: &str) -> String {
+        let result = db.execute(&format!("SELECT COUNT(*) FROM {}", table)).unwrap();
+        result.rows[0].values[0].to_string()
+    }
+
+    #[test]
+    fn deep_cascade_delete_removes_whole_chain() {
+        let mut db = chain("CASCADE");
+        db.execute("DELETE FROM s WHERE x = 0").unwrap();
+        assert_eq!(count(&mut db, "s"), "0");
+    }
+
+    #[test]
+    fn restrict_at_end_of_chain_rolls_back_every_level() {
+        let mut db = chain("CASCADE");
+        db.execute("CREATE TABLE r (y INT REFERENCES s(x))").unwrap();
+        db.execute(&format!("INSERT INTO r VALUES ({})", DEPTH - 1)).unwrap();
+        assert!(db.execute("DELETE FROM s WHERE x = 0").is_err());
+        // 失败时已经算出的各层删除都不生效
+        assert_eq!(count(&mut db, "s"), DEPTH.to_string());
+    }
+
+    #[test]
+    fn set_null_stops_the_chain() {
+        let mut db = chain("SET NULL");
+        db.execute("DELETE FROM s WHERE x = 0").unwrap();
+        assert_eq!(count(&mut db, "s"), (DEPTH - 1).to_string());
+        let result = db.execute("SELECT x FROM s WHERE parent IS NULL").unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0].values[0].to_string(), "1");
+    }
+
+    #[test]
+    fn update_cascade_follows_self_reference() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE p (id INT PRIMARY KEY, ref INT REFERENCES p(id) ON UPDATE CASCADE)").unwrap();
+        db.execute("CREATE TABLE c (pid INT REFERENCES p(id) ON UPDATE CASCADE)").unwrap();
+        db.execute("INSERT INTO p VALUES (5, 5), (6, 5)").unwrap();
+        db.execute("INSERT INTO c VALUES (5), (6)").unwrap();
+        db.execute("UPDATE p SET id = 7 WHERE id = 5").unwrap();
+        let result = db.execute("SELECT id, ref FROM p ORDER BY id").unwrap();
+        let rows: Vec<String> = result.rows.iter().map(|row| format!("{} {}", row.values[0], row.values[1])).collect();
+        assert_eq!(rows, ["6 7", "7 7"]);
+        let result = db.execute("SELECT pid FROM c ORDER BY pid").unwrap();
+        let rows: Vec<String> = result.rows.iter().map(|row| row.values[0].to_string()).collect();
+        assert_eq!(rows, ["6", "7"]);
+    }
+}
@@ -1,13 +1,27 @@
 use eframe::egui;
-use crate::part1::{Database, Table, Row, DataType, Column};
+use sql_homework::part1::{Database, Table, Row, DataType, Column, QueryResult, LengthPolicy, StoredRow, FORMAT_VERSION};
+use sql_homework::value::Value;
+use sql_homework::decimal::MAX_PRECISION;
+use sql_homework::blob::{self, DEFAULT_MAX_SIZE};
+use sql_homework::page_store;
+use sql_homework::storage::{self, Recovery, StorageFormat};
+use sql_homework::wal::{Change, Wal};
+use sql_homework::datetime::Timestamp;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
-use serde::{Serialize, Deserialize};
-use sha2::{Sha256, Digest};
+use std::path::{Path, PathBuf};
+
+// 没有最近打开的文件时，启动时加载的默认数据库
+const DEFAULT_DATABASE_FILE: &str = "database.json";
+// 最近打开的数据库列表，保存在工作目录中
+const RECENT_FILES_FILE: &str = "recent_databases.json";
+const MAX_RECENT_FILES: usize = 10;
 
 pub struct DatabaseGui {
     database: Option<Database>,
+    database_path: PathBuf, // 当前数据库的文件
+    wal: Option<Wal>,       // 当前数据库的预写日志，修改先写入日志，定期写回数据文件
+    recent_files: Vec<PathBuf>, // 最近打开的数据库，最近的在前
     is_secure: bool,
     query_input: String,
     output_text: String,
@@ -16,6 +30,8 @@ pub struct DatabaseGui {
     new_column_name: String,
     new_column_type: String,
     new_column_length: String,
+    new_column_scale: String,
+    new_column_is_primary_key: bool,
     temp_columns: Vec<Column>,
     // 用于插入数据的临时状态
     selected_table: String,
@@ -38,6 +54,9 @@ impl Default for DatabaseGui {
     fn default() -> Self {
         Self {
             database: None,
+            database_path: PathBuf::from(DEFAULT_DATABASE_FILE),
+            wal: None,
+            recent_files: load_recent_files(),
             is_secure: true,
             query_input: String::new(),
             output_text: String::new(),
@@ -45,6 +64,8 @@ impl Default for DatabaseGui {
             new_column_name: String::new(),
             new_column_type: String::from("Int"),
             new_column_length: String::new(),
+            new_column_scale: String::new(),
+            new_column_is_primary_key: false,
             temp_columns: Vec::new(),
             selected_table: String::new(),
             insert_values: HashMap::new(),
@@ -59,6 +80,36 @@ impl eframe::App for DatabaseGui {
         egui::CentralPanel::default().show(ctx, |ui| {
             // 顶部菜单栏
             ui.horizontal(|ui| {
+                ui.menu_button("文件", |ui| {
+                    if ui.button("新建数据库…").clicked() {
+                        ui.close_menu();
+                        self.new_database_dialog();
+                    }
+                    if ui.button("打开数据库…").clicked() {
+                        ui.close_menu();
+                        self.open_database_dialog();
+                    }
+                    if ui.add_enabled(self.database.is_some(), egui::Button::new("另存为…")).clicked() {
+                        ui.close_menu();
+                        self.save_database_as_dialog();
+                    }
+                    ui.separator();
+                    ui.menu_button("最近打开", |ui| {
+                        if self.recent_files.is_empty() {
+                            ui.label("（无）");
+                        }
+                        let mut opened = None;
+                        for path in &self.recent_files {
+                            if ui.button(path.display().to_string()).clicked() {
+                                opened = Some(path.clone());
+                            }
+                        }
+                        if let Some(path) = opened {
+                            ui.close_menu();
+                            self.load_database(&path);
+                        }
+                    });
+                });
                 if ui.button("主页").clicked() {
                     self.current_view = ViewState::Main;
                 }
@@ -93,74 +144,245 @@ impl eframe::App for DatabaseGui {
             }
         });
     }
+
+    // 退出时把日志写回数据文件
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_database();
+    }
 }
 
 impl DatabaseGui {
     fn calculate_hash(&self) -> String {
-        if let Some(ref db) = self.database {
-            if let Ok(json) = serde_json::to_string(db) {
-                let mut hasher = Sha256::new();
-                hasher.update(json.as_bytes());
-                format!("{:x}", hasher.finalize())
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
+        match self.database {
+            Some(ref db) => storage::database_hash(db),
+            None => String::new(),
         }
     }
 
+    // 把整个数据库写回数据文件；有日志时即为检查点，同时清空日志
     fn save_database(&mut self) {
-        if let Some(ref db) = self.database {
-            if let Ok(json) = serde_json::to_string_pretty(db) {
-                if let Err(e) = fs::write("database.json", &json) {
-                    self.error_message = format!("保存数据库失败: {}", e);
-                    return;
+        if let Some(ref mut db) = self.database {
+            let path = &self.database_path;
+            let result = match self.wal {
+                Some(ref mut wal) => wal.checkpoint(db),
+                None => storage::save(db, path, &storage::hash_path(path)),
+            };
+            if let Err(e) = result {
+                self.error_message = format!("保存数据库失败: {}", e);
+            }
+        }
+    }
+
+    // 记录已在内存中生效的修改：追加到日志，日志满时做检查点；写入日志失败时改为保存整个数据库
+    fn log_change(&mut self, change: Change) {
+        let appended = self.wal.as_mut().map(|wal| wal.append(change).map(|()| wal.is_full()));
+        match appended {
+            Some(Ok(false)) => {}
+            Some(Ok(true)) | None => self.save_database(),
+            Some(Err(e)) => {
+                self.error_message = format!("写入日志失败，已改为保存整个数据库: {}", e);
+                self.save_database();
+            }
+        }
+    }
+
+    // 为当前数据库在 database_path 处创建空日志，并把数据库写入数据文件
+    fn start_new_file(&mut self) {
+        let Some(ref db) = self.database else {
+            return;
+        };
+        self.wal = match Wal::create(&self.database_path, db) {
+            Ok(wal) => Some(wal),
+            Err(e) => {
+                self.error_message = format!("创建日志失败: {}", e);
+                None
+            }
+        };
+        self.save_database();
+    }
+
+    // 启动时打开最近使用的数据库，没有时打开工作目录中的默认数据库
+    fn load_initial_database(&mut self) {
+        let path = self
+            .recent_files
+            .iter()
+            .find(|path| path.exists())
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DATABASE_FILE));
+        if path.exists() {
+            self.load_database(&path);
+        }
+    }
+
+    // 在指定位置新建空数据库，以文件名作为数据库名称
+    fn new_database_dialog(&mut self) {
+        let Some(path) = database_file_dialog().set_file_name(DEFAULT_DATABASE_FILE).save_file() else {
+            return;
+        };
+        if let Some(e) = paged_file_error(&path) {
+            self.error_message = e;
+            return;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        self.save_database();
+        self.database = Some(Database::new(&name));
+        self.database_path = path.clone();
+        self.is_secure = true;
+        self.reset_view_state();
+        self.start_new_file();
+        self.remember_recent_file(&path);
+        self.output_text = format!("数据库 {} 创建成功！", name);
+    }
+
+    fn open_database_dialog(&mut self) {
+        if let Some(path) = database_file_dialog().pick_file() {
+            self.load_database(&path);
+        }
+    }
+
+    // 把当前数据库保存到新位置，之后的修改都保存到新文件
+    fn save_database_as_dialog(&mut self) {
+        let file_name = self.database_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let Some(path) = database_file_dialog().set_file_name(file_name).save_file() else {
+            return;
+        };
+        if let Some(e) = paged_file_error(&path) {
+            self.error_message = e;
+            return;
+        }
+        // 先把日志写回原来的文件，之后的修改记录在新文件的日志中
+        self.save_database();
+        self.database_path = path.clone();
+        self.error_message.clear();
+        self.start_new_file();
+        if self.error_message.is_empty() {
+            self.is_secure = true;
+            self.remember_recent_file(&path);
+            self.output_text = format!("数据库已另存为 {}", path.display());
+        }
+    }
+
+    fn remember_recent_file(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.recent_files.retain(|p| *p != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+        if let Err(e) = save_recent_files(&self.recent_files) {
+            self.error_message = format!("保存最近打开的文件列表失败: {}", e);
+        }
+    }
+
+    // 切换数据库时清除针对上一个数据库的输入
+    fn reset_view_state(&mut self) {
+        self.temp_columns.clear();
+        self.selected_table.clear();
+        self.insert_values.clear();
+    }
+
+    // 打开数据文件的日志并重放上次没有写入数据文件的修改，之后做一次检查点。
+    // 重放失败时不使用此数据库，以免保存时覆盖日志中的修改
+    fn open_wal(&mut self, path: &Path, migrated: bool) {
+        let Some(ref mut db) = self.database else {
+            return;
+        };
+        match Wal::open(path, db) {
+            Ok((wal, replayed)) => {
+                self.wal = Some(wal);
+                if replayed > 0 {
+                    self.output_text.push_str(&format!("\n已从日志恢复 {} 条修改", replayed));
                 }
-                
-                let hash = self.calculate_hash();
-                if let Err(e) = fs::write("database_hash.txt", &hash) {
-                    self.error_message = format!("保存哈希值失败: {}", e);
+                if self.is_secure && (replayed > 0 || migrated) {
+                    self.save_database();
                 }
             }
+            Err(e) => {
+                self.database = None;
+                self.error_message = format!("打开日志失败: {}", e);
+            }
         }
     }
 
-    fn load_database(&mut self) {
-        if Path::new("database.json").exists() {
-            match fs::read_to_string("database.json") {
-                Ok(json) => {
-                    match serde_json::from_str(&json) {
-                        Ok(db) => {
-                            self.database = Some(db);
-                            
-                            let current_hash = self.calculate_hash();
-                            
-                            if Path::new("database_hash.txt").exists() {
-                                if let Ok(stored_hash) = fs::read_to_string("database_hash.txt") {
-                                    self.is_secure = current_hash == stored_hash;
-                                } else {
-                                    self.is_secure = false;
-                                }
+    fn load_database(&mut self, path: &Path) {
+        if let Some(e) = paged_file_error(path) {
+            self.error_message = e;
+            return;
+        }
+        // 先把当前数据库的日志写回数据文件
+        self.save_database();
+        let hash_path = storage::hash_path(path);
+        // 先处理上次被中断的保存，以免读到写了一半的文件或不匹配的哈希
+        let recovery = match storage::recover(path, &hash_path) {
+            Ok(recovery) => recovery,
+            Err(e) => {
+                self.error_message = format!("恢复未完成的保存失败: {}", e);
+                return;
+            }
+        };
+        if path.exists() {
+            match storage::read_database(path) {
+                Ok(db) => {
+                    // 文件可能被手工修改过，外键引用的行不存在时提示但仍然加载
+                    if let Err(e) = db.validate_foreign_keys() {
+                        self.error_message = format!("数据库文件中的数据违反外键约束: {}", e);
+                    }
+                    self.database = Some(db);
+                    self.database_path = path.to_path_buf();
+                    self.wal = None;
+                    self.reset_view_state();
+                    let mut migrated = false;
+                    self.remember_recent_file(path);
+                    
+                    let current_hash = self.calculate_hash();
+                    
+                    if hash_path.exists() {
+                        if let Ok(stored_hash) = fs::read_to_string(&hash_path) {
+                            self.is_secure = current_hash == stored_hash;
+                        } else {
+                            self.is_secure = false;
+                        }
+                    } else {
+                        if let Err(e) = storage::write_atomic(&hash_path, current_hash.as_bytes()) {
+                            self.error_message = format!("创建哈希文件失败: {}", e);
+                        }
+                        self.is_secure = true;
+                    }
+                    
+                    self.output_text = match recovery {
+                        Recovery::Clean => "数据库加载成功！".to_string(),
+                        Recovery::Completed => "数据库加载成功，已完成上次中断的保存！".to_string(),
+                        Recovery::Discarded => "数据库加载成功；上次保存没有完成，已恢复到之前保存的内容".to_string(),
+                    };
+
+                    if let Some(ref mut db) = self.database {
+                        // 旧文件没有名称，在完整性校验之后取文件名，下次保存时写入
+                        if db.name.is_empty() {
+                            db.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                        }
+                        // 旧格式的文件在完整性校验之后升级，打开日志后以新格式保存
+                        if db.format_version < FORMAT_VERSION {
+                            migrated = true;
+                            let unconverted = db.migrate();
+                            self.output_text = if unconverted == 0 {
+                                "数据库加载成功，已升级到新的存储格式！".to_string()
                             } else {
-                                if let Err(e) = fs::write("database_hash.txt", &current_hash) {
-                                    self.error_message = format!("创建哈希文件失败: {}", e);
-                                }
-                                self.is_secure = true;
-                            }
-                            
-                            self.output_text = "数据库加载成功！".to_string();
-                        },
-                        Err(e) => self.error_message = format!("解析数据库失败: {}", e),
+                                format!(
+                                    "数据库加载成功，已升级到新的存储格式；{} 个值无法转换为列的类型，保留为文本",
+                                    unconverted
+                                )
+                            };
+                        }
                     }
+                    self.open_wal(path, migrated);
                 },
                 Err(e) => self.error_message = format!("读取数据库文件失败: {}", e),
             }
+        } else {
+            self.error_message = format!("数据库文件 {} 不存在", path.display());
         }
     }
 
     fn show_main_view(&mut self, ui: &mut egui::Ui) {
-        if let Some(ref db) = self.database {
+        if self.database.is_some() {
             let status_text = if self.is_secure {
                 "数据库安全性：正常"
             } else {
@@ -175,23 +397,25 @@ impl DatabaseGui {
         
         if self.database.is_none() {
             ui.horizontal(|ui| {
-                if ui.button("创建数据库").clicked() {
-                    self.database = Some(Database::new("test_db"));
-                    self.output_text = "数据库创建成功！".to_string();
-                    self.save_database(); // 保存新创建的数据库
+                if ui.button("新建数据库…").clicked() {
+                    self.new_database_dialog();
                 }
-                if ui.button("加载数据库").clicked() {
-                    self.load_database();
+                if ui.button("打开数据库…").clicked() {
+                    self.open_database_dialog();
                 }
             });
         } else {
-            ui.heading("数据库概览");
+            if let Some(ref db) = self.database {
+                ui.heading(format!("数据库概览：{}", db.name));
+                ui.label(format!("文件：{}", self.database_path.display()));
+            }
             if let Some(ref db) = self.database {
                 for table in &db.tables {
                     ui.collapsing(&table.name, |ui| {
                         ui.label("列：");
                         for col in &table.columns {
-                            ui.label(format!("{}: {:?}", col.name, col.data_type));
+                            let key = if col.is_primary_key { " (主键)" } else { "" };
+                            ui.label(format!("{}: {:?}{}", col.name, col.data_type, key));
                         }
                         ui.label(format!("行数：{}", table.rows.len()));
                         
@@ -211,7 +435,7 @@ impl DatabaseGui {
                             for row in &table.rows {
                                 ui.horizontal(|ui| {
                                     for value in &row.values {
-                                        ui.label(value.as_ref().unwrap_or(&"NULL".to_string()));
+                                        ui.label(cell_text(value));
                                         ui.add_space(10.0); // 添加一些間距
                                     }
                                 });
@@ -222,9 +446,24 @@ impl DatabaseGui {
                     });
                 }
             }
+            if let Some(ref mut db) = self.database {
+                let previous = db.length_policy;
+                ui.horizontal(|ui| {
+                    ui.label("文本超过列的长度时：");
+                    ui.radio_value(&mut db.length_policy, LengthPolicy::Reject, "拒绝写入");
+                    ui.radio_value(&mut db.length_policy, LengthPolicy::Truncate, "截断并警告");
+                });
+                if db.length_policy != previous {
+                    let policy = db.length_policy;
+                    self.log_change(Change::LengthPolicy(policy));
+                }
+            }
             if ui.button("保存数据库").clicked() {
+                self.error_message.clear();
                 self.save_database();
-                self.output_text = "数据库保存成功！".to_string();
+                if self.error_message.is_empty() {
+                    self.output_text = "数据库保存成功！".to_string();
+                }
             }
         }
     }
@@ -254,56 +493,95 @@ impl DatabaseGui {
                         ui.selectable_value(&mut self.new_column_type, "Char".to_string(), "Char");
                         ui.selectable_value(&mut self.new_column_type, "Bool".to_string(), "Bool");
                         ui.selectable_value(&mut self.new_column_type, "String".to_string(), "String");
+                        ui.selectable_value(&mut self.new_column_type, "Float".to_string(), "Float");
+                        ui.selectable_value(&mut self.new_column_type, "Double".to_string(), "Double");
+                        ui.selectable_value(&mut self.new_column_type, "Decimal".to_string(), "Decimal");
+                        ui.selectable_value(&mut self.new_column_type, "Date".to_string(), "Date");
+                        ui.selectable_value(&mut self.new_column_type, "Time".to_string(), "Time");
+                        ui.selectable_value(&mut self.new_column_type, "Timestamp".to_string(), "Timestamp");
+                        ui.selectable_value(&mut self.new_column_type, "TimestampTz".to_string(), "Timestamp (带时区)");
+                        ui.selectable_value(&mut self.new_column_type, "Interval".to_string(), "Interval");
+                        ui.selectable_value(&mut self.new_column_type, "Json".to_string(), "Json");
+                        ui.selectable_value(&mut self.new_column_type, "Blob".to_string(), "Blob");
                     });
 
                 if self.new_column_type == "Char" || self.new_column_type == "String" {
                     ui.label("长度：");
                     ui.text_edit_singleline(&mut self.new_column_length);
                 }
-
-                if ui.button("添加列").clicked() {
-                    if !self.new_column_name.is_empty() {
-                        let column = match self.new_column_type.as_str() {
-                            "Int" => Column {
-                                name: self.new_column_name.clone(),
-                                data_type: DataType::Int,
-                                is_primary_key: false,
-                            },
-                            "Bool" => Column {
-                                name: self.new_column_name.clone(),
-                                data_type: DataType::Bool,
-                                is_primary_key: false,
-                            },
-                            "Char" => {
-                                if let Ok(len) = self.new_column_length.parse() {
-                                    Column {
-                                        name: self.new_column_name.clone(),
-                                        data_type: DataType::Char(len),
-                                        is_primary_key: false,
+                if self.new_column_type == "Blob" {
+                    ui.label("最大字节数：");
+                    ui.text_edit_singleline(&mut self.new_column_length);
+                }
+                if self.new_column_type == "Decimal" {
+                    ui.label("总位数：");
+                    ui.text_edit_singleline(&mut self.new_column_length);
+                    ui.label("小数位数：");
+                    ui.text_edit_singleline(&mut self.new_column_scale);
+                }
+                ui.checkbox(&mut self.new_column_is_primary_key, "主键");
+
+                if ui.button("添加列").clicked() && !self.new_column_name.is_empty() {
+                    let data_type = match self.new_column_type.as_str() {
+                        "Int" => DataType::Int,
+                        "Bool" => DataType::Bool,
+                        "Float" => DataType::Float,
+                        "Double" => DataType::Double,
+                        "Date" => DataType::Date,
+                        "Time" => DataType::Time,
+                        "Timestamp" => DataType::Timestamp,
+                        "TimestampTz" => DataType::TimestampTz,
+                        "Interval" => DataType::Interval,
+                        "Json" => DataType::Json,
+                        "Blob" => {
+                            // 留空时使用默认的大小限制
+                            match self.new_column_length.trim() {
+                                "" => DataType::Blob(DEFAULT_MAX_SIZE),
+                                length => match length.parse::<u32>() {
+                                    Ok(max_size) if max_size > 0 => DataType::Blob(max_size),
+                                    _ => {
+                                        self.error_message = "无效的最大字节数".to_string();
+                                        return;
                                     }
-                                } else {
-                                    self.error_message = "无效的长度值".to_string();
+                                },
+                            }
+                        }
+                        "Decimal" => {
+                            // 小数位数留空时为 0
+                            let scale = if self.new_column_scale.trim().is_empty() { Ok(0) } else { self.new_column_scale.trim().parse() };
+                            match (self.new_column_length.trim().parse::<u32>(), scale) {
+                                (Ok(precision), Ok(scale)) if (1..=MAX_PRECISION).contains(&precision) && scale <= precision => {
+                                    DataType::Decimal(precision, scale)
+                                }
+                                _ => {
+                                    self.error_message = format!("无效的总位数或小数位数：总位数应在 1 到 {} 之间，小数位数不能大于总位数", MAX_PRECISION);
                                     return;
                                 }
-                            },
-                            "String" => {
-                                if let Ok(len) = self.new_column_length.parse() {
-                                    Column {
-                                        name: self.new_column_name.clone(),
-                                        data_type: DataType::String(len),
-                                        is_primary_key: false,
-                                    }
+                            }
+                        }
+                        "Char" | "String" => {
+                            if let Ok(len) = self.new_column_length.parse() {
+                                if self.new_column_type == "Char" {
+                                    DataType::Char(len)
                                 } else {
-                                    self.error_message = "无效的长度值".to_string();
-                                    return;
+                                    DataType::String(len)
                                 }
-                            },
-                            _ => return,
-                        };
-                        self.temp_columns.push(column);
-                        self.new_column_name.clear();
-                        self.new_column_length.clear();
-                    }
+                            } else {
+                                self.error_message = "无效的长度值".to_string();
+                                return;
+                            }
+                        },
+                        _ => return,
+                    };
+                    let column = Column {
+                        is_primary_key: self.new_column_is_primary_key,
+                        ..Column::new(&self.new_column_name, data_type)
+                    };
+                    self.temp_columns.push(column);
+                    self.new_column_name.clear();
+                    self.new_column_length.clear();
+                    self.new_column_scale.clear();
+                    self.new_column_is_primary_key = false;
                 }
             });
         });
@@ -312,9 +590,10 @@ impl DatabaseGui {
         ui.group(|ui| {
             ui.label("当前列：");
             let mut columns_to_remove = Vec::new(); // 收集要删除的列名
-            for (i, col) in self.temp_columns.iter().enumerate() {
+            for col in &self.temp_columns {
                 ui.horizontal(|ui| {
-                    ui.label(format!("{}: {:?}", col.name, col.data_type));
+                    let key = if col.is_primary_key { " (主键)" } else { "" };
+                    ui.label(format!("{}: {:?}{}", col.name, col.data_type, key));
                     if ui.button("删除").clicked() {
                         let col_name = col.name.clone();
                         columns_to_remove.push(col_name); // 将要删除的列名添加到列表中
@@ -334,12 +613,16 @@ impl DatabaseGui {
                         name: self.new_table_name.clone(),
                         columns: self.temp_columns.clone(),
                         rows: Vec::new(),
+                        foreign_keys: Vec::new(),
                     };
-                    db.create_table(table);
+                    if let Err(e) = db.create_table(table.clone()) {
+                        self.error_message = e.to_string();
+                        return;
+                    }
                     self.new_table_name.clear();
                     self.temp_columns.clear();
                     self.output_text = "表创建成功！".to_string();
-                    self.save_database(); // 保存更改
+                    self.log_change(Change::CreateTable(table)); // 保存更改
                 } else {
                     self.error_message = "表名和列不能为空".to_string();
                 }
@@ -370,24 +653,45 @@ impl DatabaseGui {
                     for col in &table.columns {
                         ui.horizontal(|ui| {
                             ui.label(&col.name);
-                            let value = self.insert_values.entry(col.name.clone()).or_insert_with(String::new);
+                            let value = self.insert_values.entry(col.name.clone()).or_default();
                             ui.text_edit_singleline(value);
                         });
                     }
                 });
 
                 if ui.button("插入").clicked() {
+                    // 未填写的列取默认值，没有默认值时为 NULL
                     let mut values = Vec::new();
                     for col in &table.columns {
-                        values.push(Some(self.insert_values.get(&col.name).cloned().unwrap_or_default()));
+                        match self.insert_values.get(&col.name).filter(|v| !v.is_empty()) {
+                            Some(value) => values.push(Value::Text(value.clone())),
+                            None => match col.default_value() {
+                                Ok(default) => values.push(default),
+                                Err(e) => {
+                                    self.error_message = e.to_string();
+                                    return;
+                                }
+                            },
+                        }
                     }
-                    if let Some(ref mut db) = self.database {
-                        let row = Row { values };
-                        db.insert_row(&self.selected_table, row);
-                        self.output_text = "数据插入成功！".to_string();
-                        self.insert_values.clear();
+                    let Some(ref mut db) = self.database else {
+                        return;
+                    };
+                    let row = Row { values };
+                    self.output_text = match db.insert_row(&self.selected_table, row) {
+                        Ok(0) => "数据插入成功！".to_string(),
+                        Ok(truncated) => format!("数据插入成功！警告: {} 个值超过列的长度限制，已被截断", truncated),
+                        Err(e) => {
+                            self.error_message = e.to_string();
+                            return;
+                        }
+                    };
+                    self.insert_values.clear();
+                    // 记录转换为列的类型之后的行，重放时不必再次计算默认值
+                    let inserted = db.table(&self.selected_table).ok().and_then(|t| t.rows.last().cloned());
+                    if let Some(row) = inserted {
+                        self.log_change(Change::InsertRow { table: self.selected_table.clone(), row: StoredRow::from(&row) }); // 保存更改
                     }
-                    self.save_database(); // 保存更改
                 }
             }
         }
@@ -408,7 +712,7 @@ impl DatabaseGui {
         });
 
         if ui.button("执行查询").clicked() {
-            self.execute_sql_query(ui);
+            self.execute_sql_query();
         }
 
         // 输出区域
@@ -419,266 +723,96 @@ impl DatabaseGui {
         });
     }
 
-    fn execute_sql_query(&mut self, _ui: &mut egui::Ui) {
+    fn execute_sql_query(&mut self) {
+        let query = self.query_input.trim().to_string();
         if let Some(ref mut db) = self.database {
-            let query = self.query_input.trim();
-            let query_upper = query.to_uppercase();
-
-            if query_upper.starts_with("SELECT") {
-                // 解析 SELECT 語句
-                let parts: Vec<&str> = query_upper.split("FROM").collect();
-                if parts.len() != 2 {
-                    self.error_message = "無效的 SELECT 語句格式".to_string();
-                    return;
+            // 固定语句的当前时间，重放日志时 NOW() 等取值不变
+            let now = Timestamp::now();
+            let modified = match db.execute_at(&query, now) {
+                Ok(result) => {
+                    self.output_text = format_result(&result);
+                    !result.is_query()
                 }
-
-                let from_part = parts[1].trim();
-                
-                if from_part.contains("JOIN") {
-                    // ... 保持現有的 JOIN 邏輯 ...
-                } else if from_part.contains("AND") {
-                    // 處理多表並行查詢
-                    let table_names: Vec<&str> = from_part
-                        .split("AND")
-                        .map(|s| s.trim())
-                        .collect();
-
-                    let mut output = String::with_capacity(1024);
-                    
-                    // 為每個表生成查詢結果
-                    for table_name in table_names {
-                        if let Some(table) = db.tables.iter().find(|t| t.name.to_uppercase() == table_name) {
-                            // 添加表名作為標題
-                            output.push_str(&format!("\n表 {} 的查詢結果：\n", table_name));
-                            
-                            // 生成表頭
-                            let header = table.columns.iter()
-                                .map(|col| format!("{:<20}", col.name))
-                                .collect::<Vec<_>>()
-                                .join(" | ");
-                            output.push_str(&header);
-                            output.push('\n');
-
-                            // 添加分隔線
-                            let separator = "-".repeat(header.len());
-                            output.push_str(&separator);
-                            output.push('\n');
-
-                            // 添加數據行
-                            for row in &table.rows {
-                                let row_str = row.values.iter()
-                                    .map(|v| format!("{:<20}", v.as_ref().unwrap_or(&"NULL".to_string())))
-                                    .collect::<Vec<_>>()
-                                    .join(" | ");
-                                output.push_str(&row_str);
-                                output.push('\n');
-                            }
-                            
-                            // 添加表間分隔
-                            output.push_str("\n");
-                        } else {
-                            self.error_message = format!("表 '{}' 不存在", table_name);
-                            return;
-                        }
-                    }
-
-                    self.output_text = output;
-                } else {
-                    // 這裡是單表查詢的邏輯
-                    let table_name = from_part.split_whitespace().next().unwrap_or("");
-                    if let Some(table) = db.tables.iter().find(|t| t.name.to_uppercase() == table_name) {
-                        // 添加表名作為標題
-                        self.output_text = format!("\n表 {} 的查詢結果：\n", table_name);
-                        
-                        // 生成表頭
-                        let header = table.columns.iter()
-                            .map(|col| format!("{:<20}", col.name))
-                            .collect::<Vec<_>>()
-                            .join(" | ");
-                        self.output_text.push_str(&header);
-                        self.output_text.push('\n');
-
-                        // 添加分隔線
-                        let separator = "-".repeat(header.len());
-                        self.output_text.push_str(&separator);
-                        self.output_text.push('\n');
-
-                        // 添加數據行
-                        for row in &table.rows {
-                            let row_str = row.values.iter()
-                                .map(|v| format!("{:<20}", v.as_ref().unwrap_or(&"NULL".to_string())))
-                                .collect::<Vec<_>>()
-                                .join(" | ");
-                            self.output_text.push_str(&row_str);
-                            self.output_text.push('\n');
-                        }
-                    } else {
-                        self.error_message = format!("表 '{}' 不存在", table_name);
-                    }
-                }
-            } else if query_upper.starts_with("INSERT INTO") {
-                // 解析 INSERT 語句
-                let parts: Vec<&str> = query.split("VALUES").collect();
-                if parts.len() != 2 {
-                    self.error_message = "無效的 INSERT 語句格式".to_string();
+                Err(e) => {
+                    self.error_message = e.to_string();
                     return;
                 }
+            };
+            if modified {
+                self.log_change(Change::sql(&query, now)); // 保存更改
+            }
+        }
+    }
+}
 
-                // 獲取表名
-                let table_name = parts[0]
-                    .trim()
-                    .strip_prefix("INSERT INTO")
-                    .unwrap_or("")
-                    .trim();
-
-                // 解析值
-                let values_str = parts[1]
-                    .trim()
-                    .trim_start_matches('(')
-                    .trim_end_matches(')')
-                    .trim();
-
-                // 分割值
-                let values: Vec<String> = values_str
-                    .split(',')
-                    .map(|s| s.trim().trim_matches('\'').to_string())
-                    .collect();
-
-                // 查找表
-                if let Some(table) = db.tables.iter().find(|t| t.name == table_name) {
-                    // 檢查值的數量是否匹配列的數量
-                    if values.len() != table.columns.len() {
-                        self.error_message = format!(
-                            "值的數量 ({}) 與列的數量 ({}) 不匹配",
-                            values.len(),
-                            table.columns.len()
-                        );
-                        return;
-                    }
-
-                    // 創建新行
-                    let row = Row {
-                        values: values.into_iter().map(Some).collect(),
-                    };
-
-                    // 插入數據
-                    db.insert_row(table_name, row);
-                    self.output_text = "數據插入成功！".to_string();
-                    self.save_database(); // 保存更改
-                } else {
-                    self.error_message = format!("表 '{}' 不存在", table_name);
-                }
-            } else if query_upper.starts_with("DELETE FROM") {
-                // 解析 DELETE 語句
-                let parts: Vec<&str> = query[11..].trim().split("WHERE").collect();
-                let table_name = parts[0].trim();
-
-                if let Some(table) = db.tables.iter_mut().find(|t| t.name == table_name) {
-                    if parts.len() == 1 {
-                        // 無 WHERE 子句，刪除所有記錄
-                        table.rows.clear();
-                        self.output_text = format!("已刪除表 '{}' 中的所有記錄", table_name);
-                    } else {
-                        // 解析 WHERE 子句
-                        let condition = parts[1].trim();
-                        let cond_parts: Vec<&str> = condition.split('=').collect();
-                        if cond_parts.len() == 2 {
-                            let col_name = cond_parts[0].trim();
-                            let value = cond_parts[1].trim().trim_matches('\'').trim_matches('"');
-
-                            // 找到列的索引
-                            if let Some(col_idx) = table.columns.iter().position(|c| c.name == col_name) {
-                                // 刪除匹配的記錄
-                                let initial_len = table.rows.len();
-                                table.rows.retain(|row| {
-                                    row.values[col_idx].as_ref().map_or(true, |v| v != value)
-                                });
-                                let deleted_count = initial_len - table.rows.len();
-                                self.output_text = format!("已刪除 {} 條記錄", deleted_count);
-                            } else {
-                                self.error_message = format!("列 '{}' 不存在", col_name);
-                                return;
-                            }
-                        } else {
-                            self.error_message = "無效的 WHERE 子句格式".to_string();
-                            return;
-                        }
-                    }
-                } else {
-                    self.error_message = format!("表 '{}' 不存在", table_name);
-                    return;
-                }
-            } else if query_upper.starts_with("UPDATE") {
-                // 解析 UPDATE 語句
-                let parts: Vec<&str> = query[6..].trim().split("SET").collect();
-                if parts.len() != 2 {
-                    self.error_message = "無效的 UPDATE 語句格式".to_string();
-                    return;
-                }
+// 选择数据库文件的对话框
+// 界面把数据库整体读入内存，不打开分页数据库文件（.rspg），它们只能在命令行中使用
+fn paged_file_error(path: &Path) -> Option<String> {
+    page_store::is_paged(path).then(|| {
+        format!(
+            "{} 是分页数据库文件，只能在命令行中使用（sql_homework sql <文件> <SQL>）；\
+             可先用 sql_homework convert 转换为 JSON 或二进制格式再在界面中打开",
+            path.display()
+        )
+    })
+}
 
-                let table_name = parts[0].trim();
-                let remaining: Vec<&str> = parts[1].trim().split("WHERE").collect();
-                
-                if remaining.len() != 2 {
-                    self.error_message = "UPDATE 語句必須包含 WHERE 子句".to_string();
-                    return;
-                }
+// 另存为时选择二进制格式的扩展名即转换为二进制文件，反之亦然
+fn database_file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new()
+        .add_filter("数据库文件", &["json", StorageFormat::BINARY_EXTENSION])
+        .add_filter("JSON 数据库文件", &["json"])
+        .add_filter("二进制数据库文件", &[StorageFormat::BINARY_EXTENSION])
+}
 
-                let set_clause = remaining[0].trim();
-                let where_clause = remaining[1].trim();
+// 读取最近打开的数据库列表，文件不存在或无法解析时为空
+fn load_recent_files() -> Vec<PathBuf> {
+    fs::read_to_string(RECENT_FILES_FILE)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
 
-                // 解析 SET 子句
-                let set_parts: Vec<&str> = set_clause.split('=').collect();
-                if set_parts.len() != 2 {
-                    self.error_message = "無效的 SET 子句格式".to_string();
-                    return;
-                }
+fn save_recent_files(files: &[PathBuf]) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(files).map_err(std::io::Error::other)?;
+    storage::write_atomic(Path::new(RECENT_FILES_FILE), json.as_bytes())
+}
 
-                let update_col = set_parts[0].trim();
-                let new_value = set_parts[1].trim().trim_matches('\'').trim_matches('"');
+// 单元格中显示的文本，二进制数据只显示大小和开头部分的十六进制
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Blob(bytes) => blob::preview(bytes),
+        value => value.to_string(),
+    }
+}
 
-                // 解析 WHERE 子句
-                let where_parts: Vec<&str> = where_clause.split('=').collect();
-                if where_parts.len() != 2 {
-                    self.error_message = "無效的 WHERE 子句格式".to_string();
-                    return;
-                }
+// 将查询结果格式化为文本表格
+fn format_result(result: &QueryResult) -> String {
+    if !result.is_query() {
+        return result.message.clone();
+    }
 
-                let where_col = where_parts[0].trim();
-                let where_value = where_parts[1].trim().trim_matches('\'').trim_matches('"');
-
-                if let Some(table) = db.tables.iter_mut().find(|t| t.name == table_name) {
-                    // 找到相關列的索引
-                    let update_col_idx = table.columns.iter().position(|c| c.name == update_col);
-                    let where_col_idx = table.columns.iter().position(|c| c.name == where_col);
-
-                    match (update_col_idx, where_col_idx) {
-                        (Some(update_idx), Some(where_idx)) => {
-                            let mut update_count = 0;
-                            for row in &mut table.rows {
-                                if row.values[where_idx].as_ref().map_or(false, |v| v == where_value) {
-                                    row.values[update_idx] = Some(new_value.to_string());
-                                    update_count += 1;
-                                }
-                            }
-                            self.output_text = format!("已更新 {} 條記錄", update_count);
-                        }
-                        _ => {
-                            self.error_message = "指定的列不存在".to_string();
-                            return;
-                        }
-                    }
-                } else {
-                    self.error_message = format!("表 '{}' 不存在", table_name);
-                    return;
-                }
-            } else {
-                self.error_message = "不支持的 SQL 命令".to_string();
-                return;
-            }
-            self.save_database(); // 保存更改
-        }
+    let mut output = String::new();
+    let header = result.columns.iter()
+        .map(|col| format!("{:<20}", col.name))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    output.push_str(&header);
+    output.push('\n');
+    output.push_str(&"-".repeat(header.len()));
+    output.push('\n');
+
+    for row in &result.rows {
+        let row_str = row.values.iter()
+            .map(|v| format!("{:<20}", cell_text(v)))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        output.push_str(&row_str);
+        output.push('\n');
     }
+    output.push_str(&result.message);
+    output.push_str("\n\n");
+    output
 }
 
 pub fn run_gui() -> Result<(), eframe::Error> {
@@ -710,7 +844,7 @@ pub fn run_gui() -> Result<(), eframe::Error> {
             cc.egui_ctx.set_fonts(fonts);
             
             let mut gui = DatabaseGui::default();
-            gui.load_database(); // 启动时加载数据库
+            gui.load_initial_database(); // 启动时加载数据库
             Ok(Box::new(gui))
         })
     )
@@ -0,0 +1,344 @@
+use crate::ast::*;
+use crate::eval::{is_true, resolve_column, row_values, table_scope, Scope, ScopeColumn};
+use crate::part1::{DataType, Database, DbError, ResultColumn};
+use crate::value::{values_key, Value};
+use std::collections::HashMap;
+
+// FROM 子句求值得到的中间结果
+pub struct Relation {
+    pub columns: Vec<ScopeColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+// 连接的执行方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JoinStrategy {
+    NestedLoop,
+    // 按等值键对右表建立哈希表，再用左表的键探测；键为 (左列下标, 右列下标)
+    Hash,
+}
+
+// 计算 FROM 子句，outer 为外层查询的当前行
+pub fn build_relation(db: &Database, from: &TableRef, outer: Option<&Scope>) -> Result<Relation, DbError> {
+    let mut qualifiers = Vec::new();
+    check_qualifiers(db, from, &mut qualifiers)?;
+    let env = Scope {
+        outer,
+        db: Some(db),
+        ..Scope::empty()
+    };
+    relation(db, from, &env)
+}
+
+// 同一个表名或别名在 FROM 中只能出现一次，否则 table.column 无法区分
+fn check_qualifiers(db: &Database, from: &TableRef, seen: &mut Vec<String>) -> Result<(), DbError> {
+    let qualifier = match from {
+        TableRef::Table { alias: Some(alias), .. } | TableRef::Subquery { alias, .. } => alias.clone(),
+        TableRef::Table { name, alias: None } => db.table(name)?.name.clone(),
+        TableRef::Join { left, right, .. } => {
+            check_qualifiers(db, left, seen)?;
+            return check_qualifiers(db, right, seen);
+        }
+    };
+    if seen.iter().any(|q| q.eq_ignore_ascii_case(&qualifier)) {
+        return Err(DbError::InvalidQuery(format!(
+            "表名或别名 '{}' 在 FROM 中出现多次，请使用别名区分",
+            qualifier
+        )));
+    }
+    seen.push(qualifier);
+    Ok(())
+}
+
+fn relation(db: &Database, from: &TableRef, env: &Scope) -> Result<Relation, DbError> {
+    match from {
+        TableRef::Table { name, alias } => {
+            let table = db.table(name)?;
+            Ok(Relation {
+                columns: table_scope(table, alias.as_deref().unwrap_or(&table.name)),
+                rows: table.rows.iter().map(|row| row_values(table, row)).collect(),
+            })
+        }
+        TableRef::Subquery { query, alias } => {
+            let output = db.run_query(query, env.outer)?;
+            Ok(Relation {
+                columns: derived_columns(output.columns, alias),
+                rows: output.rows,
+            })
+        }
+        TableRef::Join { left, right, kind, constraint } => {
+            let left = relation(db, left, env)?;
+            let right = relation(db, right, env)?;
+            join(left, right, *kind, constraint, env)
+        }
+    }
+}
+
+// FROM 子句的列，不读取任何行；其中派生表和 ON 条件里的列引用同时被解析，见 Database::resolve_query
+pub fn relation_columns(db: &Database, from: &TableRef, outer: Option<&Scope>) -> Result<Vec<ScopeColumn>, DbError> {
+    check_qualifiers(db, from, &mut Vec::new())?;
+    scope_columns(db, from, outer)
+}
+
+fn scope_columns(db: &Database, from: &TableRef, outer: Option<&Scope>) -> Result<Vec<ScopeColumn>, DbError> {
+    match from {
+        TableRef::Table { name, alias } => {
+            let table = db.table(name)?;
+            Ok(table_scope(table, alias.as_deref().unwrap_or(&table.name)))
+        }
+        TableRef::Subquery { query, alias } => Ok(derived_columns(db.resolve_query(query, outer)?, alias)),
+        TableRef::Join { left, right, constraint, .. } => {
+            let left = scope_columns(db, left, outer)?;
+            let right = scope_columns(db, right, outer)?;
+            let using = using_columns(&left, &right, constraint)?;
+            let columns = join_columns(&left, &right, &using);
+            if let JoinConstraint::On(expr) = constraint {
+                db.resolve_expr(expr, &columns, outer)?;
+            }
+            Ok(columns)
+        }
+    }
+}
+
+// 派生表的列以别名限定
+fn derived_columns(columns: Vec<ResultColumn>, alias: &str) -> Vec<ScopeColumn> {
+    columns
+        .into_iter()
+        .map(|col| ScopeColumn {
+            table: Some(alias.to_string()),
+            name: col.name,
+            data_type: col.data_type,
+            qualified_only: false,
+        })
+        .collect()
+}
+
+// USING 的列对 (左下标, 右下标)
+fn using_columns(
+    left: &[ScopeColumn],
+    right: &[ScopeColumn],
+    constraint: &JoinConstraint,
+) -> Result<Vec<(usize, usize)>, DbError> {
+    match constraint {
+        JoinConstraint::Using(names) => names
+            .iter()
+            .map(|name| Ok((resolve_column(left, None, name)?, resolve_column(right, None, name)?)))
+            .collect(),
+        _ => Ok(Vec::new()),
+    }
+}
+
+// 连接结果的列：USING 合并后的列在最前，原来两侧的同名列只能通过限定名引用
+fn join_columns(left: &[ScopeColumn], right: &[ScopeColumn], using: &[(usize, usize)]) -> Vec<ScopeColumn> {
+    let mut columns: Vec<ScopeColumn> = using
+        .iter()
+        .map(|&(l, _)| ScopeColumn {
+            table: None,
+            qualified_only: false,
+            ..left[l].clone()
+        })
+        .collect();
+    columns.extend(left.iter().enumerate().map(|(i, c)| ScopeColumn {
+        qualified_only: c.qualified_only || using.iter().any(|&(l, _)| l == i),
+        ..c.clone()
+    }));
+    columns.extend(right.iter().enumerate().map(|(i, c)| ScopeColumn {
+        qualified_only: c.qualified_only || using.iter().any(|&(_, r)| r == i),
+        ..c.clone()
+    }));
+    columns
+}
+
+fn join(
+    left: Relation,
+    right: Relation,
+    kind: JoinKind,
+    constraint: &JoinConstraint,
+    env: &Scope,
+) -> Result<Relation, DbError> {
+    let using = using_columns(&left.columns, &right.columns, constraint)?;
+    let columns = join_columns(&left.columns, &right.columns, &using);
+    let prefix = using.len();
+    let left_width = left.columns.len();
+    let right_width = right.columns.len();
+
+    let combine = |l: Option<&Vec<Value>>, r: Option<&Vec<Value>>| -> Vec<Value> {
+        let mut values = Vec::with_capacity(columns.len());
+        for &(li, ri) in &using {
+            let value = match (l.map(|l| &l[li]), r.map(|r| &r[ri])) {
+                (Some(v), _) if !v.is_null() => v.clone(),
+                (_, Some(v)) => v.clone(),
+                _ => Value::Null,
+            };
+            values.push(value);
+        }
+        match l {
+            Some(l) => values.extend(l.iter().cloned()),
+            None => values.extend(std::iter::repeat_n(Value::Null, left_width)),
+        }
+        match r {
+            Some(r) => values.extend(r.iter().cloned()),
+            None => values.extend(std::iter::repeat_n(Value::Null, right_width)),
+        }
+        values
+    };
+
+    // 连接条件：USING 为各列相等，ON 为任意表达式，CROSS 无条件
+    let satisfies = |values: &[Value]| -> Result<bool, DbError> {
+        match constraint {
+            JoinConstraint::On(expr) => is_true(expr, &Scope { columns: &columns, values, ..*env }),
+            JoinConstraint::Using(_) => {
+                for &(li, ri) in &using {
+                    let (l, r) = (&values[prefix + li], &values[prefix + left_width + ri]);
+                    if l.compare(r)? != Some(std::cmp::Ordering::Equal) {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            JoinConstraint::None => Ok(true),
+        }
+    };
+
+    let keys = match constraint {
+        JoinConstraint::Using(_) => using.clone(),
+        JoinConstraint::On(expr) => equi_keys(expr, &columns, prefix, left_width),
+        JoinConstraint::None => Vec::new(),
+    };
+    let keys: Vec<(usize, usize)> = keys
+        .into_iter()
+        .filter(|&(l, r)| hashable(&left.columns[l].data_type, &right.columns[r].data_type))
+        .collect();
+    let strategy = if keys.is_empty() {
+        JoinStrategy::NestedLoop
+    } else {
+        JoinStrategy::Hash
+    };
+
+    // 哈希连接时对右表建表，含 NULL 的键不会与任何行相等
+    let mut buckets: HashMap<Vec<(&'static str, String)>, Vec<usize>> = HashMap::new();
+    if strategy == JoinStrategy::Hash {
+        for (i, row) in right.rows.iter().enumerate() {
+            let key: Vec<Value> = keys.iter().map(|&(_, r)| row[r].clone()).collect();
+            if !key.iter().any(Value::is_null) {
+                buckets.entry(values_key(&key)).or_default().push(i);
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut right_matched = vec![false; right.rows.len()];
+    let all_right: Vec<usize> = (0..right.rows.len()).collect();
+    for l in &left.rows {
+        let candidates: &[usize] = match strategy {
+            JoinStrategy::NestedLoop => &all_right,
+            JoinStrategy::Hash => {
+                let key: Vec<Value> = keys.iter().map(|&(li, _)| l[li].clone()).collect();
+                buckets.get(&values_key(&key)).map(Vec::as_slice).unwrap_or(&[])
+            }
+        };
+        let mut matched = false;
+        for &ri in candidates {
+            let values = combine(Some(l), Some(&right.rows[ri]));
+            if satisfies(&values)? {
+                matched = true;
+                right_matched[ri] = true;
+                rows.push(values);
+            }
+        }
+        if !matched && matches!(kind, JoinKind::Left | JoinKind::Full) {
+            rows.push(combine(Some(l), None));
+        }
+    }
+    if matches!(kind, JoinKind::Right | JoinKind::Full) {
+        for (r, matched) in right.rows.iter().zip(right_matched) {
+            if !matched {
+                rows.push(combine(None, Some(r)));
+            }
+        }
+    }
+
+    Ok(Relation { columns, rows })
+}
+
+// 从 ON 条件的 AND 各项中找出 左列 = 右列 形式的等值键，返回两侧关系内的列下标
+fn equi_keys(expr: &Expr, columns: &[ScopeColumn], prefix: usize, left_width: usize) -> Vec<(usize, usize)> {
+    let side = |expr: &Expr| -> Option<usize> {
+        let idx = match expr {
+            Expr::Column(name) => resolve_column(columns, None, name).ok()?,
+            Expr::QualifiedColumn { table, name } => resolve_column(columns, Some(table), name).ok()?,
+            _ => return None,
+        };
+        idx.checked_sub(prefix)
+    };
+    match expr {
+        Expr::Binary { left, op: BinaryOp::And, right } => {
+            let mut keys = equi_keys(left, columns, prefix, left_width);
+            keys.extend(equi_keys(right, columns, prefix, left_width));
+            keys
+        }
+        Expr::Binary { left, op: BinaryOp::Eq, right } => match (side(left), side(right)) {
+            (Some(a), Some(b)) if a < left_width && b >= left_width => vec![(a, b - left_width)],
+            (Some(a), Some(b)) if b < left_width && a >= left_width => vec![(b, a - left_width)],
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+// 两列的值按相同规则比较时才能用哈希键代替比较
+fn hashable(left: &Option<DataType>, right: &Option<DataType>) -> bool {
+    matches!(
+        (left, right),
+        (Some(DataType::Int), Some(DataType::Int))
+            | (Some(DataType::Bool), Some(DataType::Bool))
+            | (
+                Some(DataType::Char(_) | DataType::String(_)),
+                Some(DataType::Char(_) | DataType::String(_))
+            )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::part1::{Database, DbError};
+
+    fn database() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE p (id INT PRIMARY KEY, name VARCHAR(10))").unwrap();
+        db.execute("CREATE TABLE o (pid INT, item VARCHAR(10))").unwrap();
+        db.execute("INSERT INTO p VALUES (1, 'ann'), (2, 'bob')").unwrap();
+        db.execute("INSERT INTO o VALUES (1, 'x'), (1, 'y'), (9, 'z')").unwrap();
+        db
+    }
+
+    // 各行的值以 | 连接
+    fn rows(db: &mut Database, sql: &str) -> Vec<String> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("|")).collect()
+    }
+
+    #[test]
+    fn join_kinds_keep_unmatched_rows_on_the_right_side() {
+        let mut db = database();
+        assert_eq!(rows(&mut db, "SELECT p.name, o.item FROM p JOIN o ON o.pid = p.id ORDER BY o.item"), ["ann|x", "ann|y"]);
+        assert_eq!(
+            rows(&mut db, "SELECT p.name, o.item FROM p LEFT JOIN o ON o.pid = p.id ORDER BY p.id, o.item"),
+            ["ann|x", "ann|y", "bob|NULL"]
+        );
+        assert_eq!(
+            rows(&mut db, "SELECT o.item, p.name FROM p RIGHT OUTER JOIN o ON o.pid = p.id ORDER BY o.item"),
+            ["x|ann", "y|ann", "z|NULL"]
+        );
+        assert_eq!(rows(&mut db, "SELECT COUNT(*) FROM p FULL JOIN o ON o.pid = p.id"), ["4"]);
+        assert_eq!(rows(&mut db, "SELECT COUNT(*) FROM p CROSS JOIN o"), ["6"]);
+        assert_eq!(rows(&mut db, "SELECT COUNT(*) FROM p, o"), ["6"]);
+
+        // USING 合并同名列，自连接需要别名区分
+        db.execute("CREATE TABLE q (id INT, v INT)").unwrap();
+        db.execute("INSERT INTO q VALUES (2, 20)").unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM p JOIN q USING (id)"), ["2|bob|20"]);
+        assert_eq!(rows(&mut db, "SELECT a.id, b.id FROM p a JOIN p b ON b.id = a.id + 1"), ["1|2"]);
+        assert!(matches!(db.execute("SELECT id FROM p JOIN q ON q.id = p.id"), Err(DbError::InvalidQuery(_))));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::decimal::Decimal;
+use crate::part1::DbError;
+use crate::value::Value;
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+
+// 解析 JSON 文本
+pub fn parse(text: &str) -> Result<JsonValue, DbError> {
+    serde_json::from_str(text).map_err(|e| DbError::TypeMismatch(format!("无效的 JSON '{}': {}", text, e)))
+}
+
+// 作为 JSON 使用的值：Json 原样使用，文本按 JSON 解析，以便查询保存在 VARCHAR 列中的 JSON
+pub fn as_json(value: &Value) -> Result<Cow<'_, JsonValue>, DbError> {
+    match value {
+        Value::Json(json) => Ok(Cow::Borrowed(json)),
+        Value::Text(text) => parse(text).map(Cow::Owned),
+        other => Err(DbError::TypeMismatch(format!("{} '{}' 不是 JSON", other.type_name(), other))),
+    }
+}
+
+// 取数组元素或对象成员：key 为整数时取数组下标（从 0 开始，负数从末尾数起），为文本时取对象的键；不存在时为 None
+pub fn get<'a>(json: &'a JsonValue, key: &Value) -> Result<Option<&'a JsonValue>, DbError> {
+    match key {
+        Value::Int(index) => Ok(json.as_array().and_then(|array| {
+            let index = if *index < 0 { array.len() as i64 + index } else { *index };
+            usize::try_from(index).ok().and_then(|i| array.get(i))
+        })),
+        Value::Text(key) => Ok(json.as_object().and_then(|object| object.get(key))),
+        other => Err(DbError::TypeMismatch(format!(
+            "JSON 的键必须是整数或文本，实际为 {} '{}'",
+            other.type_name(),
+            other
+        ))),
+    }
+}
+
+// 按 $.a.b[0] 形式的路径取值，键中有特殊字符时可以加双引号，如 $."first name"
+pub fn extract<'a>(json: &'a JsonValue, path: &str) -> Result<Option<&'a JsonValue>, DbError> {
+    let mut current = json;
+    for step in parse_path(path)? {
+        match get(current, &step)? {
+            Some(next) => current = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(current))
+}
+
+// 路径的各级键，对象的键为 Text，数组下标为 Int
+fn parse_path(path: &str) -> Result<Vec<Value>, DbError> {
+    let invalid = || DbError::TypeMismatch(format!("无效的 JSON 路径 '{}'，应形如 $.a.b[0]", path));
+    let chars: Vec<char> = path.trim().chars().collect();
+    if chars.first() != Some(&'$') {
+        return Err(invalid());
+    }
+    let mut steps = Vec::new();
+    let mut i = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '.' if chars.get(i + 1) == Some(&'"') => {
+                let end = chars[i + 2..].iter().position(|&c| c == '"').ok_or_else(invalid)? + i + 2;
+                steps.push(Value::Text(chars[i + 2..end].iter().collect()));
+                i = end + 1;
+            }
+            '.' => {
+                let start = i + 1;
+                let end = chars[start..]
+                    .iter()
+                    .position(|&c| c == '.' || c == '[')
+                    .map_or(chars.len(), |n| start + n);
+                if start == end {
+                    return Err(invalid());
+                }
+                steps.push(Value::Text(chars[start..end].iter().collect()));
+                i = end;
+            }
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']').ok_or_else(invalid)? + i;
+                let index: String = chars[i + 1..end].iter().collect();
+                steps.push(Value::Int(index.trim().parse().map_err(|_| invalid())?));
+                i = end + 1;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(steps)
+}
+
+// JSON 值对应的 SQL 值：字符串不带引号，整数为 Int，超出 i64 的正整数为 Decimal 以免损失精度，
+// 其他数字为 Float，null 为 NULL，数组和对象为 JSON 文本
+pub fn to_sql(json: &JsonValue) -> Value {
+    match json {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Bool(*b),
+        JsonValue::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Int(i),
+            (None, Some(u)) => Value::Decimal(Decimal::new(u as i128, 0)),
+            _ => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::String(s) => Value::Text(s.clone()),
+        JsonValue::Array(_) | JsonValue::Object(_) => Value::Text(json.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::part1::{Database, DbError};
+
+    #[test]
+    fn path_queries_return_json_or_sql_values() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE j (doc JSON)").unwrap();
+        db.execute(r#"INSERT INTO j VALUES ('{"a": {"b": [1, 2, 3]}, "n": "x", "age": 20}'), ('null')"#).unwrap();
+        assert!(matches!(db.execute("INSERT INTO j VALUES ('{bad')"), Err(DbError::TypeMismatch(_))));
+        let result = db
+            .execute("SELECT doc->'a'->'b'->-1, doc->>'n', JSON_EXTRACT(doc, '$.a.b[0]'), JSON_ARRAY_LENGTH(doc, '$.a.b'), doc->>'zz' FROM j WHERE doc->>'age' > 18")
+            .unwrap();
+        let values: Vec<String> = result.rows[0].values.iter().map(|v| format!("{}:{}", v.type_name(), v)).collect();
+        assert_eq!(values, ["Json:3", "Text:x", "Json:1", "Int:3", "NULL:NULL"]);
+        // 顶层的 JSON null 按 SQL NULL 保存
+        assert_eq!(db.execute("SELECT * FROM j WHERE doc IS NULL").unwrap().rows.len(), 1);
+    }
+
+    #[test]
+    fn integers_beyond_i64_keep_every_digit() {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE j (doc JSON)").unwrap();
+        db.execute(r#"INSERT INTO j VALUES ('{"big": 18446744073709551615, "min": -9223372036854775808, "f": 1.5}')"#).unwrap();
+        let result = db.execute("SELECT doc->>'big', doc->>'big' - 1, doc->>'min', doc->>'f' FROM j").unwrap();
+        let values: Vec<String> = result.rows[0].values.iter().map(|v| format!("{}:{}", v.type_name(), v)).collect();
+        assert_eq!(
+            values,
+            ["Decimal:18446744073709551615", "Decimal:18446744073709551614", "Int:-9223372036854775808", "Float:1.5"]
+        );
+        let rows = db.execute("SELECT * FROM j WHERE doc->>'big' = 18446744073709551615").unwrap().rows;
+        assert_eq!(rows.len(), 1);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::blob::{parse_hex, to_hex};
+use crate::part1::DbError;
+
+// 词法单元类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),       // 普通标识符或关键字，关键字由语法分析器按大小写无关方式识别
+    QuotedIdent(String), // "name" 或 `name` 形式的标识符
+    String(String),      // 'text' 字符串字面量
+    Number(String),      // 数字字面量，保留原始文本
+    Blob(Vec<u8>),       // X'0A1B' 十六进制二进制字面量
+    Comma,
+    Dot,
+    Semicolon,
+    LParen,
+    RParen,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Concat, // ||
+    Arrow,     // ->
+    LongArrow, // ->>
+    Eq,
+    NotEq, // <> 或 !=
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Eof,
+}
+
+// 带位置信息的词法单元，行列号从 1 开始
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl TokenKind {
+    // 用于错误信息的描述
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Ident(s) => s.clone(),
+            TokenKind::QuotedIdent(s) => format!("\"{}\"", s),
+            TokenKind::String(s) => format!("'{}'", s),
+            TokenKind::Number(s) => s.clone(),
+            TokenKind::Blob(bytes) => format!("X'{}'", to_hex(bytes)),
+            TokenKind::Comma => ",".to_string(),
+            TokenKind::Dot => ".".to_string(),
+            TokenKind::Semicolon => ";".to_string(),
+            TokenKind::LParen => "(".to_string(),
+            TokenKind::RParen => ")".to_string(),
+            TokenKind::Star => "*".to_string(),
+            TokenKind::Plus => "+".to_string(),
+            TokenKind::Minus => "-".to_string(),
+            TokenKind::Slash => "/".to_string(),
+            TokenKind::Percent => "%".to_string(),
+            TokenKind::Concat => "||".to_string(),
+            TokenKind::Arrow => "->".to_string(),
+            TokenKind::LongArrow => "->>".to_string(),
+            TokenKind::Eq => "=".to_string(),
+            TokenKind::NotEq => "<>".to_string(),
+            TokenKind::Lt => "<".to_string(),
+            TokenKind::LtEq => "<=".to_string(),
+            TokenKind::Gt => ">".to_string(),
+            TokenKind::GtEq => ">=".to_string(),
+            TokenKind::Eof => "语句结尾".to_string(),
+        }
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+// 将 SQL 文本切分为词法单元，结尾总是附带一个 Eof
+pub fn tokenize(sql: &str) -> Result<Vec<Token>, DbError> {
+    let mut lexer = Lexer {
+        chars: sql.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    let mut tokens = Vec::new();
+    loop {
+        lexer.skip_whitespace_and_comments()?;
+        let (line, column) = (lexer.line, lexer.column);
+        let kind = match lexer.peek() {
+            None => {
+                tokens.push(Token { kind: TokenKind::Eof, line, column });
+                return Ok(tokens);
+            }
+            Some(c) => lexer.next_token(c)?,
+        };
+        tokens.push(Token { kind, line, column });
+    }
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, line: usize, column: usize, message: String) -> DbError {
+        DbError::Syntax { message, line, column }
+    }
+
+    // 跳过空白、-- 行注释和 /* */ 块注释
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), DbError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('-'), Some('-')) => {
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let (line, column) = (self.line, self.column);
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('*') if self.peek() == Some('/') => {
+                                self.bump();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => return Err(self.error(line, column, "块注释未闭合".to_string())),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next_token(&mut self, c: char) -> Result<TokenKind, DbError> {
+        let (line, column) = (self.line, self.column);
+        if (c == 'X' || c == 'x') && self.peek_at(1) == Some('\'') {
+            self.bump();
+            self.bump();
+            let text = self.quoted('\'', line, column, false)?;
+            return match parse_hex(&text) {
+                Some(bytes) => Ok(TokenKind::Blob(bytes)),
+                None => Err(self.error(line, column, format!("无效的十六进制字面量 X'{}'，应为偶数个十六进制数字", text))),
+            };
+        }
+        if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(c) = self.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    ident.push(c);
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+            return Ok(TokenKind::Ident(ident));
+        }
+        if c.is_ascii_digit() || (c == '.' && self.peek_at(1).is_some_and(|d| d.is_ascii_digit())) {
+            return self.number(line, column);
+        }
+
+        self.bump();
+        let kind = match c {
+            '\'' => TokenKind::String(self.quoted('\'', line, column, true)?),
+            '"' => TokenKind::QuotedIdent(self.quoted('"', line, column, false)?),
+            '`' => TokenKind::QuotedIdent(self.quoted('`', line, column, false)?),
+            ',' => TokenKind::Comma,
+            '.' => TokenKind::Dot,
+            ';' => TokenKind::Semicolon,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '*' => TokenKind::Star,
+            '+' => TokenKind::Plus,
+            '-' if self.peek() == Some('>') => {
+                self.bump();
+                if self.peek() == Some('>') {
+                    self.bump();
+                    TokenKind::LongArrow
+                } else {
+                    TokenKind::Arrow
+                }
+            }
+            '-' => TokenKind::Minus,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '=' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                }
+                TokenKind::Eq
+            }
+            '|' if self.peek() == Some('|') => {
+                self.bump();
+                TokenKind::Concat
+            }
+            '!' if self.peek() == Some('=') => {
+                self.bump();
+                TokenKind::NotEq
+            }
+            '<' => match self.peek() {
+                Some('=') => {
+                    self.bump();
+                    TokenKind::LtEq
+                }
+                Some('>') => {
+                    self.bump();
+                    TokenKind::NotEq
+                }
+                _ => TokenKind::Lt,
+            },
+            '>' => {
+                if self.peek() == Some('=') {
+                    self.bump();
+                    TokenKind::GtEq
+                } else {
+                    TokenKind::Gt
+                }
+            }
+            other => return Err(self.error(line, column, format!("无法识别的字符 '{}'", other))),
+        };
+        Ok(kind)
+    }
+
+    // 整数、小数与科学计数法
+    fn number(&mut self, line: usize, column: usize) -> Result<TokenKind, DbError> {
+        let mut text = String::new();
+        let mut seen_dot = false;
+        let mut seen_exp = false;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                text.push(c);
+            } else if c == '.' && !seen_dot && !seen_exp {
+                seen_dot = true;
+                text.push(c);
+            } else if (c == 'e' || c == 'E') && !seen_exp {
+                let sign = matches!(self.peek_at(1), Some('+') | Some('-'));
+                let digit_at = if sign { 2 } else { 1 };
+                if !self.peek_at(digit_at).is_some_and(|d| d.is_ascii_digit()) {
+                    break;
+                }
+                seen_exp = true;
+                text.push(c);
+                if sign {
+                    self.bump();
+                    text.push(self.peek().unwrap_or('+'));
+                }
+            } else {
+                break;
+            }
+            self.bump();
+        }
+        if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') {
+            return Err(self.error(line, column, format!("无效的数字 '{}{}'", text, self.peek().unwrap_or(' '))));
+        }
+        Ok(TokenKind::Number(text))
+    }
+
+    // 读取引号内的内容，重复引号表示引号本身；字符串字面量另外支持反斜杠转义
+    fn quoted(&mut self, quote: char, line: usize, column: usize, escapes: bool) -> Result<String, DbError> {
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                None => {
+                    let what = if escapes { "字符串" } else { "标识符" };
+                    return Err(self.error(line, column, format!("{}缺少结束引号 {}", what, quote)));
+                }
+                Some(c) if c == quote => {
+                    if self.peek() == Some(quote) {
+                        self.bump();
+                        text.push(quote);
+                    } else {
+                        return Ok(text);
+                    }
+                }
+                Some('\\') if escapes => {
+                    let (esc_line, esc_column) = (self.line, self.column - 1);
+                    match self.bump() {
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some('r') => text.push('\r'),
+                        Some('0') => text.push('\0'),
+                        Some(c @ ('\\' | '\'' | '"')) => text.push(c),
+                        Some(other) => {
+                            return Err(self.error(esc_line, esc_column, format!("未知的转义序列 '\\{}'", other)))
+                        }
+                        None => return Err(self.error(line, column, "字符串缺少结束引号 '".to_string())),
+                    }
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str) -> Vec<TokenKind> {
+        tokenize(sql).unwrap().into_iter().map(|token| token.kind).collect()
+    }
+
+    // 错误信息和出错的行列号
+    fn error(sql: &str) -> (String, usize, usize) {
+        match tokenize(sql) {
+            Err(DbError::Syntax { message, line, column }) => (message, line, column),
+            other => panic!("{:?} 应报语法错误，实际为 {:?}", sql, other),
+        }
+    }
+
+    #[test]
+    fn tokens_carry_line_and_column() {
+        let tokens = tokenize("SELECT a,\n  b -- 注释\n/* 块\n注释 */ FROM t").unwrap();
+        let positions: Vec<(String, usize, usize)> =
+            tokens.iter().map(|token| (token.kind.describe(), token.line, token.column)).collect();
+        let expected = [("SELECT", 1, 1), ("a", 1, 8), (",", 1, 9), ("b", 2, 3), ("FROM", 4, 7), ("t", 4, 12), ("语句结尾", 4, 13)];
+        let expected: Vec<(String, usize, usize)> = expected.iter().map(|&(s, l, c)| (s.to_string(), l, c)).collect();
+        assert_eq!(positions, expected);
+    }
+
+    #[test]
+    fn literals_and_operators() {
+        assert_eq!(
+            kinds(r#"'it''s' 'a\nb' "x""y" `z` 1.5e3 X'0aFF' -> ->> || <> != <= >="#),
+            [
+                TokenKind::String("it's".to_string()),
+                TokenKind::String("a\nb".to_string()),
+                TokenKind::QuotedIdent("x\"y".to_string()),
+                TokenKind::QuotedIdent("z".to_string()),
+                TokenKind::Number("1.5e3".to_string()),
+                TokenKind::Blob(vec![0x0a, 0xff]),
+                TokenKind::Arrow,
+                TokenKind::LongArrow,
+                TokenKind::Concat,
+                TokenKind::NotEq,
+                TokenKind::NotEq,
+                TokenKind::LtEq,
+                TokenKind::GtEq,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_text() {
+        assert_eq!(error("SELECT 'abc"), ("字符串缺少结束引号 '".to_string(), 1, 8));
+        assert_eq!(error("SELECT 1 # 2"), ("无法识别的字符 '#'".to_string(), 1, 10));
+        assert_eq!(error("SELECT /* x"), ("块注释未闭合".to_string(), 1, 8));
+        assert_eq!(error("SELECT 'a\\q'"), ("未知的转义序列 '\\q'".to_string(), 1, 10));
+        assert_eq!(error("SELECT 12abc"), ("无效的数字 '12a'".to_string(), 1, 8));
+        assert_eq!(error("SELECT 1,\n  'x").1, 2);
+        assert_eq!(error("SELECT X'ABC'").0, "无效的十六进制字面量 X'ABC'，应为偶数个十六进制数字");
+    }
+}
@@ -0,0 +1,23 @@
+pub mod part1;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod value;
+pub mod decimal;
+pub mod datetime;
+pub mod blob;
+pub mod eval;
+pub mod aggregate;
+pub mod join;
+pub mod storage;
+pub mod wal;
+pub mod pager;
+pub mod btree;
+pub mod page_store;
+mod executor;
+mod constraints;
+mod foreign_key;
+mod json;
+mod binary;
+mod sort;
+pub mod ai_assistant;
@@ -1,7 +1,87 @@
-mod part1;
-mod part2;
 mod gui;
-mod ai_assistant;
-fn main() -> Result<(), eframe::Error> {
-    gui::run_gui()
+use sql_homework::page_store::PagedDatabase;
+use sql_homework::pager::DEFAULT_POOL_PAGES;
+use sql_homework::storage;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "用法: sql_homework [convert <源文件> <目标文件> | sql <分页数据库.rspg> [--pool <页数>] <SQL>...]";
+
+// 不带参数时启动界面；convert <源文件> <目标文件> 按目标文件的扩展名在 JSON、二进制和分页格式之间转换；
+// sql 在分页数据库上依次执行 SQL，查询结果逐行输出，文件不存在时新建
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => match gui::run_gui() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("启动界面失败: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        [command, from, to] if command == "convert" => match storage::convert(Path::new(from), Path::new(to)) {
+            Ok(()) => {
+                println!("已将 {} 转换为 {}", from, to);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("转换失败: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+        [command, path, rest @ ..] if command == "sql" && !rest.is_empty() => run_paged_sql(Path::new(path), rest),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_paged_sql(path: &Path, args: &[String]) -> ExitCode {
+    let (pool_pages, statements) = match args {
+        [flag, pages, rest @ ..] if flag == "--pool" => match pages.parse::<usize>() {
+            Ok(pages) if pages > 0 => (pages, rest),
+            _ => {
+                eprintln!("缓冲池页数无效: {}", pages);
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => (DEFAULT_POOL_PAGES, args),
+    };
+    let opened = if path.exists() {
+        PagedDatabase::open(path, pool_pages)
+    } else {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        PagedDatabase::create(path, &name, pool_pages)
+    };
+    let mut db = match opened {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("打开 {} 失败: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    for sql in statements {
+        let mut header_printed = false;
+        let result = db.execute_streaming(sql, &mut |columns, row| {
+            if !header_printed {
+                let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+                println!("{}", names.join("\t"));
+                header_printed = true;
+            }
+            let values: Vec<String> = row.values.iter().map(|v| v.to_string()).collect();
+            println!("{}", values.join("\t"));
+            Ok(())
+        });
+        match result {
+            Ok(result) => println!("{}", result.message),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let stats = db.pool_stats();
+    eprintln!("缓冲池: 命中 {} 次，读入 {} 页，淘汰 {} 页", stats.hits, stats.misses, stats.evictions);
+    ExitCode::SUCCESS
 }
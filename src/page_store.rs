@@ -0,0 +1,1355 @@
+// 分页存储：表的行保存在分页文件（.rspg）的堆页中，经缓冲池按需读写，不必把整个数据库读入内存。
+// 表结构等目录信息以 JSON 保存在从文件头指向的溢出页链中。
+// 堆页的布局：类型（u8）、同一表的下一页（u32）、槽数（u16）、数据区起点（u16），随后是槽目录，
+// 每槽为行的偏移和长度（各 u16）；行从页尾向前存放，偏移为 0 的槽已被删除，可以复用。
+// 行的第一个字节区分存放方式：编码后不超过 INLINE_LIMIT 的行直接放在页内，否则放在溢出页链中，
+// 页内只记录首页和长度。溢出页的布局：类型（u8）、下一页（u32）、本页数据的长度（u16），随后是数据。
+// 主键和各 UNIQUE 列各有一个 B+ 树索引（见 btree），把键值的散列映射到行的位置，
+// 插入和更新时经索引检查重复，不扫描整张表；键值含 NULL 的行不在索引中。
+// 查询逐行扫描表，由执行器的 select_rows 过滤、分组和投影，排序见 sort；连接、集合运算和子查询不支持。
+// 外键列上没有索引，外键的检查和级联仍要为每一行扫描另一张表，因此暂不支持外键：建表时不能声明外键，
+// 从其他格式转换来的带外键的表只能查询。ALTER TABLE 需要改写表中的每一行，也未实现。
+// 需要这些功能时可先用 convert 转换为 JSON 或二进制格式，修改后再转换回来
+use crate::ast::{Delete, Expr, Insert, Query, Select, SelectItem, Statement, TableRef, Update};
+use crate::binary::{decode_row, encode_row};
+use crate::btree;
+use crate::datetime::{with_statement_time, Timestamp};
+use crate::eval::{eval, is_true, resolve_columns, row_values, table_scope, Scope};
+use crate::executor::{output_value, with_truncation_warning};
+use crate::join::relation_columns;
+use crate::pager::{
+    invalid, read_u16, read_u32, write_u16, write_u32, PageId, Pager, PoolStats, PAGE_HEAP, PAGE_OVERFLOW, PAGE_SIZE,
+};
+use crate::parser::parse;
+use crate::sort::Sorter;
+use crate::part1::{
+    Constraint, Database, DbError, LengthPolicy, QueryResult, ResultColumn, Row, Table, FORMAT_VERSION,
+};
+use crate::value::{values_key, Value};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+// 分页数据库文件的扩展名
+pub const PAGED_EXTENSION: &str = "rspg";
+
+const HEAP_HEADER: usize = 9;
+const SLOT_SIZE: usize = 4;
+const OVERFLOW_HEADER: usize = 7;
+// 编码后超过此长度的行放入溢出页
+const INLINE_LIMIT: usize = PAGE_SIZE / 4;
+// 可用空间少于此值的页不再作为插入的候选
+const MIN_FREE_SPACE: usize = PAGE_SIZE / 8;
+// 插入时最多尝试的候选页数
+const MAX_CANDIDATES: usize = 8;
+// UPDATE 每批写入的行数
+const BATCH_ROWS: usize = 1000;
+// 排序时内存中最多保留的结果行数，超过时分段写入临时文件
+const SORT_RUN_ROWS: usize = 10_000;
+// 索引中键值的散列方式，改变时打开文件会重建索引；早期没有索引的文件视为 0
+const INDEX_VERSION: u32 = 1;
+
+const CELL_INLINE: u8 = 0;
+const CELL_OVERFLOW: u8 = 1;
+
+// 行在文件中的位置，更新时行放不下会移到别的页，位置随之改变
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RowId {
+    pub page: PageId,
+    pub slot: u16,
+}
+
+// 接收查询结果的回调，参数为结果的列和一行
+pub type RowSink<'a> = dyn FnMut(&[ResultColumn], Row) -> Result<(), DbError> + 'a;
+
+// 从一页中复制出的行及其位置
+type Cells = Vec<(RowId, Vec<u8>)>;
+
+// 目录中的一张表
+#[derive(Serialize, Deserialize)]
+struct TableEntry {
+    table: Table, // 只有表结构，rows 为空
+    first_page: PageId,
+    last_page: PageId,
+    row_count: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    free_pages: Vec<PageId>, // 删除或移走过行、可能有可用空间的页，插入时优先使用
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    indexes: Vec<PageId>, // 各索引的根页，与 key_constraints 一一对应
+}
+
+#[derive(Serialize, Deserialize)]
+struct Catalog {
+    name: String,
+    // 行中的值的保存方式，与 Database::format_version 相同；早期的分页文件没有此字段，视为 0
+    #[serde(default)]
+    format_version: u32,
+    #[serde(default)]
+    length_policy: LengthPolicy,
+    #[serde(default)]
+    index_version: u32,
+    tables: Vec<TableEntry>,
+}
+
+pub struct PagedDatabase {
+    pager: Pager,
+    catalog: Catalog,
+    catalog_dirty: bool,
+}
+
+impl PagedDatabase {
+    // 创建空的分页数据库，pool_pages 为缓冲池的页数
+    pub fn create(path: &Path, name: &str, pool_pages: usize) -> io::Result<PagedDatabase> {
+        let mut db = PagedDatabase {
+            pager: Pager::create(path, pool_pages)?,
+            catalog: Catalog {
+                name: name.to_string(),
+                format_version: FORMAT_VERSION,
+                length_policy: LengthPolicy::default(),
+                index_version: INDEX_VERSION,
+                tables: Vec::new(),
+            },
+            catalog_dirty: true,
+        };
+        db.flush()?;
+        Ok(db)
+    }
+
+    pub fn open(path: &Path, pool_pages: usize) -> io::Result<PagedDatabase> {
+        let mut pager = Pager::open(path, pool_pages)?;
+        let root = pager.root();
+        if root == 0 {
+            return Err(invalid("缺少目录".to_string()));
+        }
+        let json = read_chain(&mut pager, root)?;
+        let catalog = serde_json::from_slice(&json).map_err(|e| invalid(format!("目录无效: {}", e)))?;
+        let mut db = PagedDatabase { pager, catalog, catalog_dirty: false };
+        if db.catalog.format_version < FORMAT_VERSION {
+            db.migrate()?;
+        }
+        if db.catalog.index_version != INDEX_VERSION {
+            db.rebuild_indexes()?;
+        }
+        Ok(db)
+    }
+
+    // 把内存中的数据库写成分页文件，覆盖同名文件；旧格式的行逐行升级后写入
+    pub fn import(db: &Database, path: &Path, pool_pages: usize) -> io::Result<PagedDatabase> {
+        let mut paged = PagedDatabase::create(path, &db.name, pool_pages)?;
+        paged.catalog.length_policy = db.length_policy;
+        let migrate = db.format_version < FORMAT_VERSION;
+        for table in &db.tables {
+            let t = paged.add_table(Table { rows: Vec::new(), ..table.clone() })?;
+            for row in &table.rows {
+                let mut migrated;
+                let row = if migrate {
+                    migrated = row.clone();
+                    table.migrate_row(&mut migrated);
+                    &migrated
+                } else {
+                    row
+                };
+                paged.insert_values(t, &row.values)?;
+            }
+        }
+        paged.flush()?;
+        Ok(paged)
+    }
+
+    // 读出全部数据，得到内存中的数据库
+    pub fn export(&mut self) -> io::Result<Database> {
+        let mut db = Database::new(&self.catalog.name);
+        db.format_version = self.catalog.format_version;
+        db.length_policy = self.catalog.length_policy;
+        for t in 0..self.catalog.tables.len() {
+            let rows = self.scan_index(t).map(|item| item.map(|(_, row)| row)).collect::<io::Result<_>>()?;
+            db.tables.push(Table { rows, ..self.catalog.tables[t].table.clone() });
+        }
+        Ok(db)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.catalog.name
+    }
+
+    // 各表的结构和行数
+    pub fn tables(&self) -> impl Iterator<Item = (&Table, u64)> {
+        self.catalog.tables.iter().map(|entry| (&entry.table, entry.row_count))
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pager.stats()
+    }
+
+    // 逐行读取表，每次只把一页的行读入内存
+    pub fn scan(&mut self, table: &str) -> Result<Scan<'_>, DbError> {
+        let t = self.table_index(table)?;
+        Ok(self.scan_index(t))
+    }
+
+    // 执行一条 SQL 语句，查询结果全部放在返回值中
+    pub fn execute(&mut self, sql: &str) -> Result<QueryResult, DbError> {
+        let mut rows = Vec::new();
+        let mut result = self.execute_streaming(sql, &mut |_, row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        result.rows = rows;
+        Ok(result)
+    }
+
+    // 执行一条 SQL 语句，查询结果逐行交给 on_row，不在内存中保留。
+    // 只支持单表上没有子查询的 SELECT，可以分组、聚合、DISTINCT 和排序，逐行流式执行；
+    // INSERT、UPDATE 经索引检查主键和 UNIQUE 约束，暂不支持带外键约束的表；不支持 ALTER TABLE。
+    // 语句成功后提交，失败时回滚到语句执行之前
+    pub fn execute_streaming(
+        &mut self,
+        sql: &str,
+        on_row: &mut RowSink,
+    ) -> Result<QueryResult, DbError> {
+        let statement = parse(sql)?;
+        let result = with_statement_time(Timestamp::now(), || match statement {
+            Statement::Query(Query::Select(select)) if streamable(&select) => self.select_streaming(*select, on_row),
+            // 连接、集合运算和子查询要同时看到多张表的行，读入内存可能超出内存的大小，不悄悄退回到整体读入
+            Statement::Query(_) => Err(DbError::Unsupported(
+                "分页存储只支持单表上没有子查询的查询，连接、集合运算和子查询可先用 convert 转换为 JSON 或二进制格式"
+                    .to_string(),
+            )),
+            Statement::Insert(insert) => self.insert(insert),
+            Statement::Update(update) => self.update(update),
+            Statement::Delete(delete) => self.delete(delete),
+            Statement::CreateTable(_) | Statement::DropTable(_) => self.execute_ddl(statement),
+            Statement::AlterTable(_) => Err(DbError::Unsupported(
+                "分页存储不支持 ALTER TABLE，可先转换为 JSON 或二进制格式修改后再转换回来".to_string(),
+            )),
+        });
+        match result {
+            Ok(_) => self.flush(),
+            Err(_) => self.rollback(),
+        }
+        .map_err(storage_error)?;
+        result
+    }
+
+    // 放弃上次提交之后的修改，重新读取目录
+    fn rollback(&mut self) -> io::Result<()> {
+        self.pager.rollback()?;
+        let root = self.pager.root();
+        let json = read_chain(&mut self.pager, root)?;
+        self.catalog = serde_json::from_slice(&json).map_err(|e| invalid(format!("目录无效: {}", e)))?;
+        self.catalog_dirty = false;
+        Ok(())
+    }
+
+    // 把目录和脏页写回文件并提交
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.catalog_dirty {
+            let json = serde_json::to_vec(&self.catalog).map_err(io::Error::other)?;
+            let old = self.pager.root();
+            let root = write_chain(&mut self.pager, &json)?;
+            self.pager.set_root(root);
+            if old != 0 {
+                free_chain(&mut self.pager, old)?;
+            }
+            self.catalog_dirty = false;
+        }
+        self.pager.flush()
+    }
+
+    // 早期的分页文件可能由未升级的旧数据导入，打开时逐页把各行升级为当前格式。
+    // 行变长时可能移到表末尾的页，之后会再次读到，升级已是当前格式的行不会改变它
+    fn migrate(&mut self) -> io::Result<()> {
+        for t in 0..self.catalog.tables.len() {
+            let table = self.catalog.tables[t].table.clone();
+            let mut page = self.catalog.tables[t].first_page;
+            while page != 0 {
+                let (_, cells) = self.page_cells(page)?;
+                for (id, cell) in cells {
+                    let row = self.read_cell(&cell)?;
+                    let mut migrated = row.clone();
+                    table.migrate_row(&mut migrated);
+                    if migrated.values != row.values {
+                        self.update_values(t, id, &migrated.values)?;
+                    }
+                }
+                // 本页的行移走时表末尾可能追加了新页，下一页的页号在处理完本页之后读取
+                page = read_u32(self.pager.page(page)?, 1);
+            }
+        }
+        self.catalog.format_version = FORMAT_VERSION;
+        self.catalog_dirty = true;
+        self.flush()
+    }
+
+    // 释放已有的索引，按表中的行重新建立
+    fn rebuild_indexes(&mut self) -> io::Result<()> {
+        for t in 0..self.catalog.tables.len() {
+            for root in std::mem::take(&mut self.catalog.tables[t].indexes) {
+                btree::free(&mut self.pager, root)?;
+            }
+            let count = key_constraints(&self.catalog.tables[t].table).len();
+            self.catalog.tables[t].indexes = (0..count).map(|_| btree::create(&mut self.pager)).collect::<io::Result<_>>()?;
+            let mut page = self.catalog.tables[t].first_page;
+            while page != 0 {
+                let (next, cells) = self.page_cells(page)?;
+                for (id, cell) in cells {
+                    let row = self.read_cell(&cell)?;
+                    for (root, entry) in self.index_entries(t, id, &row.values) {
+                        if let Some(entry) = entry {
+                            btree::insert(&mut self.pager, root, entry)?;
+                        }
+                    }
+                }
+                page = next;
+            }
+        }
+        self.catalog.index_version = INDEX_VERSION;
+        self.catalog_dirty = true;
+        self.flush()
+    }
+
+    // 逐行读取表交给执行器的 select_rows 过滤、分组和投影，内存中只保留各组的中间结果、
+    // DISTINCT 见过的值和排序中的一段行。没有 ORDER BY 时结果行逐行输出，够 OFFSET 和 LIMIT 后停止扫描
+    fn select_streaming(&mut self, select: Select, on_row: &mut RowSink) -> Result<QueryResult, DbError> {
+        let TableRef::Table { name, .. } = &select.from else {
+            unreachable!("streamable 只接受单表查询");
+        };
+        let t = self.table_index(name)?;
+        let table = self.catalog.tables[t].table.clone();
+        let schema = self.schema();
+        let query = Query::Select(Box::new(select));
+        let columns = schema.resolve_query(&query, None)?;
+        let Query::Select(select) = &query else {
+            unreachable!("查询是单个 SELECT");
+        };
+        let scope_columns = relation_columns(&schema, &select.from, None)?;
+
+        let mut count = 0;
+        let mut emit = |values: Vec<Value>| {
+            let values = values
+                .into_iter()
+                .zip(&columns)
+                .map(|(value, col)| output_value(value, col.data_type.as_ref()))
+                .collect();
+            on_row(&columns, Row { values })?;
+            count += 1;
+            Ok(true)
+        };
+        let mut sorter = Sorter::new(&select.order_by, select.offset, select.limit, SORT_RUN_ROWS);
+        let done = Cell::new(false);
+        let rows = self
+            .scan_index(t)
+            .take_while(|_| !done.get())
+            .map(|item| item.map(|(_, row)| row_values(&table, &row)).map_err(storage_error));
+        schema.select_rows(select, scope_columns, rows, None, &mut |values, keys| {
+            done.set(!sorter.push(values, keys, &mut emit)?);
+            Ok(())
+        })?;
+        sorter.finish(&mut emit)?;
+        Ok(QueryResult {
+            columns,
+            message: format!("共 {} 行", count),
+            ..Default::default()
+        })
+    }
+
+    // 新行在只有表结构的临时数据库中求出并转换为列的类型，再经索引检查与已有的行是否重复
+    fn insert(&mut self, insert: Insert) -> Result<QueryResult, DbError> {
+        let t = self.modifiable_table(&insert.table)?;
+        if insert.rows.iter().flatten().any(has_subquery) {
+            return Err(DbError::Unsupported("分页存储中 INSERT 的值不能使用子查询".to_string()));
+        }
+        let mut schema = self.schema();
+        let result = schema.execute_statement(Statement::Insert(insert.clone()))?;
+        let rows = std::mem::take(&mut schema.table_mut(&insert.table)?.rows);
+        self.check_keys(t, &rows)?;
+        for row in &rows {
+            self.insert_values(t, &row.values).map_err(storage_error)?;
+        }
+        Ok(result)
+    }
+
+    // 逐页扫描，新行每满一批就检查该批本身的约束并写入，索引随之更新，内存中只保留各新键值的散列，
+    // 全部写入后再经索引查找重复的键值。出错时 execute_streaming 回滚整条语句
+    fn update(&mut self, update: Update) -> Result<QueryResult, DbError> {
+        let t = self.modifiable_table(&update.table)?;
+        let table = self.catalog.tables[t].table.clone();
+        let policy = self.catalog.length_policy;
+        let assignments: Vec<(usize, &Expr)> = update
+            .assignments
+            .iter()
+            .map(|(name, expr)| Ok((table.column_index(name)?, expr)))
+            .collect::<Result<_, DbError>>()?;
+
+        let scope_columns = table_scope(&table, &table.name);
+        for expr in update.selection.iter().chain(assignments.iter().map(|(_, expr)| *expr)) {
+            resolve_columns(expr, &scope_columns, None)?;
+        }
+        let keys = key_constraints(&table);
+        let mut written = Updated {
+            keys: vec![Vec::new(); keys.len()],
+            moved: HashSet::new(),
+            count: 0,
+        };
+        let mut batch = Vec::new();
+        let mut truncated = 0;
+        let mut page = self.catalog.tables[t].first_page;
+        while page != 0 {
+            let (_, cells) = self.page_cells(page).map_err(storage_error)?;
+            for (id, cell) in cells {
+                // 本条语句已经更新过、移到后面的页中的行
+                if written.moved.contains(&id) {
+                    continue;
+                }
+                let row = self.read_cell(&cell).map_err(storage_error)?;
+                let values = row_values(&table, &row);
+                let scope = Scope {
+                    columns: &scope_columns,
+                    values: &values,
+                    ..Scope::empty()
+                };
+                if matches(update.selection.as_ref(), &scope)? {
+                    let mut new_row = row.clone();
+                    new_row.values.resize(table.columns.len(), Value::Null);
+                    for (idx, expr) in &assignments {
+                        new_row.values[*idx] = eval(expr, &scope)?;
+                    }
+                    truncated += table.conform_row(&mut new_row, policy)?;
+                    batch.push((id, new_row));
+                }
+            }
+            if batch.len() >= BATCH_ROWS {
+                self.update_batch(t, &keys, std::mem::take(&mut batch), &mut written)?;
+            }
+            // 行移走时表末尾可能追加了新页，下一页的页号在写入之后读取
+            page = read_u32(self.pager.page(page).map_err(storage_error)?, 1);
+        }
+        self.update_batch(t, &keys, batch, &mut written)?;
+        self.check_updated_keys(t, &keys, written.keys)?;
+
+        Ok(QueryResult {
+            affected_rows: written.count,
+            message: with_truncation_warning(format!("已更新 {} 条记录", written.count), truncated),
+            ..Default::default()
+        })
+    }
+
+    // 表结构中没有行，check_constraints 只检查一批新行本身及其间的重复；与其他行的重复由 check_updated_keys 发现
+    fn update_batch(
+        &mut self,
+        t: usize,
+        keys: &[(Vec<usize>, Constraint)],
+        batch: Vec<(RowId, Row)>,
+        written: &mut Updated,
+    ) -> Result<(), DbError> {
+        let table = self.catalog.tables[t].table.clone();
+        table.check_constraints(&batch.iter().map(|(_, row)| row).collect::<Vec<_>>(), &[])?;
+        for (id, row) in batch {
+            for (k, (columns, _)) in keys.iter().enumerate() {
+                let values = table.key_values(&row, columns);
+                if !values.iter().any(Value::is_null) {
+                    written.keys[k].push(key_hash(&values));
+                }
+            }
+            if let Some(moved) = self.update_values(t, id, &row.values).map_err(storage_error)? {
+                written.moved.insert(moved);
+            }
+            written.count += 1;
+        }
+        Ok(())
+    }
+
+    // 更新后每个新键值在表中只能出现一次，否则与未更新的行或其他新行重复。
+    // 散列只在索引中出现一次时不必读取行；同时违反多个约束时报告主键，其次是靠前的 UNIQUE 列
+    fn check_updated_keys(
+        &mut self,
+        t: usize,
+        keys: &[(Vec<usize>, Constraint)],
+        new_keys: Vec<Vec<u64>>,
+    ) -> Result<(), DbError> {
+        let table = self.catalog.tables[t].table.clone();
+        for (k, mut hashes) in new_keys.into_iter().enumerate() {
+            hashes.sort_unstable();
+            hashes.dedup();
+            let root = self.catalog.tables[t].indexes[k];
+            for hash in hashes {
+                let ids = btree::find(&mut self.pager, root, hash).map_err(storage_error)?;
+                if ids.len() < 2 {
+                    continue;
+                }
+                let mut seen = HashSet::new();
+                for id in ids {
+                    let row = self.read_row(RowId::unpack(id)).map_err(storage_error)?;
+                    let values = table.key_values(&row, &keys[k].0);
+                    if !seen.insert(values_key(&values)) {
+                        return Err(key_violation(&table, keys, k, &values));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, delete: Delete) -> Result<QueryResult, DbError> {
+        let t = self.modifiable_table(&delete.table)?;
+        let table = self.catalog.tables[t].table.clone();
+        let scope_columns = table_scope(&table, &table.name);
+        if let Some(selection) = &delete.selection {
+            resolve_columns(selection, &scope_columns, None)?;
+        }
+        let mut deleted = Vec::new();
+        for item in self.scan_index(t) {
+            let (id, row) = item.map_err(storage_error)?;
+            let values = row_values(&table, &row);
+            let scope = Scope {
+                columns: &scope_columns,
+                values: &values,
+                ..Scope::empty()
+            };
+            if matches(delete.selection.as_ref(), &scope)? {
+                deleted.push(id);
+            }
+        }
+
+        let count = deleted.len();
+        for id in deleted {
+            self.delete_row(t, id).map_err(storage_error)?;
+        }
+        Ok(QueryResult {
+            affected_rows: count,
+            message: format!("已删除 {} 条记录", count),
+            ..Default::default()
+        })
+    }
+
+    // CREATE TABLE 和 DROP TABLE 在只有表结构的临时数据库上执行，再按结果增删表
+    fn execute_ddl(&mut self, statement: Statement) -> Result<QueryResult, DbError> {
+        let mut schema = self.schema();
+        let result = schema.execute_statement(statement)?;
+        let exists = |name: &str| schema.tables.iter().any(|table| table.name == name);
+        let dropped: Vec<String> = self
+            .catalog
+            .tables
+            .iter()
+            .map(|entry| entry.table.name.clone())
+            .filter(|name| !exists(name))
+            .collect();
+        for name in dropped {
+            self.drop_table(&name).map_err(storage_error)?;
+        }
+        let created: Vec<Table> = schema
+            .tables
+            .into_iter()
+            .filter(|table| self.catalog.tables.iter().all(|entry| entry.table.name != table.name))
+            .collect();
+        if let Some(table) = created.iter().find(|table| !table.foreign_keys.is_empty()) {
+            return Err(DbError::Unsupported(format!(
+                "分页存储暂不支持外键约束，不能创建表 '{}'，可先转换为 JSON 或二进制格式",
+                table.name
+            )));
+        }
+        for table in created {
+            self.add_table(table).map_err(storage_error)?;
+        }
+        Ok(result)
+    }
+
+    // 只含表结构的内存数据库
+    fn schema(&self) -> Database {
+        let mut db = Database::new(&self.catalog.name);
+        db.length_policy = self.catalog.length_policy;
+        db.tables = self.catalog.tables.iter().map(|entry| entry.table.clone()).collect();
+        db
+    }
+
+    // 按名称查找表，完全匹配优先，其次大小写不敏感
+    fn table_index(&self, name: &str) -> Result<usize, DbError> {
+        let tables = &self.catalog.tables;
+        tables
+            .iter()
+            .position(|entry| entry.table.name == name)
+            .or_else(|| tables.iter().position(|entry| entry.table.name.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))
+    }
+
+    // 外键检查需要在表之间查找行，分页存储中暂不支持修改外键涉及的表
+    fn modifiable_table(&self, name: &str) -> Result<usize, DbError> {
+        let t = self.table_index(name)?;
+        let table = &self.catalog.tables[t].table;
+        let referenced = self.catalog.tables.iter().any(|entry| {
+            entry.table.foreign_keys.iter().any(|fk| fk.ref_table.eq_ignore_ascii_case(&table.name))
+        });
+        if referenced || !table.foreign_keys.is_empty() {
+            return Err(DbError::Unsupported(format!("分页存储暂不支持修改带外键约束的表 '{}'", table.name)));
+        }
+        Ok(t)
+    }
+
+    // 新行的主键和 UNIQUE 列不能与表中已有的行重复，经索引查找。
+    // 与内存中的检查顺序一致，同时违反多个约束时报告主键，其次是靠前的 UNIQUE 列
+    fn check_keys(&mut self, t: usize, new_rows: &[Row]) -> Result<(), DbError> {
+        let table = self.catalog.tables[t].table.clone();
+        let keys = key_constraints(&table);
+        for (k, (columns, _)) in keys.iter().enumerate() {
+            for row in new_rows {
+                let values = table.key_values(row, columns);
+                if !values.iter().any(Value::is_null) && self.key_exists(t, k, &values).map_err(storage_error)? {
+                    return Err(key_violation(&table, &keys, k, &values));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 表中是否有行在第 k 个键上的值与 values 相同；散列相同的行逐一读出比较
+    fn key_exists(&mut self, t: usize, k: usize, values: &[Value]) -> io::Result<bool> {
+        let root = self.catalog.tables[t].indexes[k];
+        let key = values_key(values);
+        for id in btree::find(&mut self.pager, root, key_hash(values))? {
+            let row = self.read_row(RowId::unpack(id))?;
+            let table = &self.catalog.tables[t].table;
+            let (columns, _) = &key_constraints(table)[k];
+            if values_key(&table.key_values(&row, columns)) == key {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // 行在各索引中的项，与 key_constraints 一一对应；键值含 NULL 时为 None
+    fn index_entries(&self, t: usize, id: RowId, values: &[Value]) -> Vec<(PageId, Option<btree::Entry>)> {
+        let entry = &self.catalog.tables[t];
+        let row = Row { values: values.to_vec() };
+        key_constraints(&entry.table)
+            .iter()
+            .zip(&entry.indexes)
+            .map(|((columns, _), &root)| {
+                let key = entry.table.key_values(&row, columns);
+                (root, (!key.iter().any(Value::is_null)).then(|| (key_hash(&key), id.pack())))
+            })
+            .collect()
+    }
+
+    fn add_table(&mut self, table: Table) -> io::Result<usize> {
+        let page = self.pager.allocate(PAGE_HEAP)?;
+        init_heap(self.pager.page_mut(page)?);
+        let indexes = key_constraints(&table)
+            .iter()
+            .map(|_| btree::create(&mut self.pager))
+            .collect::<io::Result<_>>()?;
+        self.catalog.tables.push(TableEntry {
+            table,
+            first_page: page,
+            last_page: page,
+            row_count: 0,
+            free_pages: Vec::new(),
+            indexes,
+        });
+        self.catalog_dirty = true;
+        Ok(self.catalog.tables.len() - 1)
+    }
+
+    // 删除表并释放它的全部页，包括行的溢出页和索引
+    fn drop_table(&mut self, name: &str) -> io::Result<()> {
+        let Some(t) = self.catalog.tables.iter().position(|entry| entry.table.name == name) else {
+            return Ok(());
+        };
+        let entry = self.catalog.tables.remove(t);
+        self.catalog_dirty = true;
+        for root in entry.indexes {
+            btree::free(&mut self.pager, root)?;
+        }
+        let mut page = entry.first_page;
+        while page != 0 {
+            let (next, cells) = self.page_cells(page)?;
+            for (_, cell) in cells {
+                self.release_cell(&cell)?;
+            }
+            self.pager.free(page)?;
+            page = next;
+        }
+        Ok(())
+    }
+
+    fn scan_index(&mut self, t: usize) -> Scan<'_> {
+        let next_page = self.catalog.tables[t].first_page;
+        Scan {
+            db: self,
+            next_page,
+            cells: Vec::new().into_iter(),
+        }
+    }
+
+    fn insert_values(&mut self, t: usize, values: &[Value]) -> io::Result<RowId> {
+        let cell = self.make_cell(values)?;
+        let id = self.insert_cell(t, &cell)?;
+        for (root, entry) in self.index_entries(t, id, values) {
+            if let Some(entry) = entry {
+                btree::insert(&mut self.pager, root, entry)?;
+            }
+        }
+        self.catalog.tables[t].row_count += 1;
+        self.catalog_dirty = true;
+        Ok(id)
+    }
+
+    // 新行优先放在原位置；放不下时移到别的页，返回新的位置。键值或位置改变时更新索引
+    fn update_values(&mut self, t: usize, id: RowId, values: &[Value]) -> io::Result<Option<RowId>> {
+        let cell = self.make_cell(values)?;
+        let page = self.pager.page_mut(id.page)?;
+        let old = take_cell(page, id.slot as usize)?;
+        let moved = if place_cell(page, &cell, Some(id.slot as usize)).is_none() {
+            trim_slots(page);
+            let moved = self.insert_cell(t, &cell)?;
+            self.note_free_space(t, id.page);
+            Some(moved)
+        } else {
+            None
+        };
+        if !self.catalog.tables[t].indexes.is_empty() {
+            let old_row = self.read_cell(&old)?;
+            let old_entries = self.index_entries(t, id, &old_row.values);
+            let new_entries = self.index_entries(t, moved.unwrap_or(id), values);
+            for ((root, old), (_, new)) in old_entries.into_iter().zip(new_entries) {
+                if old != new {
+                    if let Some(old) = old {
+                        btree::remove(&mut self.pager, root, old)?;
+                    }
+                    if let Some(new) = new {
+                        btree::insert(&mut self.pager, root, new)?;
+                    }
+                }
+            }
+        }
+        self.release_cell(&old)?;
+        Ok(moved)
+    }
+
+    fn delete_row(&mut self, t: usize, id: RowId) -> io::Result<()> {
+        let page = self.pager.page_mut(id.page)?;
+        let old = take_cell(page, id.slot as usize)?;
+        trim_slots(page);
+        if !self.catalog.tables[t].indexes.is_empty() {
+            let old_row = self.read_cell(&old)?;
+            for (root, entry) in self.index_entries(t, id, &old_row.values) {
+                if let Some(entry) = entry {
+                    btree::remove(&mut self.pager, root, entry)?;
+                }
+            }
+        }
+        self.release_cell(&old)?;
+        let entry = &mut self.catalog.tables[t];
+        entry.row_count = entry.row_count.saturating_sub(1);
+        self.note_free_space(t, id.page);
+        Ok(())
+    }
+
+    // 依次尝试表的最后一页和有可用空间的页，都放不下时在表末尾追加新页
+    fn insert_cell(&mut self, t: usize, cell: &[u8]) -> io::Result<RowId> {
+        let entry = &self.catalog.tables[t];
+        let last_page = entry.last_page;
+        let candidates: Vec<PageId> =
+            std::iter::once(last_page).chain(entry.free_pages.iter().copied()).take(MAX_CANDIDATES).collect();
+        for page_id in candidates {
+            let free = free_bytes(self.pager.page(page_id)?);
+            if free >= cell.len() + SLOT_SIZE {
+                if let Some(slot) = place_cell(self.pager.page_mut(page_id)?, cell, None) {
+                    return Ok(RowId { page: page_id, slot: slot as u16 });
+                }
+            }
+            if page_id != last_page && free < MIN_FREE_SPACE {
+                self.catalog.tables[t].free_pages.retain(|&p| p != page_id);
+                self.catalog_dirty = true;
+            }
+        }
+
+        let page_id = self.pager.allocate(PAGE_HEAP)?;
+        let page = self.pager.page_mut(page_id)?;
+        init_heap(page);
+        let slot = place_cell(page, cell, None).ok_or_else(|| invalid("行超过页的大小".to_string()))?;
+        write_u32(self.pager.page_mut(last_page)?, 1, page_id);
+        self.catalog.tables[t].last_page = page_id;
+        self.catalog_dirty = true;
+        Ok(RowId { page: page_id, slot: slot as u16 })
+    }
+
+    fn note_free_space(&mut self, t: usize, page: PageId) {
+        let entry = &mut self.catalog.tables[t];
+        if page != entry.last_page && !entry.free_pages.contains(&page) {
+            entry.free_pages.push(page);
+            self.catalog_dirty = true;
+        }
+    }
+
+    fn make_cell(&mut self, values: &[Value]) -> io::Result<Vec<u8>> {
+        let data = encode_row(values);
+        let mut cell = Vec::with_capacity(data.len().min(INLINE_LIMIT) + 1);
+        if data.len() < INLINE_LIMIT {
+            cell.push(CELL_INLINE);
+            cell.extend_from_slice(&data);
+        } else {
+            let len = u32::try_from(data.len()).map_err(|_| invalid("行过长".to_string()))?;
+            cell.push(CELL_OVERFLOW);
+            cell.extend_from_slice(&write_chain(&mut self.pager, &data)?.to_le_bytes());
+            cell.extend_from_slice(&len.to_le_bytes());
+        }
+        Ok(cell)
+    }
+
+    fn read_cell(&mut self, cell: &[u8]) -> io::Result<Row> {
+        let values = match cell.first() {
+            Some(&CELL_INLINE) => decode_row(&cell[1..])?,
+            Some(&CELL_OVERFLOW) if cell.len() == 9 => {
+                let data = read_chain(&mut self.pager, read_u32(cell, 1))?;
+                if data.len() != read_u32(cell, 5) as usize {
+                    return Err(invalid("溢出页中的行长度不符".to_string()));
+                }
+                decode_row(&data)?
+            }
+            _ => return Err(invalid("无法识别的行".to_string())),
+        };
+        Ok(Row { values })
+    }
+
+    // 释放行占用的溢出页
+    fn release_cell(&mut self, cell: &[u8]) -> io::Result<()> {
+        if cell.first() == Some(&CELL_OVERFLOW) && cell.len() == 9 {
+            free_chain(&mut self.pager, read_u32(cell, 1))?;
+        }
+        Ok(())
+    }
+
+    // 按位置读取一行
+    fn read_row(&mut self, id: RowId) -> io::Result<Row> {
+        let page = self.pager.page(id.page)?;
+        let slot = id.slot as usize;
+        let (offset, len) = if page[0] == PAGE_HEAP && slot < slot_count(page) { slot_at(page, slot) } else { (0, 0) };
+        let cell = page
+            .get(offset..offset + len)
+            .filter(|_| offset != 0)
+            .ok_or_else(|| invalid(format!("第 {} 页的行 {} 不存在", id.page, slot)))?
+            .to_vec();
+        self.read_cell(&cell)
+    }
+
+    // 复制出一页中的全部行，返回下一页的页号
+    fn page_cells(&mut self, page_id: PageId) -> io::Result<(PageId, Cells)> {
+        let page = self.pager.page(page_id)?;
+        if page[0] != PAGE_HEAP {
+            return Err(invalid(format!("第 {} 页不是数据页", page_id)));
+        }
+        let mut cells = Vec::new();
+        for slot in 0..slot_count(page) {
+            let (offset, len) = slot_at(page, slot);
+            if offset != 0 {
+                let cell = page.get(offset..offset + len).ok_or_else(|| invalid(format!("第 {} 页已损坏", page_id)))?;
+                cells.push((RowId { page: page_id, slot: slot as u16 }, cell.to_vec()));
+            }
+        }
+        Ok((read_u32(page, 1), cells))
+    }
+}
+
+impl Drop for PagedDatabase {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+// 按页顺序逐行读取一张表
+pub struct Scan<'a> {
+    db: &'a mut PagedDatabase,
+    next_page: PageId,
+    cells: <Cells as IntoIterator>::IntoIter,
+}
+
+impl Iterator for Scan<'_> {
+    type Item = io::Result<(RowId, Row)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((id, cell)) = self.cells.next() {
+                return Some(self.db.read_cell(&cell).map(|row| (id, row)));
+            }
+            if self.next_page == 0 {
+                return None;
+            }
+            match self.db.page_cells(self.next_page) {
+                Ok((next, cells)) => {
+                    self.next_page = next;
+                    self.cells = cells.into_iter();
+                }
+                Err(e) => {
+                    self.next_page = 0;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl RowId {
+    // 索引中保存的形式
+    fn pack(self) -> u64 {
+        (self.page as u64) << 16 | self.slot as u64
+    }
+
+    fn unpack(value: u64) -> RowId {
+        RowId { page: (value >> 16) as PageId, slot: value as u16 }
+    }
+}
+
+// UPDATE 已写入的新行
+struct Updated {
+    keys: Vec<Vec<u64>>,   // 各主键和 UNIQUE 约束上新行的键值的散列，见 key_constraints
+    moved: HashSet<RowId>, // 移到别的页的行的新位置
+    count: usize,
+}
+
+// 表的主键和各 UNIQUE 列，按内存中检查的顺序排列
+fn key_constraints(table: &Table) -> Vec<(Vec<usize>, Constraint)> {
+    let mut keys = Vec::new();
+    let primary_key = table.primary_key();
+    if !primary_key.is_empty() {
+        keys.push((primary_key, Constraint::PrimaryKey));
+    }
+    for (i, col) in table.columns.iter().enumerate() {
+        if col.unique {
+            keys.push((vec![i], Constraint::Unique));
+        }
+    }
+    keys
+}
+
+// 键值的散列（FNV-1a），保存在索引中，改变计算方法时须增加 INDEX_VERSION
+fn key_hash(values: &[Value]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (tag, text) in values_key(values) {
+        // 0xff 不会出现在 UTF-8 文本中，用来分隔各部分
+        for byte in tag.bytes().chain([0xff]).chain(text.bytes()).chain([0xff]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn key_violation(table: &Table, keys: &[(Vec<usize>, Constraint)], k: usize, values: &[Value]) -> DbError {
+    let (columns, constraint) = &keys[k];
+    let names: Vec<&str> = columns.iter().map(|&i| table.columns[i].name.as_str()).collect();
+    let shown: Vec<String> = values.iter().map(Value::to_string).collect();
+    table.violation(constraint.clone(), names.join(", "), shown.join(", "))
+}
+
+// 文件名是否为分页数据库的扩展名
+pub fn is_paged(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(PAGED_EXTENSION))
+}
+
+// 能否逐行流式执行，见 execute_streaming
+fn streamable(select: &Select) -> bool {
+    let mut exprs = select
+        .projection
+        .iter()
+        .filter_map(|item| match item {
+            SelectItem::Expr { expr, .. } => Some(expr),
+            _ => None,
+        })
+        .chain(&select.selection)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(select.order_by.iter().map(|order| &order.expr));
+    matches!(select.from, TableRef::Table { .. }) && !exprs.any(has_subquery)
+}
+
+fn has_subquery(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.clone().visit_mut(&mut |e| {
+        found |= matches!(e, Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists(_));
+    });
+    found
+}
+
+fn matches(selection: Option<&Expr>, scope: &Scope) -> Result<bool, DbError> {
+    match selection {
+        None => Ok(true),
+        Some(expr) => is_true(expr, scope),
+    }
+}
+
+fn storage_error(e: io::Error) -> DbError {
+    DbError::Storage(e.to_string())
+}
+
+fn init_heap(page: &mut [u8]) {
+    write_u16(page, 7, PAGE_SIZE as u16);
+}
+
+fn slot_count(page: &[u8]) -> usize {
+    read_u16(page, 5) as usize
+}
+
+fn slot_at(page: &[u8], slot: usize) -> (usize, usize) {
+    let at = HEAP_HEADER + slot * SLOT_SIZE;
+    (read_u16(page, at) as usize, read_u16(page, at + 2) as usize)
+}
+
+fn set_slot(page: &mut [u8], slot: usize, offset: usize, len: usize) {
+    let at = HEAP_HEADER + slot * SLOT_SIZE;
+    write_u16(page, at, offset as u16);
+    write_u16(page, at + 2, len as u16);
+}
+
+// 整理后可用的字节数，包括已删除的行留下的空隙
+fn free_bytes(page: &[u8]) -> usize {
+    let slots = slot_count(page);
+    let live: usize = (0..slots).map(|i| slot_at(page, i)).filter(|&(offset, _)| offset != 0).map(|(_, len)| len).sum();
+    PAGE_SIZE.saturating_sub(HEAP_HEADER + slots * SLOT_SIZE + live)
+}
+
+// 把行放入页中，slot 为空时复用已删除的槽或新增一个槽；空间不足时返回 None
+fn place_cell(page: &mut [u8], cell: &[u8], slot: Option<usize>) -> Option<usize> {
+    let slots = slot_count(page);
+    let slot = slot.or_else(|| (0..slots).find(|&i| slot_at(page, i).0 == 0));
+    let new_slots = if slot.is_some() { slots } else { slots + 1 };
+    if free_bytes(page) < cell.len() + (new_slots - slots) * SLOT_SIZE {
+        return None;
+    }
+    if (read_u16(page, 7) as usize) < HEAP_HEADER + new_slots * SLOT_SIZE + cell.len() {
+        compact(page);
+    }
+    let offset = read_u16(page, 7) as usize - cell.len();
+    page[offset..offset + cell.len()].copy_from_slice(cell);
+    write_u16(page, 7, offset as u16);
+    write_u16(page, 5, new_slots as u16);
+    let slot = slot.unwrap_or(slots);
+    set_slot(page, slot, offset, cell.len());
+    Some(slot)
+}
+
+// 取出一行并把槽标记为已删除
+fn take_cell(page: &mut [u8], slot: usize) -> io::Result<Vec<u8>> {
+    let (offset, len) = if slot < slot_count(page) { slot_at(page, slot) } else { (0, 0) };
+    if offset == 0 {
+        return Err(invalid(format!("行 {} 不存在", slot)));
+    }
+    let cell = page[offset..offset + len].to_vec();
+    set_slot(page, slot, 0, 0);
+    Ok(cell)
+}
+
+// 去掉末尾已删除的槽
+fn trim_slots(page: &mut [u8]) {
+    let mut slots = slot_count(page);
+    while slots > 0 && slot_at(page, slots - 1).0 == 0 {
+        slots -= 1;
+    }
+    write_u16(page, 5, slots as u16);
+}
+
+// 把各行移到页尾连续存放，合并已删除的行留下的空隙
+fn compact(page: &mut [u8]) {
+    let cells: Vec<(usize, Vec<u8>)> = (0..slot_count(page))
+        .filter_map(|i| {
+            let (offset, len) = slot_at(page, i);
+            (offset != 0).then(|| (i, page[offset..offset + len].to_vec()))
+        })
+        .collect();
+    let mut end = PAGE_SIZE;
+    for (i, cell) in cells {
+        end -= cell.len();
+        page[end..end + cell.len()].copy_from_slice(&cell);
+        set_slot(page, i, end, cell.len());
+    }
+    write_u16(page, 7, end as u16);
+}
+
+// 把数据写入新分配的溢出页链，返回首页
+fn write_chain(pager: &mut Pager, data: &[u8]) -> io::Result<PageId> {
+    let chunk_size = PAGE_SIZE - OVERFLOW_HEADER;
+    let chunks: Vec<&[u8]> = if data.is_empty() { vec![&[]] } else { data.chunks(chunk_size).collect() };
+    let pages = chunks.iter().map(|_| pager.allocate(PAGE_OVERFLOW)).collect::<io::Result<Vec<_>>>()?;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let page = pager.page_mut(pages[i])?;
+        write_u32(page, 1, pages.get(i + 1).copied().unwrap_or(0));
+        write_u16(page, 5, chunk.len() as u16);
+        page[OVERFLOW_HEADER..OVERFLOW_HEADER + chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(pages[0])
+}
+
+fn read_chain(pager: &mut Pager, first: PageId) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for id in chain_pages(pager, first)? {
+        let page = pager.page(id)?;
+        let len = read_u16(page, 5) as usize;
+        data.extend_from_slice(&page[OVERFLOW_HEADER..OVERFLOW_HEADER + len.min(PAGE_SIZE - OVERFLOW_HEADER)]);
+    }
+    Ok(data)
+}
+
+fn free_chain(pager: &mut Pager, first: PageId) -> io::Result<()> {
+    for id in chain_pages(pager, first)? {
+        pager.free(id)?;
+    }
+    Ok(())
+}
+
+// 溢出页链中的各页，链的长度不超过文件的页数，以免损坏的文件造成死循环
+fn chain_pages(pager: &mut Pager, first: PageId) -> io::Result<Vec<PageId>> {
+    let page_count = pager.page_count() as usize;
+    let mut pages = Vec::new();
+    let mut id = first;
+    while id != 0 {
+        let page = pager.page(id)?;
+        if page[0] != PAGE_OVERFLOW || pages.len() >= page_count {
+            return Err(invalid(format!("第 {} 页不是有效的溢出页", id)));
+        }
+        pages.push(id);
+        id = read_u32(page, 1);
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pager::journal_path;
+    use crate::part1::{Column, DataType};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sql_homework_paged_{}_{}.rspg", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(journal_path(&path));
+        path
+    }
+
+    fn rows(db: &mut PagedDatabase, sql: &str) -> Vec<Vec<String>> {
+        let result = db.execute(sql).unwrap();
+        result.rows.iter().map(|row| row.values.iter().map(Value::to_string).collect()).collect()
+    }
+
+    fn insert_many(db: &mut PagedDatabase, table: &str, ids: std::ops::Range<usize>, text: &str) {
+        let values: Vec<String> = ids.map(|i| format!("({}, '{}')", i, text)).collect();
+        db.execute(&format!("INSERT INTO {} VALUES {}", table, values.join(", "))).unwrap();
+    }
+
+    #[test]
+    fn rows_above_inline_limit_use_overflow_chains() {
+        let path = temp_file("overflow");
+        let mut db = PagedDatabase::create(&path, "t", 4).unwrap();
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, s VARCHAR(20000))").unwrap();
+        let long = "长".repeat(3000); // 9000 字节，需要三页溢出页
+        db.execute(&format!("INSERT INTO t VALUES (1, '{}'), (2, 'short')", long)).unwrap();
+
+        let (_, cells) = db.page_cells(db.catalog.tables[0].first_page).unwrap();
+        assert_eq!(cells.iter().map(|(_, cell)| cell[0]).collect::<Vec<_>>(), [CELL_OVERFLOW, CELL_INLINE]);
+        assert_eq!(cells[0].1.len(), 9);
+        assert_eq!(rows(&mut db, "SELECT s FROM t WHERE id = 1"), [[long.clone()]]);
+        drop(db);
+
+        let mut db = PagedDatabase::open(&path, 2).unwrap();
+        assert_eq!(rows(&mut db, "SELECT id, LENGTH(s) FROM t"), [["1", "3000"], ["2", "5"]]);
+        // 删除后溢出页回到空闲页链表，再写入同样长的行不增加页数
+        let pages = db.pager.page_count();
+        db.execute("DELETE FROM t WHERE id = 1").unwrap();
+        db.execute(&format!("UPDATE t SET s = '{}' WHERE id = 2", long)).unwrap();
+        assert_eq!(db.pager.page_count(), pages);
+        assert_eq!(rows(&mut db, "SELECT LENGTH(s) FROM t"), [["3000"]]);
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn deleted_slots_and_pages_are_reused() {
+        let path = temp_file("reuse");
+        let mut db = PagedDatabase::create(&path, "t", 4).unwrap();
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, s VARCHAR(100))").unwrap();
+        let text = "x".repeat(80);
+        insert_many(&mut db, "t", 0..500, &text);
+        let pages = db.pager.page_count();
+        assert!(pages > 10);
+
+        // 删除的行留下的空间在插入时复用，表的页数不变
+        db.execute("DELETE FROM t WHERE id % 2 = 1").unwrap();
+        assert!(!db.catalog.tables[0].free_pages.is_empty());
+        insert_many(&mut db, "t", 1000..1250, &text);
+        assert_eq!(db.pager.page_count(), pages);
+        assert_eq!(rows(&mut db, "SELECT id FROM t").len(), 500);
+
+        // 已删除的槽被复用，空隙在放不下时整理
+        let mut page = vec![0; PAGE_SIZE];
+        init_heap(&mut page);
+        for len in [100, 200, 300] {
+            place_cell(&mut page, &vec![CELL_INLINE; len], None).unwrap();
+        }
+        take_cell(&mut page, 0).unwrap();
+        assert_eq!(place_cell(&mut page, &[CELL_INLINE; 40], None), Some(0));
+        // 复用的槽不占用新的空间，恰好填满整页
+        take_cell(&mut page, 1).unwrap();
+        let fill = vec![CELL_INLINE; free_bytes(&page)];
+        assert_eq!(place_cell(&mut page, &fill, None), Some(1));
+        assert_eq!((free_bytes(&page), slot_count(&page)), (0, 3));
+        assert_eq!(place_cell(&mut page, &[CELL_INLINE], None), None);
+
+        // 删除的表的页回到空闲页链表，新表使用这些页
+        db.execute("DROP TABLE t").unwrap();
+        db.execute("CREATE TABLE u (id INT PRIMARY KEY, s VARCHAR(100))").unwrap();
+        insert_many(&mut db, "u", 0..500, &text);
+        assert_eq!(db.pager.page_count(), pages);
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn update_spans_batches_and_failed_statements_roll_back() {
+        let path = temp_file("update");
+        let mut db = PagedDatabase::create(&path, "t", 4).unwrap();
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, u INT UNIQUE, s VARCHAR(3000))").unwrap();
+        let values: Vec<String> = (0..2500).map(|i| format!("({}, {}, 'a')", i, i)).collect();
+        db.execute(&format!("INSERT INTO t VALUES {}", values.join(", "))).unwrap();
+
+        // 新键值与尚未更新的行暂时相同，但更新后没有重复
+        assert_eq!(db.execute("UPDATE t SET id = id + 1").unwrap().affected_rows, 2500);
+        // 变长的行移到后面的页，不会被再次更新
+        let long = "b".repeat(2000);
+        assert_eq!(db.execute(&format!("UPDATE t SET s = '{}', u = u + 10000", long)).unwrap().affected_rows, 2500);
+        assert_eq!(rows(&mut db, "SELECT id FROM t WHERE LENGTH(s) = 2000").len(), 2500);
+
+        // 与之前的批次写入的新行重复，已写入的批次一并回滚
+        let err = db.execute("UPDATE t SET u = 10000 + id % 2400").unwrap_err();
+        assert!(err.to_string().contains("唯一约束"), "{}", err);
+        assert!(!journal_path(&path).exists());
+        let first: Vec<Vec<String>> = (1..=3).map(|i| vec![i.to_string(), (i + 9999).to_string()]).collect();
+        assert_eq!(rows(&mut db, "SELECT id, u FROM t WHERE id <= 3"), first);
+        // 与未更新的行重复
+        let err = db.execute("UPDATE t SET id = 2500 WHERE id = 1").unwrap_err();
+        assert!(err.to_string().contains("主键"), "{}", err);
+        drop(db);
+
+        let mut db = PagedDatabase::open(&path, 8).unwrap();
+        assert_eq!(rows(&mut db, "SELECT id, u FROM t WHERE id <= 3"), first);
+        assert_eq!(rows(&mut db, "SELECT id FROM t WHERE LENGTH(s) = 2000 AND id > 2499"), [["2500"]]);
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn key_checks_look_up_the_index() {
+        let path = temp_file("index");
+        let mut db = PagedDatabase::create(&path, "t", 16).unwrap();
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, email VARCHAR(20) UNIQUE, s VARCHAR(100))").unwrap();
+        let text = "x".repeat(80);
+        let values: Vec<String> = (0..5000).map(|i| format!("({}, 'u{}', '{}')", i, i, text)).collect();
+        db.execute(&format!("INSERT INTO t VALUES {}", values.join(", "))).unwrap();
+        assert!(db.pager.page_count() > 100);
+
+        // 插入一行只读取索引的几层和散列相同的行，不扫描整张表
+        let misses = db.pool_stats().misses;
+        db.execute("INSERT INTO t VALUES (5000, 'u5000', 'a')").unwrap();
+        assert!(db.pool_stats().misses - misses < 10);
+        let err = db.execute("INSERT INTO t VALUES (5001, 'u4321', 'a')").unwrap_err();
+        assert!(err.to_string().contains("唯一约束"), "{}", err);
+        let err = db.execute("UPDATE t SET id = 7 WHERE email = 'u4000'").unwrap_err();
+        assert!(err.to_string().contains("主键"), "{}", err);
+
+        // 删除和更新后旧键值可以再次使用，含 NULL 的键值不参与比较
+        db.execute("DELETE FROM t WHERE id = 7").unwrap();
+        db.execute("UPDATE t SET email = NULL WHERE id < 3").unwrap();
+        db.execute("INSERT INTO t VALUES (7, 'u1', 'a'), (-1, NULL, 'b')").unwrap();
+        assert_eq!(rows(&mut db, "SELECT id FROM t WHERE email = 'u1'"), [["7"]]);
+
+        // 没有索引的旧文件打开时建立索引
+        for root in std::mem::take(&mut db.catalog.tables[0].indexes) {
+            btree::free(&mut db.pager, root).unwrap();
+        }
+        db.catalog.index_version = 0;
+        db.catalog_dirty = true;
+        db.flush().unwrap();
+        drop(db);
+        let mut db = PagedDatabase::open(&path, 16).unwrap();
+        assert_eq!(db.catalog.tables[0].indexes.len(), 2);
+        let err = db.execute("INSERT INTO t VALUES (4999, 'new', 'a')").unwrap_err();
+        assert!(err.to_string().contains("主键"), "{}", err);
+        db.execute("INSERT INTO t VALUES (5001, 'u2', 'a')").unwrap();
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn grouped_and_sorted_queries_stream_over_the_table() {
+        let path = temp_file("select");
+        let mut db = PagedDatabase::create(&path, "t", 8).unwrap();
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY, g VARCHAR(10), x DECIMAL(8,2), s VARCHAR(100))").unwrap();
+        let text = "x".repeat(80);
+        let values: Vec<String> = (0..3000)
+            .map(|i| {
+                let x = if i % 11 == 0 { "NULL".to_string() } else { format!("{}.25", i % 50) };
+                format!("({}, 'g{}', {}, '{}')", i, i % 7, x, text)
+            })
+            .collect();
+        db.execute(&format!("INSERT INTO t VALUES {}", values.join(", "))).unwrap();
+
+        // 与读入内存后执行的结果（包括顺序）相同
+        let mut memory = db.export().unwrap();
+        for sql in [
+            "SELECT COUNT(*), COUNT(x), SUM(x), AVG(x), MIN(x), MAX(x) FROM t",
+            "SELECT COUNT(*), SUM(x) FROM t WHERE id < 0",
+            "SELECT g, COUNT(*), SUM(x) FROM t WHERE id % 3 = 0 GROUP BY g HAVING COUNT(*) > 100 ORDER BY g DESC",
+            "SELECT COUNT(DISTINCT x), STRING_AGG(g, '-') FROM t WHERE id < 20",
+            "SELECT DISTINCT g FROM t ORDER BY 1",
+            "SELECT id, x FROM t ORDER BY x DESC NULLS LAST, id LIMIT 5 OFFSET 10",
+            "SELECT id FROM t ORDER BY x, id DESC",
+            "SELECT g, MAX(id) AS m FROM t GROUP BY g ORDER BY m LIMIT 3",
+        ] {
+            let expected: Vec<Vec<String>> = memory
+                .execute(sql)
+                .unwrap()
+                .rows
+                .iter()
+                .map(|row| row.values.iter().map(Value::to_string).collect())
+                .collect();
+            assert_eq!(rows(&mut db, sql), expected, "{}", sql);
+        }
+
+        // 没有 ORDER BY 时读够 LIMIT 行就停止扫描
+        let misses = db.pool_stats().misses;
+        assert_eq!(rows(&mut db, "SELECT id FROM t LIMIT 2"), [["0"], ["1"]]);
+        assert!(db.pool_stats().misses - misses < 5);
+
+        let err = db.execute("SELECT * FROM t WHERE id IN (SELECT id FROM t)").unwrap_err();
+        assert!(matches!(err, DbError::Unsupported(_)), "{}", err);
+        let err = db.execute("SELECT SUM(s) FROM t").unwrap_err();
+        assert!(matches!(err, DbError::Arithmetic(_) | DbError::TypeMismatch(_)), "{}", err);
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn old_catalog_is_migrated_on_open() {
+        let path = temp_file("migrate");
+        let mut db = PagedDatabase::create(&path, "t", 4).unwrap();
+        let columns = vec![Column::new("id", DataType::Int), Column::new("d", DataType::Date)];
+        let table = Table { name: "t".to_string(), columns, rows: Vec::new(), foreign_keys: Vec::new() };
+        let t = db.add_table(table).unwrap();
+        // 早期的导入没有升级旧数据，值仍是文本，目录中也没有格式版本
+        db.catalog.format_version = 0;
+        db.insert_values(t, &[Value::Text("1".to_string()), Value::Text("2024-01-02".to_string())]).unwrap();
+        db.insert_values(t, &[Value::Text("2".to_string()), Value::Text("坏日期".to_string())]).unwrap();
+        db.flush().unwrap();
+        drop(db);
+
+        let mut db = PagedDatabase::open(&path, 4).unwrap();
+        assert_eq!(db.catalog.format_version, FORMAT_VERSION);
+        let scanned: Vec<Row> = db.scan("t").unwrap().map(|item| item.unwrap().1).collect();
+        assert!(matches!(scanned[0].values[..], [Value::Int(1), Value::Date(_)]));
+        assert!(matches!(&scanned[1].values[..], [Value::Int(2), Value::Text(text)] if text == "坏日期"));
+        assert_eq!(db.export().unwrap().format_version, FORMAT_VERSION);
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+}
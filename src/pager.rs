@@ -0,0 +1,482 @@
+// 分页文件与缓冲池。文件由定长的页组成，第 0 页为文件头：
+//   "RSPG"、格式版本（u16）、页大小（u32）、页数（u32）、空闲页链表的首页（u32）、目录的首页（u32）
+// 其余各页的第一个字节为页的类型，空闲页随后是链表中下一个空闲页的页号。页号 0 在链表中表示结束。
+// 读写都经过缓冲池，池中最多保留 capacity 个页，满时按最近最少使用淘汰，被淘汰的脏页先写回文件。
+// 页被原地覆盖之前，它在上次提交时的内容先写入回滚日志（如 data.rspg-journal）并刷到磁盘；
+// flush 写完并刷盘后删除回滚日志即为提交。打开文件时回滚日志仍在，说明上次提交没有完成，
+// 按日志恢复各页并截去之后新增的页。回滚日志：
+//   "RSPJ"、上次提交时的页数（u32），随后每条为页号（u32）、页的内容、校验和（u32）
+use crate::storage::sync_dir;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const PAGE_SIZE: usize = 4096;
+// 缓冲池默认的页数（1 MiB）
+pub const DEFAULT_POOL_PAGES: usize = 256;
+
+pub type PageId = u32;
+
+const MAGIC: &[u8; 4] = b"RSPG";
+const PAGER_VERSION: u16 = 1;
+
+const JOURNAL_MAGIC: &[u8; 4] = b"RSPJ";
+const JOURNAL_HEADER: usize = 8;
+const JOURNAL_ENTRY: usize = 4 + PAGE_SIZE + 4;
+
+// 页的类型，写在每页的第一个字节
+pub const PAGE_FREE: u8 = 0;
+pub const PAGE_HEAP: u8 = 1;
+pub const PAGE_OVERFLOW: u8 = 2;
+pub const PAGE_INDEX_LEAF: u8 = 3;
+pub const PAGE_INDEX_INNER: u8 = 4;
+
+// 缓冲池的统计，用于观察池的大小是否合适
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct Frame {
+    data: Box<[u8]>,
+    dirty: bool,
+    last_used: u64,
+}
+
+pub struct Pager {
+    file: File,
+    path: PathBuf,
+    journal: Option<File>,      // 上次提交之后已创建的回滚日志
+    journaled: HashSet<PageId>, // 已写入回滚日志的页
+    committed_pages: u32,       // 上次提交时的页数，之后新增的页不必写入回滚日志
+    page_count: u32,
+    free_list: PageId,
+    root: PageId, // 目录的首页，0 表示还没有目录
+    header_dirty: bool,
+    capacity: usize,
+    frames: HashMap<PageId, Frame>,
+    lru: BTreeMap<u64, PageId>, // 最近使用的时刻 -> 页号，最小的先淘汰
+    clock: u64,
+    stats: PoolStats,
+}
+
+impl Pager {
+    // 创建新的分页文件，覆盖同名文件，同名文件的回滚日志一并删除
+    pub fn create(path: &Path, capacity: usize) -> io::Result<Pager> {
+        remove_journal(path)?;
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let mut pager = Pager::with_file(file, path, capacity);
+        pager.page_count = 1;
+        pager.header_dirty = true;
+        pager.flush()?;
+        Ok(pager)
+    }
+
+    pub fn open(path: &Path, capacity: usize) -> io::Result<Pager> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        rollback(path, &mut file)?;
+        let mut pager = Pager::with_file(file, path, capacity);
+        pager.read_header()?;
+        Ok(pager)
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = vec![0; PAGE_SIZE];
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_exact(&mut header).map_err(|_| invalid("文件头不完整".to_string()))?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid("不是分页数据库文件".to_string()));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version > PAGER_VERSION {
+            return Err(invalid(format!("文件格式版本 {} 高于当前支持的版本 {}", version, PAGER_VERSION)));
+        }
+        let page_size = read_u32(&header, 6) as usize;
+        if page_size != PAGE_SIZE {
+            return Err(invalid(format!("页大小 {} 与当前的 {} 不同", page_size, PAGE_SIZE)));
+        }
+        self.page_count = read_u32(&header, 10);
+        self.committed_pages = self.page_count;
+        self.free_list = read_u32(&header, 14);
+        self.root = read_u32(&header, 18);
+        self.header_dirty = false;
+        let expected = self.page_count as u64 * PAGE_SIZE as u64;
+        if self.page_count == 0 || self.file.metadata()?.len() < expected {
+            return Err(invalid(format!("文件应有 {} 页，实际长度不足", self.page_count)));
+        }
+        Ok(())
+    }
+
+    fn with_file(file: File, path: &Path, capacity: usize) -> Pager {
+        Pager {
+            file,
+            path: path.to_path_buf(),
+            journal: None,
+            journaled: HashSet::new(),
+            committed_pages: 0,
+            page_count: 0,
+            free_list: 0,
+            root: 0,
+            header_dirty: false,
+            capacity: capacity.max(1),
+            frames: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            stats: PoolStats::default(),
+        }
+    }
+
+    pub fn root(&self) -> PageId {
+        self.root
+    }
+
+    pub fn set_root(&mut self, root: PageId) {
+        self.root = root;
+        self.header_dirty = true;
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    // 只读地访问一页
+    pub fn page(&mut self, id: PageId) -> io::Result<&[u8]> {
+        self.load(id)?;
+        Ok(&self.frames[&id].data)
+    }
+
+    // 修改一页，该页在写回文件之前一直是脏页
+    pub fn page_mut(&mut self, id: PageId) -> io::Result<&mut [u8]> {
+        self.load(id)?;
+        let frame = self.frames.get_mut(&id).expect("页已载入缓冲池");
+        frame.dirty = true;
+        Ok(&mut frame.data)
+    }
+
+    // 分配一页并清零，优先复用空闲页
+    pub fn allocate(&mut self, page_type: u8) -> io::Result<PageId> {
+        let id = if self.free_list != 0 {
+            let id = self.free_list;
+            self.free_list = read_u32(self.page(id)?, 1);
+            id
+        } else {
+            let id = self.page_count;
+            self.page_count = id.checked_add(1).ok_or_else(|| invalid("页数超过上限".to_string()))?;
+            // 新页还不在文件中，直接放入缓冲池
+            self.insert_frame(id, vec![0; PAGE_SIZE].into_boxed_slice())?;
+            id
+        };
+        self.header_dirty = true;
+        let page = self.page_mut(id)?;
+        page.fill(0);
+        page[0] = page_type;
+        Ok(id)
+    }
+
+    // 把页放回空闲页链表
+    pub fn free(&mut self, id: PageId) -> io::Result<()> {
+        let next = self.free_list;
+        let page = self.page_mut(id)?;
+        page.fill(0);
+        page[0] = PAGE_FREE;
+        write_u32(page, 1, next);
+        self.free_list = id;
+        self.header_dirty = true;
+        Ok(())
+    }
+
+    // 把脏页和文件头写回文件并刷到磁盘，然后删除回滚日志，提交上次提交之后的全部修改
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut dirty: Vec<PageId> = self.frames.iter().filter(|(_, f)| f.dirty).map(|(&id, _)| id).collect();
+        dirty.sort_unstable();
+        let header = self.header_dirty.then_some(0);
+        self.journal_pages(header.iter().chain(&dirty).copied())?;
+        for id in dirty {
+            let frame = self.frames.get_mut(&id).expect("脏页在缓冲池中");
+            write_page(&mut self.file, id, &frame.data)?;
+            frame.dirty = false;
+        }
+        if self.header_dirty {
+            let mut header = vec![0; PAGE_SIZE];
+            header[0..4].copy_from_slice(MAGIC);
+            header[4..6].copy_from_slice(&PAGER_VERSION.to_le_bytes());
+            write_u32(&mut header, 6, PAGE_SIZE as u32);
+            write_u32(&mut header, 10, self.page_count);
+            write_u32(&mut header, 14, self.free_list);
+            write_u32(&mut header, 18, self.root);
+            write_page(&mut self.file, 0, &header)?;
+            self.header_dirty = false;
+        }
+        self.file.sync_all()?;
+        if self.journal.take().is_some() {
+            remove_journal(&self.path)?;
+        }
+        self.journaled.clear();
+        self.committed_pages = self.page_count;
+        Ok(())
+    }
+
+    // 放弃上次提交之后的全部修改：清空缓冲池，按回滚日志恢复已被覆盖的页，截去新增的页后重新读取文件头
+    pub fn rollback(&mut self) -> io::Result<()> {
+        self.frames.clear();
+        self.lru.clear();
+        self.journal = None;
+        self.journaled.clear();
+        rollback(&self.path, &mut self.file)?;
+        self.file.set_len(self.committed_pages as u64 * PAGE_SIZE as u64)?;
+        self.read_header()
+    }
+
+    // 页将被原地覆盖，先把它们在上次提交时的内容写入回滚日志并刷到磁盘
+    fn journal_pages(&mut self, ids: impl Iterator<Item = PageId>) -> io::Result<()> {
+        let ids: Vec<PageId> = ids.filter(|id| *id < self.committed_pages && !self.journaled.contains(id)).collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut entries = Vec::with_capacity(JOURNAL_HEADER + ids.len() * JOURNAL_ENTRY);
+        if self.journal.is_none() {
+            entries.extend_from_slice(JOURNAL_MAGIC);
+            entries.extend_from_slice(&self.committed_pages.to_le_bytes());
+        }
+        let mut data = vec![0; PAGE_SIZE];
+        for &id in &ids {
+            self.file.seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+            self.file.read_exact(&mut data)?;
+            entries.extend_from_slice(&id.to_le_bytes());
+            entries.extend_from_slice(&data);
+            entries.extend_from_slice(&checksum(id, &data).to_le_bytes());
+        }
+        let created = self.journal.is_none();
+        if created {
+            let path = journal_path(&self.path);
+            self.journal = Some(OpenOptions::new().write(true).create(true).truncate(true).open(path)?);
+        }
+        let journal = self.journal.as_mut().expect("回滚日志已创建");
+        journal.write_all(&entries)?;
+        journal.sync_data()?;
+        if created {
+            sync_dir(&self.path);
+        }
+        self.journaled.extend(ids);
+        Ok(())
+    }
+
+    // 确保页在缓冲池中，并记为最近使用
+    fn load(&mut self, id: PageId) -> io::Result<()> {
+        if id == 0 || id >= self.page_count {
+            return Err(invalid(format!("页号 {} 超出范围", id)));
+        }
+        self.clock += 1;
+        if let Some(frame) = self.frames.get_mut(&id) {
+            self.stats.hits += 1;
+            self.lru.remove(&frame.last_used);
+            frame.last_used = self.clock;
+            self.lru.insert(self.clock, id);
+            return Ok(());
+        }
+        self.stats.misses += 1;
+        let mut data = vec![0; PAGE_SIZE].into_boxed_slice();
+        self.file.seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+        self.file.read_exact(&mut data)?;
+        self.insert_frame(id, data)
+    }
+
+    fn insert_frame(&mut self, id: PageId, data: Box<[u8]>) -> io::Result<()> {
+        while self.frames.len() >= self.capacity {
+            self.evict()?;
+        }
+        self.clock += 1;
+        self.frames.insert(id, Frame { data, dirty: false, last_used: self.clock });
+        self.lru.insert(self.clock, id);
+        Ok(())
+    }
+
+    fn evict(&mut self) -> io::Result<()> {
+        let Some((_, id)) = self.lru.pop_first() else {
+            return Ok(());
+        };
+        let frame = self.frames.remove(&id).expect("LRU 中的页在缓冲池中");
+        if frame.dirty {
+            self.journal_pages(std::iter::once(id))?;
+            write_page(&mut self.file, id, &frame.data)?;
+        }
+        self.stats.evictions += 1;
+        Ok(())
+    }
+}
+
+// 分页文件对应的回滚日志，如 data.rspg 对应 data.rspg-journal
+pub fn journal_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push("-journal");
+    path.with_file_name(name)
+}
+
+fn remove_journal(path: &Path) -> io::Result<()> {
+    match fs::remove_file(journal_path(path)) {
+        Ok(()) => {
+            sync_dir(path);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// 上次提交没有完成时按回滚日志恢复。日志末尾不完整或校验和不符的记录写入时崩溃，
+// 对应的页还没有被覆盖，到此为止
+fn rollback(path: &Path, file: &mut File) -> io::Result<()> {
+    let journal = match fs::read(journal_path(path)) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if journal.len() >= JOURNAL_HEADER && &journal[0..4] == JOURNAL_MAGIC {
+        let committed_pages = read_u32(&journal, 4);
+        for entry in journal[JOURNAL_HEADER..].chunks_exact(JOURNAL_ENTRY) {
+            let id = read_u32(entry, 0);
+            let data = &entry[4..4 + PAGE_SIZE];
+            if id >= committed_pages || read_u32(entry, 4 + PAGE_SIZE) != checksum(id, data) {
+                break;
+            }
+            write_page(file, id, data)?;
+        }
+        file.set_len(committed_pages as u64 * PAGE_SIZE as u64)?;
+        file.sync_all()?;
+    }
+    remove_journal(path)
+}
+
+// 回滚日志记录的校验和（FNV-1a），包括页号
+fn checksum(id: PageId, data: &[u8]) -> u32 {
+    id.to_le_bytes().iter().chain(data).fold(0x811c_9dc5, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+fn write_page(file: &mut File, id: PageId, data: &[u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+    file.write_all(data)
+}
+
+pub fn read_u16(page: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([page[offset], page[offset + 1]])
+}
+
+pub fn write_u16(page: &mut [u8], offset: usize, value: u16) {
+    page[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn read_u32(page: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(page[offset..offset + 4].try_into().expect("4 字节"))
+}
+
+pub fn write_u32(page: &mut [u8], offset: usize, value: u32) {
+    page[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(crate) fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("无效的分页数据库文件: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("sql_homework_pager_{}_{}.rspg", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(journal_path(&path));
+        path
+    }
+
+    // 分配 n 页，第 i 页的内容以 i 填充
+    fn fill_pages(pager: &mut Pager, n: u8) -> Vec<PageId> {
+        (0..n)
+            .map(|i| {
+                let id = pager.allocate(PAGE_HEAP).unwrap();
+                pager.page_mut(id).unwrap()[1..].fill(i);
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used_page() {
+        let path = temp_file("lru");
+        let mut pager = Pager::create(&path, 2).unwrap();
+        let ids = fill_pages(&mut pager, 3);
+        assert_eq!(pager.stats().evictions, 1);
+        assert!(!pager.frames.contains_key(&ids[0]));
+        // 访问第 1 页后，最久未用的是第 2 页
+        pager.page(ids[1]).unwrap();
+        pager.page(ids[0]).unwrap();
+        assert!(pager.frames.contains_key(&ids[1]) && !pager.frames.contains_key(&ids[2]));
+        assert_eq!(pager.frames.len(), 2);
+        // 被淘汰的脏页已写回文件，再次读入时内容不变
+        for (i, &id) in ids.iter().enumerate() {
+            assert!(pager.page(id).unwrap()[1..].iter().all(|&b| b == i as u8));
+        }
+        let stats = pager.stats();
+        assert!(stats.misses >= 2 && stats.hits >= 1);
+        drop(pager);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn uncommitted_writes_are_rolled_back_on_open() {
+        let path = temp_file("rollback");
+        let mut pager = Pager::create(&path, 1).unwrap();
+        let ids = fill_pages(&mut pager, 3);
+        pager.flush().unwrap();
+        assert!(!journal_path(&path).exists());
+
+        // 缓冲池只有一页，修改过的页在淘汰时原地写回，然后在提交之前崩溃
+        for &id in &ids {
+            pager.page_mut(id).unwrap()[1..].fill(0xEE);
+        }
+        let added = fill_pages(&mut pager, 2);
+        assert!(journal_path(&path).exists());
+        assert!(pager.stats().evictions > 0);
+        drop(pager); // 没有 flush，相当于崩溃
+
+        let mut pager = Pager::open(&path, 4).unwrap();
+        assert!(!journal_path(&path).exists());
+        assert_eq!(pager.page_count(), 4);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 4 * PAGE_SIZE as u64);
+        for (i, &id) in ids.iter().enumerate() {
+            assert!(pager.page(id).unwrap()[1..].iter().all(|&b| b == i as u8));
+        }
+        assert!(pager.page(added[0]).is_err());
+        drop(pager);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn torn_journal_entry_is_ignored() {
+        let path = temp_file("torn");
+        let mut pager = Pager::create(&path, 1).unwrap();
+        let ids = fill_pages(&mut pager, 2);
+        pager.flush().unwrap();
+        pager.page_mut(ids[0]).unwrap()[1..].fill(0xEE);
+        pager.page(ids[1]).unwrap(); // 淘汰第一页，写入回滚日志后原地覆盖
+        drop(pager);
+
+        // 日志末尾追加一条不完整的记录，恢复时忽略
+        let mut journal = OpenOptions::new().append(true).open(journal_path(&path)).unwrap();
+        journal.write_all(&ids[1].to_le_bytes()).unwrap();
+        journal.write_all(&[0xAB; 100]).unwrap();
+        drop(journal);
+
+        let mut pager = Pager::open(&path, 4).unwrap();
+        assert!(pager.page(ids[0]).unwrap()[1..].iter().all(|&b| b == 0));
+        assert!(pager.page(ids[1]).unwrap()[1..].iter().all(|&b| b == 1));
+        drop(pager);
+        fs::remove_file(path).unwrap();
+    }
+}
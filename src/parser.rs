@@ -0,0 +1,1247 @@
+use crate::ast::*;
+use crate::blob::DEFAULT_MAX_SIZE;
+use crate::decimal::MAX_PRECISION;
+use crate::part1::{DataType, ForeignKey, ReferentialAction};
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::part1::DbError;
+use crate::value::{parse_number, Value};
+
+// 保留关键字，未加引号时不能用作表名或列名
+const RESERVED: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE", "SET", "DELETE", "AND", "OR",
+    "NOT", "NULL", "TRUE", "FALSE", "IS", "IN", "BETWEEN", "LIKE", "ILIKE",
+    "DISTINCT", "ALL", "AS", "ORDER", "BY", "ASC", "DESC", "LIMIT", "OFFSET",
+    "GROUP", "HAVING", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "OUTER", "CROSS", "ON", "USING",
+    "UNION", "INTERSECT", "EXCEPT", "EXISTS", "CREATE", "DROP", "TABLE", "ALTER",
+];
+
+// 可以写成 DATE '2024-01-31' 这种带类型的字面量的类型名
+const TYPED_LITERALS: &[&str] = &["DATE", "TIME", "TIMESTAMP", "TIMESTAMPTZ", "INTERVAL"];
+// 可以不带括号调用的函数
+const CURRENT_FUNCTIONS: &[&str] = &["CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP"];
+// INTERVAL n unit 中的单位
+const INTERVAL_UNITS: &[&str] = &["YEAR", "QUARTER", "MONTH", "WEEK", "DAY", "HOUR", "MINUTE", "SECOND"];
+
+// 查询末尾的 ORDER BY、LIMIT、OFFSET
+type OrderLimit = (Vec<OrderByExpr>, Option<usize>, Option<usize>);
+
+// 解析一条 SQL 语句，允许以分号结尾
+pub fn parse(sql: &str) -> Result<Statement, DbError> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        pos: 0,
+    };
+    let statement = parser.parse_statement()?;
+    parser.consume(&TokenKind::Semicolon);
+    parser.expect(&TokenKind::Eof)?;
+    Ok(statement)
+}
+
+// 解析单独的表达式，用于表结构中保存的 DEFAULT 和 CHECK
+pub fn parse_expression(sql: &str) -> Result<Expr, DbError> {
+    let mut parser = Parser {
+        tokens: tokenize(sql)?,
+        pos: 0,
+    };
+    let expr = parser.parse_expr()?;
+    parser.expect(&TokenKind::Eof)?;
+    Ok(expr)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn peek_nth(&self, n: usize) -> &TokenKind {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.pos + n).min(last)].kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error_at(&self, token: &Token, message: String) -> DbError {
+        DbError::Syntax {
+            message,
+            line: token.line,
+            column: token.column,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> DbError {
+        let token = self.peek();
+        self.error_at(token, format!("期望 {}，但遇到 {}", expected, token.kind.describe()))
+    }
+
+    fn consume(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind) -> Result<(), DbError> {
+        if self.consume(kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&kind.describe()))
+        }
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(s) if s.eq_ignore_ascii_case(keyword))
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), DbError> {
+        if self.consume_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    // 标识符：未加引号的保留字不能作为标识符
+    fn parse_identifier(&mut self) -> Result<String, DbError> {
+        match &self.peek().kind {
+            TokenKind::Ident(s) if !RESERVED.iter().any(|k| s.eq_ignore_ascii_case(k)) => {
+                let name = s.clone();
+                self.advance();
+                Ok(name)
+            }
+            TokenKind::QuotedIdent(s) => {
+                let name = s.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("标识符")),
+        }
+    }
+
+    // 当前位置是否为括号括起的子查询 (SELECT ...)
+    fn peek_subquery(&self) -> bool {
+        self.peek().kind == TokenKind::LParen
+            && matches!(self.peek_nth(1), TokenKind::Ident(s) if s.eq_ignore_ascii_case("SELECT"))
+    }
+
+    // (query)
+    fn parse_subquery(&mut self) -> Result<Query, DbError> {
+        self.expect(&TokenKind::LParen)?;
+        let query = self.parse_query()?;
+        self.expect(&TokenKind::RParen)?;
+        Ok(query)
+    }
+
+    fn parse_identifier_list(&mut self) -> Result<Vec<String>, DbError> {
+        let mut names = vec![self.parse_identifier()?];
+        while self.consume(&TokenKind::Comma) {
+            names.push(self.parse_identifier()?);
+        }
+        Ok(names)
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, DbError> {
+        if self.peek_keyword("SELECT") || self.peek().kind == TokenKind::LParen {
+            self.parse_query().map(Statement::Query)
+        } else if self.peek_keyword("INSERT") {
+            self.parse_insert().map(Statement::Insert)
+        } else if self.peek_keyword("UPDATE") {
+            self.parse_update().map(Statement::Update)
+        } else if self.peek_keyword("DELETE") {
+            self.parse_delete().map(Statement::Delete)
+        } else if self.peek_keyword("CREATE") {
+            self.parse_create_table().map(Statement::CreateTable)
+        } else if self.peek_keyword("DROP") {
+            self.parse_drop_table().map(Statement::DropTable)
+        } else if self.peek_keyword("ALTER") {
+            self.parse_alter_table().map(Statement::AlterTable)
+        } else {
+            Err(self.unexpected("SELECT、INSERT、UPDATE、DELETE、CREATE、DROP 或 ALTER"))
+        }
+    }
+
+    // set_expr [ORDER BY ...] [LIMIT n] [OFFSET m]，排序和分页作用于整个查询
+    fn parse_query(&mut self) -> Result<Query, DbError> {
+        let mut query = self.parse_set_expr()?;
+        let token = self.peek().clone();
+        let (order_by, limit, offset) = self.parse_order_limit()?;
+        if order_by.is_empty() && limit.is_none() && offset.is_none() {
+            return Ok(query);
+        }
+        match &mut query {
+            Query::Select(select) if select.order_by.is_empty() && select.limit.is_none() && select.offset.is_none() => {
+                select.order_by = order_by;
+                select.limit = limit;
+                select.offset = offset;
+            }
+            Query::SetOperation(set) if set.order_by.is_empty() && set.limit.is_none() && set.offset.is_none() => {
+                set.order_by = order_by;
+                set.limit = limit;
+                set.offset = offset;
+            }
+            _ => return Err(self.error_at(&token, "括号内的查询已有 ORDER BY/LIMIT/OFFSET".to_string())),
+        }
+        Ok(query)
+    }
+
+    // 集合运算：INTERSECT 优先于 UNION 和 EXCEPT，同级从左到右结合
+    fn parse_set_expr(&mut self) -> Result<Query, DbError> {
+        let mut left = self.parse_intersect()?;
+        loop {
+            let op = if self.consume_keyword("UNION") {
+                SetOperator::Union
+            } else if self.consume_keyword("EXCEPT") {
+                SetOperator::Except
+            } else {
+                return Ok(left);
+            };
+            let all = self.parse_set_quantifier();
+            let right = self.parse_intersect()?;
+            left = set_operation(left, op, all, right);
+        }
+    }
+
+    fn parse_intersect(&mut self) -> Result<Query, DbError> {
+        let mut left = self.parse_query_primary()?;
+        while self.consume_keyword("INTERSECT") {
+            let all = self.parse_set_quantifier();
+            let right = self.parse_query_primary()?;
+            left = set_operation(left, SetOperator::Intersect, all, right);
+        }
+        Ok(left)
+    }
+
+    // [ALL | DISTINCT]，默认去重
+    fn parse_set_quantifier(&mut self) -> bool {
+        if self.consume_keyword("ALL") {
+            true
+        } else {
+            self.consume_keyword("DISTINCT");
+            false
+        }
+    }
+
+    // SELECT ... 或 (query)
+    fn parse_query_primary(&mut self) -> Result<Query, DbError> {
+        if self.peek().kind == TokenKind::LParen {
+            self.parse_subquery()
+        } else {
+            Ok(Query::Select(Box::new(self.parse_select()?)))
+        }
+    }
+
+    // ORDER BY 与 LIMIT/OFFSET 在 parse_query 中解析
+    fn parse_select(&mut self) -> Result<Select, DbError> {
+        self.expect_keyword("SELECT")?;
+        let distinct = self.consume_keyword("DISTINCT");
+        if !distinct {
+            self.consume_keyword("ALL");
+        }
+
+        let mut projection = Vec::new();
+        if !self.peek_keyword("FROM") {
+            loop {
+                projection.push(self.parse_select_item()?);
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.expect_keyword("FROM")?;
+        let from = self.parse_table_ref()?;
+        if self.peek_keyword("AND") {
+            let token = self.peek().clone();
+            return Err(self.error_at(
+                &token,
+                "不再支持 SELECT FROM a AND b 的多表语法，请改用 SELECT * FROM a UNION ALL SELECT * FROM b".to_string(),
+            ));
+        }
+        let selection = self.parse_where()?;
+
+        let mut group_by = Vec::new();
+        if self.consume_keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by.push(self.parse_expr()?);
+            while self.consume(&TokenKind::Comma) {
+                group_by.push(self.parse_expr()?);
+            }
+        }
+        let having = if self.consume_keyword("HAVING") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        Ok(Select {
+            distinct,
+            projection,
+            from,
+            selection,
+            group_by,
+            having,
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        })
+    }
+
+    // [ORDER BY ...] [LIMIT n] [OFFSET m]
+    fn parse_order_limit(&mut self) -> Result<OrderLimit, DbError> {
+        let mut order_by = Vec::new();
+        if self.consume_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                order_by.push(self.parse_order_by_expr()?);
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let limit = if self.consume_keyword("LIMIT") {
+            Some(self.parse_count()?)
+        } else {
+            None
+        };
+        let offset = if self.consume_keyword("OFFSET") {
+            Some(self.parse_count()?)
+        } else {
+            None
+        };
+        Ok((order_by, limit, offset))
+    }
+
+    // expr [ASC|DESC] [NULLS FIRST|LAST]
+    fn parse_order_by_expr(&mut self) -> Result<OrderByExpr, DbError> {
+        let expr = self.parse_expr()?;
+        let descending = if self.consume_keyword("DESC") {
+            true
+        } else {
+            self.consume_keyword("ASC");
+            false
+        };
+        let nulls_first = if self.consume_keyword("NULLS") {
+            if self.consume_keyword("FIRST") {
+                Some(true)
+            } else {
+                self.expect_keyword("LAST")?;
+                Some(false)
+            }
+        } else {
+            None
+        };
+        Ok(OrderByExpr {
+            expr,
+            descending,
+            nulls_first,
+        })
+    }
+
+    // LIMIT / OFFSET 之后的非负整数
+    fn parse_count(&mut self) -> Result<usize, DbError> {
+        let token = self.peek().clone();
+        match &token.kind {
+            TokenKind::Number(n) => match n.parse() {
+                Ok(count) => {
+                    self.advance();
+                    Ok(count)
+                }
+                Err(_) => Err(self.error_at(&token, format!("'{}' 不是有效的非负整数", n))),
+            },
+            _ => Err(self.unexpected("非负整数")),
+        }
+    }
+
+    // *、table.* 或 expr [[AS] alias]
+    fn parse_select_item(&mut self) -> Result<SelectItem, DbError> {
+        if self.consume(&TokenKind::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+        if *self.peek_nth(1) == TokenKind::Dot && *self.peek_nth(2) == TokenKind::Star {
+            let table = self.parse_identifier()?;
+            self.advance();
+            self.advance();
+            return Ok(SelectItem::QualifiedWildcard(table));
+        }
+        let expr = self.parse_expr()?;
+        let alias = self.parse_alias()?;
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    // [[AS] alias]
+    fn parse_alias(&mut self) -> Result<Option<String>, DbError> {
+        if self.consume_keyword("AS") {
+            Ok(Some(self.parse_identifier()?))
+        } else {
+            Ok(self.parse_identifier().ok())
+        }
+    }
+
+    // table_factor { , table_factor | [INNER|LEFT|RIGHT|FULL [OUTER]|CROSS] JOIN table_factor [ON expr | USING (cols)] }
+    fn parse_table_ref(&mut self) -> Result<TableRef, DbError> {
+        let mut left = self.parse_table_factor()?;
+        loop {
+            let kind = if self.consume(&TokenKind::Comma) {
+                JoinKind::Cross
+            } else if self.consume_keyword("CROSS") {
+                self.expect_keyword("JOIN")?;
+                JoinKind::Cross
+            } else if self.consume_keyword("JOIN") {
+                JoinKind::Inner
+            } else if self.consume_keyword("INNER") {
+                self.expect_keyword("JOIN")?;
+                JoinKind::Inner
+            } else if self.peek_keyword("LEFT") || self.peek_keyword("RIGHT") || self.peek_keyword("FULL") {
+                let kind = if self.consume_keyword("LEFT") {
+                    JoinKind::Left
+                } else if self.consume_keyword("RIGHT") {
+                    JoinKind::Right
+                } else {
+                    self.advance();
+                    JoinKind::Full
+                };
+                self.consume_keyword("OUTER");
+                self.expect_keyword("JOIN")?;
+                kind
+            } else {
+                return Ok(left);
+            };
+
+            let right = self.parse_table_factor()?;
+            let constraint = if kind == JoinKind::Cross {
+                JoinConstraint::None
+            } else if self.consume_keyword("ON") {
+                JoinConstraint::On(self.parse_expr()?)
+            } else if self.consume_keyword("USING") {
+                self.expect(&TokenKind::LParen)?;
+                let columns = self.parse_identifier_list()?;
+                self.expect(&TokenKind::RParen)?;
+                JoinConstraint::Using(columns)
+            } else {
+                return Err(self.unexpected("ON 或 USING"));
+            };
+            left = TableRef::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                kind,
+                constraint,
+            };
+        }
+    }
+
+    // table [[AS] alias] 或 (query) [AS] alias
+    fn parse_table_factor(&mut self) -> Result<TableRef, DbError> {
+        if self.peek_subquery() {
+            let query = Box::new(self.parse_subquery()?);
+            return match self.parse_alias()? {
+                Some(alias) => Ok(TableRef::Subquery { query, alias }),
+                None => Err(self.unexpected("派生表的别名")),
+            };
+        }
+        let name = self.parse_identifier()?;
+        let alias = self.parse_alias()?;
+        Ok(TableRef::Table { name, alias })
+    }
+
+    fn parse_insert(&mut self) -> Result<Insert, DbError> {
+        self.expect_keyword("INSERT")?;
+        self.expect_keyword("INTO")?;
+        let table = self.parse_identifier()?;
+
+        let columns = if self.consume(&TokenKind::LParen) {
+            let columns = self.parse_identifier_list()?;
+            self.expect(&TokenKind::RParen)?;
+            Some(columns)
+        } else {
+            None
+        };
+
+        self.expect_keyword("VALUES")?;
+        let mut rows = Vec::new();
+        loop {
+            self.expect(&TokenKind::LParen)?;
+            let mut values = vec![self.parse_expr()?];
+            while self.consume(&TokenKind::Comma) {
+                values.push(self.parse_expr()?);
+            }
+            self.expect(&TokenKind::RParen)?;
+            rows.push(values);
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        Ok(Insert { table, columns, rows })
+    }
+
+    fn parse_update(&mut self) -> Result<Update, DbError> {
+        self.expect_keyword("UPDATE")?;
+        let table = self.parse_identifier()?;
+        self.expect_keyword("SET")?;
+
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.parse_identifier()?;
+            self.expect(&TokenKind::Eq)?;
+            assignments.push((column, self.parse_expr()?));
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        let selection = self.parse_where()?;
+        Ok(Update { table, assignments, selection })
+    }
+
+    fn parse_delete(&mut self) -> Result<Delete, DbError> {
+        self.expect_keyword("DELETE")?;
+        self.expect_keyword("FROM")?;
+        let table = self.parse_identifier()?;
+        let selection = self.parse_where()?;
+        Ok(Delete { table, selection })
+    }
+
+    fn parse_create_table(&mut self) -> Result<CreateTable, DbError> {
+        self.expect_keyword("CREATE")?;
+        self.expect_keyword("TABLE")?;
+        let if_not_exists = self.parse_if_exists(true)?;
+        let name = self.parse_identifier()?;
+        self.expect(&TokenKind::LParen)?;
+        let mut columns = Vec::new();
+        let mut primary_key = Vec::new();
+        let mut foreign_keys = Vec::new();
+        loop {
+            let token = self.peek().clone();
+            if self.consume_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                if !primary_key.is_empty() {
+                    return Err(self.error_at(&token, "表级主键约束只能出现一次".to_string()));
+                }
+                self.expect(&TokenKind::LParen)?;
+                primary_key = self.parse_identifier_list()?;
+                self.expect(&TokenKind::RParen)?;
+            } else if self.consume_keyword("FOREIGN") {
+                self.expect_keyword("KEY")?;
+                self.expect(&TokenKind::LParen)?;
+                let columns = self.parse_identifier_list()?;
+                self.expect(&TokenKind::RParen)?;
+                self.expect_keyword("REFERENCES")?;
+                foreign_keys.push(self.parse_references(columns)?);
+            } else {
+                columns.push(self.parse_column_def()?);
+            }
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RParen)?;
+        Ok(CreateTable {
+            name,
+            if_not_exists,
+            columns,
+            primary_key,
+            foreign_keys,
+        })
+    }
+
+    fn parse_drop_table(&mut self) -> Result<DropTable, DbError> {
+        self.expect_keyword("DROP")?;
+        self.expect_keyword("TABLE")?;
+        let if_exists = self.parse_if_exists(false)?;
+        let name = self.parse_identifier()?;
+        Ok(DropTable { name, if_exists })
+    }
+
+    fn parse_alter_table(&mut self) -> Result<AlterTable, DbError> {
+        self.expect_keyword("ALTER")?;
+        self.expect_keyword("TABLE")?;
+        let name = self.parse_identifier()?;
+        let action = if self.consume_keyword("ADD") {
+            self.consume_keyword("COLUMN");
+            AlterAction::AddColumn(self.parse_column_def()?)
+        } else if self.consume_keyword("DROP") {
+            self.consume_keyword("COLUMN");
+            AlterAction::DropColumn(self.parse_identifier()?)
+        } else if self.consume_keyword("RENAME") {
+            if self.consume_keyword("TO") {
+                AlterAction::RenameTable(self.parse_identifier()?)
+            } else {
+                self.consume_keyword("COLUMN");
+                let name = self.parse_identifier()?;
+                self.expect_keyword("TO")?;
+                let new_name = self.parse_identifier()?;
+                AlterAction::RenameColumn { name, new_name }
+            }
+        } else if self.consume_keyword("ALTER") {
+            self.consume_keyword("COLUMN");
+            let name = self.parse_identifier()?;
+            if self.consume_keyword("SET") {
+                self.expect_keyword("DATA")?;
+            }
+            self.expect_keyword("TYPE")?;
+            let data_type = self.parse_data_type()?;
+            AlterAction::AlterColumnType { name, data_type }
+        } else {
+            return Err(self.unexpected("ADD、DROP、RENAME 或 ALTER"));
+        };
+        Ok(AlterTable { name, action })
+    }
+
+    // [IF NOT EXISTS] 或 [IF EXISTS]
+    fn parse_if_exists(&mut self, not: bool) -> Result<bool, DbError> {
+        if !self.consume_keyword("IF") {
+            return Ok(false);
+        }
+        if not {
+            self.expect_keyword("NOT")?;
+        }
+        self.expect_keyword("EXISTS")?;
+        Ok(true)
+    }
+
+    // col type 后接任意顺序的列约束
+    fn parse_column_def(&mut self) -> Result<ColumnDef, DbError> {
+        let name = self.parse_identifier()?;
+        let data_type = self.parse_data_type()?;
+        let mut def = ColumnDef {
+            name,
+            data_type,
+            primary_key: false,
+            not_null: false,
+            unique: false,
+            default: None,
+            check: None,
+            references: None,
+        };
+        loop {
+            if self.consume_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                def.primary_key = true;
+            } else if self.consume_keyword("NOT") {
+                self.expect_keyword("NULL")?;
+                def.not_null = true;
+            } else if self.consume_keyword("NULL") {
+                def.not_null = false;
+            } else if self.consume_keyword("UNIQUE") {
+                def.unique = true;
+            } else if self.consume_keyword("DEFAULT") {
+                // 默认值不含比较和逻辑运算，以免与后面的 NOT NULL 等约束混淆
+                def.default = Some(self.parse_operand()?);
+            } else if self.consume_keyword("CHECK") {
+                self.expect(&TokenKind::LParen)?;
+                def.check = Some(self.parse_expr()?);
+                self.expect(&TokenKind::RParen)?;
+            } else if self.consume_keyword("REFERENCES") {
+                def.references = Some(self.parse_references(vec![def.name.clone()])?);
+            } else {
+                return Ok(def);
+            }
+        }
+    }
+
+    // REFERENCES 之后的部分：table [(col, ...)] [ON DELETE action] [ON UPDATE action]
+    fn parse_references(&mut self, columns: Vec<String>) -> Result<ForeignKey, DbError> {
+        let ref_table = self.parse_identifier()?;
+        let ref_columns = if self.consume(&TokenKind::LParen) {
+            let ref_columns = self.parse_identifier_list()?;
+            self.expect(&TokenKind::RParen)?;
+            ref_columns
+        } else {
+            Vec::new()
+        };
+        let (mut on_delete, mut on_update) = (None, None);
+        while self.consume_keyword("ON") {
+            let token = self.peek().clone();
+            let (event, slot) = if self.consume_keyword("DELETE") {
+                ("DELETE", &mut on_delete)
+            } else if self.consume_keyword("UPDATE") {
+                ("UPDATE", &mut on_update)
+            } else {
+                return Err(self.unexpected("DELETE 或 UPDATE"));
+            };
+            if slot.is_some() {
+                return Err(self.error_at(&token, format!("ON {} 只能出现一次", event)));
+            }
+            *slot = Some(self.parse_referential_action()?);
+        }
+        Ok(ForeignKey {
+            columns,
+            ref_table,
+            ref_columns,
+            on_delete: on_delete.unwrap_or_default(),
+            on_update: on_update.unwrap_or_default(),
+        })
+    }
+
+    // RESTRICT | NO ACTION | CASCADE | SET NULL | SET DEFAULT
+    fn parse_referential_action(&mut self) -> Result<ReferentialAction, DbError> {
+        if self.consume_keyword("RESTRICT") {
+            Ok(ReferentialAction::Restrict)
+        } else if self.consume_keyword("NO") {
+            self.expect_keyword("ACTION")?;
+            Ok(ReferentialAction::Restrict)
+        } else if self.consume_keyword("CASCADE") {
+            Ok(ReferentialAction::Cascade)
+        } else if self.consume_keyword("SET") {
+            if self.consume_keyword("NULL") {
+                Ok(ReferentialAction::SetNull)
+            } else {
+                self.expect_keyword("DEFAULT")?;
+                Ok(ReferentialAction::SetDefault)
+            }
+        } else {
+            Err(self.unexpected("RESTRICT、NO ACTION、CASCADE、SET NULL 或 SET DEFAULT"))
+        }
+    }
+
+    // INT、BOOL、CHAR[(n)]、VARCHAR(n)，类型名大小写不敏感
+    fn parse_data_type(&mut self) -> Result<DataType, DbError> {
+        let token = self.peek().clone();
+        let name = match &token.kind {
+            TokenKind::Ident(s) => s.to_uppercase(),
+            _ => return Err(self.unexpected("数据类型")),
+        };
+        self.advance();
+        if name == "DOUBLE" {
+            self.consume_keyword("PRECISION");
+        }
+        // TIMESTAMP [WITH | WITHOUT TIME ZONE]、TIME [WITHOUT TIME ZONE]
+        let mut with_time_zone = false;
+        if matches!(name.as_str(), "TIMESTAMP" | "TIME") && (self.peek_keyword("WITH") || self.peek_keyword("WITHOUT")) {
+            with_time_zone = self.consume_keyword("WITH");
+            self.consume_keyword("WITHOUT");
+            self.expect_keyword("TIME")?;
+            self.expect_keyword("ZONE")?;
+            if with_time_zone && name == "TIME" {
+                return Err(self.error_at(&token, "不支持 TIME WITH TIME ZONE 类型".to_string()));
+            }
+        }
+        // 括号中的参数：CHAR/VARCHAR 的长度，或 DECIMAL 的总位数和小数位数
+        let mut params = Vec::new();
+        if self.consume(&TokenKind::LParen) {
+            loop {
+                let param_token = self.peek().clone();
+                let param = self.parse_count()?;
+                match u32::try_from(param) {
+                    Ok(param) => params.push((param, param_token)),
+                    _ => return Err(self.error_at(&param_token, format!("无效的参数 {}", param))),
+                }
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen)?;
+        }
+        if matches!(name.as_str(), "DECIMAL" | "NUMERIC") {
+            return self.decimal_type(&token, &params);
+        }
+        let length = match params.as_slice() {
+            [] => None,
+            [(0, length_token)] => return Err(self.error_at(length_token, "无效的长度 0".to_string())),
+            [(length, _)] => Some(*length),
+            _ => return Err(self.error_at(&token, format!("{} 类型只能指定一个长度", name))),
+        };
+        match (name.as_str(), length) {
+            ("INT" | "INTEGER", None) => Ok(DataType::Int),
+            ("BOOL" | "BOOLEAN", None) => Ok(DataType::Bool),
+            ("CHAR" | "CHARACTER", length) => Ok(DataType::Char(length.unwrap_or(1))),
+            ("VARCHAR" | "STRING", Some(length)) => Ok(DataType::String(length)),
+            ("VARCHAR" | "STRING", None) => Err(self.error_at(&token, format!("{} 类型需要指定长度", name))),
+            ("FLOAT" | "REAL", None) => Ok(DataType::Float),
+            ("DOUBLE", None) => Ok(DataType::Double),
+            ("DATE", None) => Ok(DataType::Date),
+            ("TIME", None) => Ok(DataType::Time),
+            ("TIMESTAMP", None) if with_time_zone => Ok(DataType::TimestampTz),
+            ("TIMESTAMP" | "DATETIME", None) => Ok(DataType::Timestamp),
+            ("TIMESTAMPTZ", None) => Ok(DataType::TimestampTz),
+            ("INTERVAL", None) => Ok(DataType::Interval),
+            ("JSON" | "JSONB", None) => Ok(DataType::Json),
+            ("BLOB" | "BYTEA", length) => Ok(DataType::Blob(length.unwrap_or(DEFAULT_MAX_SIZE))),
+            (
+                "INT" | "INTEGER" | "BOOL" | "BOOLEAN" | "FLOAT" | "REAL" | "DOUBLE" | "DATE" | "TIME" | "TIMESTAMP"
+                | "DATETIME" | "TIMESTAMPTZ" | "INTERVAL" | "JSON" | "JSONB",
+                Some(_),
+            ) => {
+                Err(self.error_at(&token, format!("{} 类型不能指定长度", name)))
+            }
+            _ => Err(self.error_at(&token, format!("未知的数据类型 {}", name))),
+        }
+    }
+
+    // DECIMAL[(precision[, scale])]，默认为 DECIMAL(10,0)
+    fn decimal_type(&self, token: &Token, params: &[(u32, Token)]) -> Result<DataType, DbError> {
+        let (precision, scale) = match params {
+            [] => (10, 0),
+            [(precision, _)] => (*precision, 0),
+            [(precision, _), (scale, _)] => (*precision, *scale),
+            _ => return Err(self.error_at(token, "DECIMAL 最多有两个参数".to_string())),
+        };
+        if precision == 0 || precision > MAX_PRECISION {
+            let message = format!("DECIMAL 的总位数应在 1 到 {} 之间，实际为 {}", MAX_PRECISION, precision);
+            return Err(self.error_at(&params[0].1, message));
+        }
+        if scale > precision {
+            let message = format!("DECIMAL 的小数位数 {} 不能大于总位数 {}", scale, precision);
+            return Err(self.error_at(&params[1].1, message));
+        }
+        Ok(DataType::Decimal(precision, scale))
+    }
+
+    fn parse_where(&mut self) -> Result<Option<Expr>, DbError> {
+        if self.consume_keyword("WHERE") {
+            Ok(Some(self.parse_expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // 表达式按优先级从低到高：OR、AND、NOT、比较/谓词、基本项
+    fn parse_expr(&mut self) -> Result<Expr, DbError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, DbError> {
+        let mut left = self.parse_and()?;
+        while self.consume_keyword("OR") {
+            let right = self.parse_and()?;
+            left = binary(left, BinaryOp::Or, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, DbError> {
+        let mut left = self.parse_not()?;
+        while self.consume_keyword("AND") {
+            let right = self.parse_not()?;
+            left = binary(left, BinaryOp::And, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, DbError> {
+        if self.consume_keyword("NOT") {
+            let expr = self.parse_not()?;
+            return Ok(Expr::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.parse_comparison()
+    }
+
+    // 比较运算与 IS NULL、IN、BETWEEN、LIKE 谓词
+    fn parse_comparison(&mut self) -> Result<Expr, DbError> {
+        let left = self.parse_operand()?;
+
+        let op = match self.peek().kind {
+            TokenKind::Eq => Some(BinaryOp::Eq),
+            TokenKind::NotEq => Some(BinaryOp::NotEq),
+            TokenKind::Lt => Some(BinaryOp::Lt),
+            TokenKind::LtEq => Some(BinaryOp::LtEq),
+            TokenKind::Gt => Some(BinaryOp::Gt),
+            TokenKind::GtEq => Some(BinaryOp::GtEq),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.advance();
+            let right = self.parse_operand()?;
+            return Ok(binary(left, op, right));
+        }
+
+        if self.consume_keyword("IS") {
+            let negated = self.consume_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+
+        let negated = self.consume_keyword("NOT");
+        if self.consume_keyword("IN") {
+            if self.peek_subquery() {
+                return Ok(Expr::InSubquery {
+                    expr: Box::new(left),
+                    query: Box::new(self.parse_subquery()?),
+                    negated,
+                });
+            }
+            self.expect(&TokenKind::LParen)?;
+            let mut list = vec![self.parse_expr()?];
+            while self.consume(&TokenKind::Comma) {
+                list.push(self.parse_expr()?);
+            }
+            self.expect(&TokenKind::RParen)?;
+            return Ok(Expr::InList {
+                expr: Box::new(left),
+                list,
+                negated,
+            });
+        }
+        if self.consume_keyword("BETWEEN") {
+            let low = self.parse_operand()?;
+            self.expect_keyword("AND")?;
+            let high = self.parse_operand()?;
+            return Ok(Expr::Between {
+                expr: Box::new(left),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated,
+            });
+        }
+        let like = self.peek_keyword("LIKE");
+        if like || self.peek_keyword("ILIKE") {
+            self.advance();
+            let pattern = self.parse_operand()?;
+            let escape = if self.consume_keyword("ESCAPE") {
+                Some(Box::new(self.parse_operand()?))
+            } else {
+                None
+            };
+            return Ok(Expr::Like {
+                expr: Box::new(left),
+                pattern: Box::new(pattern),
+                escape,
+                negated,
+                case_insensitive: !like,
+            });
+        }
+        if negated {
+            return Err(self.unexpected("IN、BETWEEN、LIKE 或 ILIKE"));
+        }
+        Ok(left)
+    }
+
+    // 比较运算符两侧的操作数：加减与字符串拼接
+    fn parse_operand(&mut self) -> Result<Expr, DbError> {
+        let mut left = self.parse_term()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Plus,
+                TokenKind::Minus => BinaryOp::Minus,
+                TokenKind::Concat => BinaryOp::Concat,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_term()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    // 乘、除、取模
+    fn parse_term(&mut self) -> Result<Expr, DbError> {
+        let mut left = self.parse_json_access()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Multiply,
+                TokenKind::Slash => BinaryOp::Divide,
+                TokenKind::Percent => BinaryOp::Modulo,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_json_access()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    // JSON 的 -> 和 ->>，优先于乘除，如 data->'items'->>0
+    fn parse_json_access(&mut self) -> Result<Expr, DbError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Arrow => BinaryOp::JsonGet,
+                TokenKind::LongArrow => BinaryOp::JsonGetText,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_unary()?;
+            left = binary(left, op, right);
+        }
+    }
+
+    // 负号；紧跟数字时直接作为负数字面量
+    fn parse_unary(&mut self) -> Result<Expr, DbError> {
+        if self.consume(&TokenKind::Minus) {
+            if let TokenKind::Number(n) = &self.peek().kind {
+                let n = format!("-{}", n);
+                self.advance();
+                return number_literal(n);
+            }
+            let expr = self.parse_unary()?;
+            return Ok(Expr::Unary {
+                op: UnaryOp::Minus,
+                expr: Box::new(expr),
+            });
+        }
+        self.consume(&TokenKind::Plus);
+        self.parse_primary()
+    }
+
+    // 时间间隔的单位，复数形式如 DAYS 按单数保存
+    fn parse_interval_unit(&mut self) -> Result<Expr, DbError> {
+        let TokenKind::Ident(unit) = self.advance().kind else {
+            unreachable!("调用前已检查是时间单位");
+        };
+        let unit = unit.to_uppercase();
+        let singular = unit.strip_suffix('S').unwrap_or(&unit);
+        Ok(Expr::Literal(Literal::String(singular.to_string())))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, DbError> {
+        match self.peek().kind.clone() {
+            TokenKind::Number(n) => {
+                self.advance();
+                number_literal(n)
+            }
+            TokenKind::String(s) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::String(s)))
+            }
+            TokenKind::Blob(bytes) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Blob(bytes)))
+            }
+            TokenKind::LParen if self.peek_subquery() => Ok(Expr::Subquery(Box::new(self.parse_subquery()?))),
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(&TokenKind::RParen)?;
+                Ok(expr)
+            }
+            TokenKind::Ident(s) if s.eq_ignore_ascii_case("EXISTS") => {
+                self.advance();
+                Ok(Expr::Exists(Box::new(self.parse_subquery()?)))
+            }
+            TokenKind::Ident(s) if s.eq_ignore_ascii_case("NULL") => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Null))
+            }
+            TokenKind::Ident(s) if s.eq_ignore_ascii_case("TRUE") => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Bool(true)))
+            }
+            TokenKind::Ident(s) if s.eq_ignore_ascii_case("FALSE") => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Bool(false)))
+            }
+            // INTERVAL '1 day'、INTERVAL 3 DAY、INTERVAL (n + 1) HOUR，没有单位时按文本转换
+            TokenKind::Ident(s)
+                if s.eq_ignore_ascii_case("INTERVAL")
+                    && (matches!(
+                        self.peek_nth(1),
+                        TokenKind::Number(_) | TokenKind::String(_) | TokenKind::LParen | TokenKind::Minus
+                    ) || matches!(self.peek_nth(1), TokenKind::Ident(_)) && is_interval_unit(self.peek_nth(2))) =>
+            {
+                self.advance();
+                let mut args = vec![self.parse_unary()?];
+                if is_interval_unit(&self.peek().kind) {
+                    args.push(self.parse_interval_unit()?);
+                }
+                Ok(function("INTERVAL", args))
+            }
+            // 带类型的字面量 DATE '2024-01-31'，解析为同名的转换函数
+            TokenKind::Ident(s)
+                if TYPED_LITERALS.iter().any(|k| s.eq_ignore_ascii_case(k))
+                    && matches!(self.peek_nth(1), TokenKind::String(_)) =>
+            {
+                self.advance();
+                let literal = self.parse_primary()?;
+                Ok(function(&s.to_uppercase(), vec![literal]))
+            }
+            // EXTRACT(field FROM source)
+            TokenKind::Ident(s)
+                if s.eq_ignore_ascii_case("EXTRACT")
+                    && matches!(self.peek_nth(1), TokenKind::LParen)
+                    && matches!(self.peek_nth(3), TokenKind::Ident(k) if k.eq_ignore_ascii_case("FROM")) =>
+            {
+                self.advance();
+                self.expect(&TokenKind::LParen)?;
+                let field = self.parse_identifier()?.to_uppercase();
+                self.expect_keyword("FROM")?;
+                let source = self.parse_expr()?;
+                self.expect(&TokenKind::RParen)?;
+                Ok(function("EXTRACT", vec![Expr::Literal(Literal::String(field)), source]))
+            }
+            // 不带括号的 CURRENT_DATE、CURRENT_TIME、CURRENT_TIMESTAMP
+            TokenKind::Ident(s)
+                if CURRENT_FUNCTIONS.iter().any(|k| s.eq_ignore_ascii_case(k))
+                    && !matches!(self.peek_nth(1), TokenKind::LParen) =>
+            {
+                self.advance();
+                Ok(function(&s.to_uppercase(), Vec::new()))
+            }
+            _ => {
+                let name = self.parse_identifier().map_err(|_| self.unexpected("表达式"))?;
+                if self.consume(&TokenKind::LParen) {
+                    let name = name.to_uppercase();
+                    let distinct = self.consume_keyword("DISTINCT");
+                    let mut args = Vec::new();
+                    if name == "COUNT" && !distinct && self.consume(&TokenKind::Star) {
+                        self.expect(&TokenKind::RParen)?;
+                    } else if !self.consume(&TokenKind::RParen) {
+                        loop {
+                            args.push(self.parse_expr()?);
+                            if !self.consume(&TokenKind::Comma) {
+                                break;
+                            }
+                        }
+                        self.expect(&TokenKind::RParen)?;
+                    }
+                    return Ok(Expr::Function { name, args, distinct });
+                }
+                if self.consume(&TokenKind::Dot) {
+                    let column = self.parse_identifier()?;
+                    return Ok(Expr::QualifiedColumn { table: name, name: column });
+                }
+                Ok(Expr::Column(name))
+            }
+        }
+    }
+}
+
+fn is_interval_unit(kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::Ident(s) => {
+            let unit = s.to_uppercase();
+            INTERVAL_UNITS.contains(&unit.strip_suffix('S').unwrap_or(&unit))
+        }
+        _ => false,
+    }
+}
+
+fn function(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Function {
+        name: name.to_string(),
+        args,
+        distinct: false,
+    }
+}
+
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+fn set_operation(left: Query, op: SetOperator, all: bool, right: Query) -> Query {
+    Query::SetOperation(Box::new(SetOperation {
+        left,
+        op,
+        all,
+        right,
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+    }))
+}
+
+// 数值字面量；超出双精度范围的浮点数（如 1e400）与写入列时一样报错，而不是得到无穷大
+fn number_literal(n: String) -> Result<Expr, DbError> {
+    if let Some(Value::Float(x)) = parse_number(&n) {
+        if !x.is_finite() {
+            return Err(DbError::TypeMismatch(format!("'{}' 超出了 {} 的范围", n, DataType::Double)));
+        }
+    }
+    Ok(Expr::Literal(Literal::Number(n)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(sql: &str) -> (String, usize, usize) {
+        match parse(sql) {
+            Err(DbError::Syntax { message, line, column }) => (message, line, column),
+            other => panic!("{:?} 应报语法错误，实际为 {:?}", sql, other),
+        }
+    }
+
+    fn select(sql: &str) -> Select {
+        match parse(sql).unwrap() {
+            Statement::Query(Query::Select(select)) => *select,
+            other => panic!("{:?} 应为 SELECT，实际为 {:?}", sql, other),
+        }
+    }
+
+    // 表达式解析后再显示，括号只在需要时保留
+    fn round_trip(sql: &str) -> String {
+        parse_expression(sql).unwrap().to_string()
+    }
+
+    #[test]
+    fn errors_report_line_and_column_of_the_unexpected_token() {
+        assert_eq!(error("SELECT * FROM"), ("期望 标识符，但遇到 语句结尾".to_string(), 1, 14));
+        assert_eq!(error("SELECT id,\n  FROM t"), ("期望 表达式，但遇到 FROM".to_string(), 2, 3));
+        assert_eq!(error("INSERT INTO t VALUES (1,\n 2"), ("期望 )，但遇到 语句结尾".to_string(), 2, 3));
+        assert_eq!(error("SELECT * FROM t;;").2, 17);
+        // 词法错误同样带位置
+        assert_eq!(error("SELECT *\nFROM t WHERE a = 'x").1, 2);
+    }
+
+    #[test]
+    fn reserved_words_need_quotes_to_be_names() {
+        assert_eq!(error("SELECT * FROM select"), ("期望 标识符，但遇到 select".to_string(), 1, 15));
+        let quoted = select("SELECT \"from\" FROM \"select\"");
+        assert_eq!(quoted.from, TableRef::Table { name: "select".to_string(), alias: None });
+        assert_eq!(quoted.projection, [SelectItem::Expr { expr: Expr::Column("from".to_string()), alias: None }]);
+    }
+
+    #[test]
+    fn precedence_and_display() {
+        assert_eq!(round_trip("1 + 2 * 3"), "1 + 2 * 3");
+        assert_eq!(round_trip("(1 + 2) * 3"), "(1 + 2) * 3");
+        assert_eq!(round_trip("1 - (2 - 3)"), "1 - (2 - 3)");
+        assert_eq!(round_trip("((a))"), "a");
+        assert_eq!(round_trip("-2 - -3"), "-2 - -3");
+        assert_eq!(round_trip("'it''s'"), "'it''s'");
+        let Expr::Binary { op: BinaryOp::Or, right, .. } = parse_expression("a = 1 OR NOT b AND c").unwrap() else {
+            panic!("OR 的优先级最低");
+        };
+        assert!(matches!(*right, Expr::Binary { op: BinaryOp::And, .. }));
+    }
+
+    #[test]
+    fn statements() {
+        let query = select("SELECT DISTINCT a AS x, t.* FROM t WHERE a > 1 GROUP BY a HAVING COUNT(*) > 1 ORDER BY x DESC LIMIT 5 OFFSET 2;");
+        assert!(query.distinct);
+        assert_eq!(query.projection[1], SelectItem::QualifiedWildcard("t".to_string()));
+        assert_eq!((query.group_by.len(), query.order_by[0].descending), (1, true));
+        assert_eq!((query.limit, query.offset), (Some(5), Some(2)));
+
+        let Statement::Insert(insert) = parse("INSERT INTO t (a, b) VALUES (1, 'x'), (2, NULL)").unwrap() else {
+            panic!("应为 INSERT");
+        };
+        assert_eq!(insert.columns, Some(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(insert.rows[1], [Expr::Literal(Literal::Number("2".to_string())), Expr::Literal(Literal::Null)]);
+
+        let Statement::CreateTable(create) = parse("CREATE TABLE t (a INT, b VARCHAR(5) NOT NULL UNIQUE, PRIMARY KEY (a))").unwrap() else {
+            panic!("应为 CREATE TABLE");
+        };
+        assert_eq!(create.primary_key, ["a"]);
+        assert!(create.columns[1].not_null && create.columns[1].unique);
+    }
+}
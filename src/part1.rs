@@ -1,12 +1,64 @@
 use serde::{Serialize, Deserialize};
+use crate::blob::decode_base64;
+use crate::datetime::{with_statement_time, Timestamp};
+use crate::constraints::multiple_primary_keys;
+use crate::value::{fit_length, Value};
+use std::fmt;
 
 // 定义数据类型枚举
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     Int,
     Char(u32), // 字符长度
     Bool,
     String(u32), // 字符串长度
+    Float,       // 单精度浮点数
+    Double,      // 双精度浮点数
+    Decimal(u32, u32), // 精确小数：总位数、小数位数
+    Date,
+    Time,
+    Timestamp,   // 不带时区的日期时间
+    TimestampTz, // 带时区的日期时间，按 UTC 保存
+    Interval,    // 时间间隔
+    Json,        // 写入时校验的 JSON 文档
+    Blob(u32),   // 二进制数据，参数为最大字节数
+}
+
+impl DataType {
+    // 值在数据文件中以字符串保存、读入时按类型解析的类型
+    fn parsed_from_string(&self) -> bool {
+        matches!(
+            self,
+            DataType::Decimal(..)
+                | DataType::Date
+                | DataType::Time
+                | DataType::Timestamp
+                | DataType::TimestampTz
+                | DataType::Interval
+        )
+    }
+}
+
+// 以 SQL 类型名显示，如 VARCHAR(20)
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Int => write!(f, "INT"),
+            DataType::Char(len) => write!(f, "CHAR({})", len),
+            DataType::Bool => write!(f, "BOOL"),
+            DataType::String(len) => write!(f, "VARCHAR({})", len),
+            DataType::Float => write!(f, "FLOAT"),
+            DataType::Double => write!(f, "DOUBLE"),
+            DataType::Decimal(precision, scale) => write!(f, "DECIMAL({},{})", precision, scale),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Time => write!(f, "TIME"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::TimestampTz => write!(f, "TIMESTAMP WITH TIME ZONE"),
+            DataType::Interval => write!(f, "INTERVAL"),
+            DataType::Json => write!(f, "JSON"),
+            DataType::Blob(max_size) => write!(f, "BLOB({})", max_size),
+        }
+    }
 }
 
 // 定义列结构体
@@ -15,46 +67,250 @@ pub struct Column {
     pub name: String,       // 将字段设为公有
     pub data_type: DataType,    // 数据类型
     pub is_primary_key: bool,   // 是否为主键
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub not_null: bool,         // NOT NULL
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub unique: bool,           // UNIQUE
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>, // DEFAULT 表达式的 SQL 文本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,   // CHECK 条件的 SQL 文本
+}
+
+impl Column {
+    // 没有任何约束的列
+    pub fn new(name: &str, data_type: DataType) -> Self {
+        Column {
+            name: name.to_string(),
+            data_type,
+            is_primary_key: false,
+            not_null: false,
+            unique: false,
+            default: None,
+            check: None,
+        }
+    }
 }
 
 // 定义行结构体
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Row {
-    pub values: Vec<Option<String>>, // 存储每一列的值，使用 Option 处理可能的空值
+    pub values: Vec<Value>, // 存储每一列的值，写入时已转换为列的类型
 }
 
 // 定义表结构体
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredTable")]
 pub struct Table {
     pub name: String,            // 将字段设为公有
     pub columns: Vec<Column>,    // 将字段设为公有
     pub rows: Vec<Row>,          // 将字段设为公有
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub foreign_keys: Vec<ForeignKey>, // 本表引用其他表的外键
+}
+
+// 数据文件中的表。DECIMAL 和日期时间类型的值保存为字符串，读入后按列的类型还原；
+// JSON 列按原样保存，读入后直接作为 JSON 值；BLOB 保存为 base64 字符串
+#[derive(Deserialize)]
+struct StoredTable {
+    name: String,
+    columns: Vec<Column>,
+    rows: Vec<StoredRow>,
+    #[serde(default)]
+    foreign_keys: Vec<ForeignKey>,
+}
+
+// 数据文件中的行。各值先按 JSON 原样读入，再按列的类型还原，
+// JSON 列中的数字因此不经过 Value 的整数和浮点数，超出 i64 的整数（如 18446744073709551615）不会丢失精度
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredRow {
+    pub values: Vec<serde_json::Value>,
+}
+
+impl From<&Row> for StoredRow {
+    fn from(row: &Row) -> StoredRow {
+        let values = row.values.iter().map(|v| serde_json::to_value(v).expect("值可以表示为 JSON"));
+        StoredRow { values: values.collect() }
+    }
+}
+
+impl From<StoredTable> for Table {
+    fn from(stored: StoredTable) -> Table {
+        let mut table = Table {
+            name: stored.name,
+            columns: stored.columns,
+            rows: Vec::new(),
+            foreign_keys: stored.foreign_keys,
+        };
+        table.rows = stored.rows.into_iter().map(|row| table.decode_stored(row)).collect();
+        table
+    }
+}
+
+impl Table {
+    // 把旧格式的一行升级为当前格式，返回无法转换为列的类型、仍保留为文本的值的个数
+    pub fn migrate_row(&self, row: &mut Row) -> usize {
+        let mut unconverted = 0;
+        row.values.resize(self.columns.len(), Value::Null);
+        for (col, value) in self.columns.iter().zip(&mut row.values) {
+            match value.clone().convert(&col.data_type) {
+                // 旧数据中超长的文本原样保留，CHAR 补足长度
+                Ok(Value::Text(text)) => match fit_length(&text, &col.data_type) {
+                    (fitted, false) => *value = Value::Text(fitted),
+                    (_, true) => *value = Value::Text(text),
+                },
+                Ok(converted) => *value = converted,
+                Err(_) => unconverted += 1,
+            }
+        }
+        unconverted
+    }
+
+    // 把从文件中读入的行还原为列的类型，见 StoredRow
+    pub fn decode_stored(&self, row: StoredRow) -> Row {
+        let values = row.values.into_iter().enumerate().map(|(i, json)| {
+            let data_type = self.columns.get(i).map(|col| &col.data_type);
+            if let Some(DataType::Json) = data_type {
+                return match json {
+                    serde_json::Value::Null => Value::Null,
+                    json => Value::Json(json),
+                };
+            }
+            let value = Value::deserialize(&json).unwrap_or(Value::Json(json));
+            let decoded = match (data_type, &value) {
+                (Some(DataType::Blob(_)), Value::Text(text)) => decode_base64(text).map(Value::Blob),
+                (Some(data_type), Value::Text(_)) if data_type.parsed_from_string() => {
+                    value.clone().convert(data_type).ok()
+                }
+                _ => None,
+            };
+            decoded.unwrap_or(value)
+        });
+        Row { values: values.collect() }
+    }
+}
+
+// 外键：本表的 columns 引用 ref_table 的 ref_columns（主键或 UNIQUE 列）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub ref_table: String,
+    pub ref_columns: Vec<String>,
+    #[serde(default)]
+    pub on_delete: ReferentialAction,
+    #[serde(default)]
+    pub on_update: ReferentialAction,
+}
+
+// 被引用的行删除或键值修改时，对引用它的行采取的动作
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ReferentialAction {
+    #[default]
+    Restrict,
+    Cascade,
+    SetNull,
+    SetDefault,
+}
+
+impl fmt::Display for ReferentialAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferentialAction::Restrict => write!(f, "RESTRICT"),
+            ReferentialAction::Cascade => write!(f, "CASCADE"),
+            ReferentialAction::SetNull => write!(f, "SET NULL"),
+            ReferentialAction::SetDefault => write!(f, "SET DEFAULT"),
+        }
+    }
 }
 
 // 定义数据库结构体
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Database {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub format_version: u32,     // 数据文件的格式版本，旧文件没有此字段，视为 0
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub name: String,            // 数据库名称，旧文件没有此字段时为空
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub wal_sequence: u64,       // 已写入数据文件的最后一条日志记录的序号，见 wal 模块
     pub tables: Vec<Table>,      // 将此字段设为公有
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub length_policy: LengthPolicy, // 写入超过 CHAR(n)/VARCHAR(n) 长度的文本时的处理方式
+}
+
+// 后来新增的字段取默认值时不写出，旧文件重新序列化的结果与原来一致，完整性校验不受影响
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+// 当前的数据文件格式版本：0 中各值都以文本保存，1 起按列的类型保存
+pub const FORMAT_VERSION: u32 = 1;
+
+// 超长文本的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LengthPolicy {
+    #[default]
+    Reject,   // 拒绝写入
+    Truncate, // 截断并给出警告
 }
 
 // 创建数据库
 impl Database {
     pub fn new(name: &str) -> Self {
         Database {
+            format_version: FORMAT_VERSION,
+            name: name.to_string(),
+            wal_sequence: 0,
             tables: Vec::new(),
+            length_policy: LengthPolicy::default(),
         }
     }
 
-    // 创建表
-    pub fn create_table(&mut self, table: Table) {
+    // 创建表，表名（大小写不敏感）和同一表内的列名不能重复
+    pub fn create_table(&mut self, table: Table) -> Result<(), DbError> {
+        if self.table_position(&table.name).is_ok() {
+            return Err(DbError::TableExists(table.name));
+        }
+        for (i, col) in table.columns.iter().enumerate() {
+            if table.columns[..i].iter().any(|c| c.name.eq_ignore_ascii_case(&col.name)) {
+                return Err(DbError::InvalidQuery(format!("表 '{}' 中的列 '{}' 重复", table.name, col.name)));
+            }
+        }
         self.tables.push(table);
+        Ok(())
+    }
+
+    // 删除表，返回被删除的表；被其他表的外键引用时不能删除
+    pub fn drop_table(&mut self, name: &str) -> Result<Table, DbError> {
+        let i = self.table_position(name)?;
+        self.check_drop_table(name)?;
+        Ok(self.tables.remove(i))
     }
 
-    // 插入行
-    pub fn insert_row(&mut self, table_name: &str, row: Row) {
-        if let Some(table) = self.tables.iter_mut().find(|t| t.name == table_name) {
-            table.rows.push(row);
+    // 插入行，返回因超长被截断的值的个数
+    pub fn insert_row(&mut self, table_name: &str, mut row: Row) -> Result<usize, DbError> {
+        let table = self.table(table_name)?;
+        let truncated = table.conform_row(&mut row, self.length_policy)?;
+        table.check_constraints(&[&row], &[])?;
+        self.check_references(table, &[&row], &[])?;
+        self.table_mut(table_name)?.rows.push(row);
+        Ok(truncated)
+    }
+
+    // 把旧格式的数据升级为当前格式，返回无法转换为列的类型、仍保留为文本的值的个数
+    pub fn migrate(&mut self) -> usize {
+        if self.format_version >= FORMAT_VERSION {
+            return 0;
+        }
+        let mut unconverted = 0;
+        for table in &mut self.tables {
+            let mut rows = std::mem::take(&mut table.rows);
+            for row in &mut rows {
+                unconverted += table.migrate_row(row);
+            }
+            table.rows = rows;
         }
+        self.format_version = FORMAT_VERSION;
+        unconverted
     }
 
     // 读取行
@@ -62,21 +318,289 @@ impl Database {
         self.tables.iter().find(|t| t.name == table_name).map(|t| &t.rows)
     }
 
-    // 更新行
-    pub fn update_row(&mut self, table_name: &str, row_index: usize, new_row: Row) {
-        if let Some(table) = self.tables.iter_mut().find(|t| t.name == table_name) {
-            if row_index < table.rows.len() {
-                table.rows[row_index] = new_row;
+    // 更新行，返回因超长被截断的值的个数
+    pub fn update_row(&mut self, table_name: &str, row_index: usize, mut new_row: Row) -> Result<usize, DbError> {
+        let table = self.table(table_name)?;
+        if row_index >= table.rows.len() {
+            return Ok(0);
+        }
+        let truncated = table.conform_row(&mut new_row, self.length_policy)?;
+        table.check_constraints(&[&new_row], &[row_index])?;
+        self.update_rows(table_name, vec![(row_index, new_row)])?;
+        Ok(truncated)
+    }
+
+    // 删除行，引用它的行按外键的 ON DELETE 动作处理
+    pub fn delete_row(&mut self, table_name: &str, row_index: usize) -> Result<(), DbError> {
+        if row_index < self.table(table_name)?.rows.len() {
+            self.delete_rows(table_name, &[row_index])?;
+        }
+        Ok(())
+    }
+}
+
+// 查询结果中的列信息
+#[derive(Debug, Clone)]
+pub struct ResultColumn {
+    pub name: String,
+    pub data_type: Option<DataType>, // 计算得到的列没有声明类型
+}
+
+// 执行 SQL 语句后返回的结构化结果
+#[derive(Debug, Clone, Default)]
+pub struct QueryResult {
+    pub columns: Vec<ResultColumn>, // 仅查询语句有列
+    pub rows: Vec<Row>,
+    pub affected_rows: usize,       // INSERT/UPDATE/DELETE 影响的行数
+    pub message: String,            // 面向用户的执行摘要
+}
+
+impl QueryResult {
+    // 是否为返回结果集的查询
+    pub fn is_query(&self) -> bool {
+        !self.columns.is_empty()
+    }
+}
+
+// 执行 SQL 时可能出现的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbError {
+    Syntax { message: String, line: usize, column: usize },
+    TableNotFound(String),
+    TableExists(String),
+    ColumnNotFound(String),
+    ColumnCountMismatch { expected: usize, found: usize },
+    TypeMismatch(String),
+    Arithmetic(String),
+    InvalidQuery(String),
+    Unsupported(String),
+    Storage(String), // 读写数据文件失败
+    // 写入的数据违反约束，column 与 value 在复合主键时为以逗号分隔的多列
+    ConstraintViolation {
+        constraint: Constraint,
+        table: String,
+        column: String,
+        value: String,
+    },
+}
+
+// 被违反的约束
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint {
+    PrimaryKey,
+    NotNull,
+    Unique,
+    Check(String), // CHECK 条件的 SQL 文本
+    ForeignKey(String), // 写入的值在被引用的表中不存在，内容为被引用的表名
+    Referenced(String), // 被删除或修改的值仍被其他行引用，内容为引用方的表名
+    Length(DataType), // 文本超过列的声明长度
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Syntax { message, line, column } => {
+                write!(f, "语法错误 (第 {} 行第 {} 列): {}", line, column, message)
             }
+            DbError::TableNotFound(name) => write!(f, "表 '{}' 不存在", name),
+            DbError::TableExists(name) => write!(f, "表 '{}' 已存在", name),
+            DbError::ColumnNotFound(name) => write!(f, "列 '{}' 不存在", name),
+            DbError::ColumnCountMismatch { expected, found } => {
+                write!(f, "值的数量 ({}) 与列的数量 ({}) 不匹配", found, expected)
+            }
+            DbError::TypeMismatch(msg) => write!(f, "类型错误: {}", msg),
+            DbError::Arithmetic(msg) => write!(f, "运算错误: {}", msg),
+            DbError::InvalidQuery(msg) => write!(f, "无效的查询: {}", msg),
+            DbError::Unsupported(msg) => write!(f, "不支持的操作: {}", msg),
+            DbError::Storage(msg) => write!(f, "存储错误: {}", msg),
+            DbError::ConstraintViolation { constraint, table, column, value } => match constraint {
+                Constraint::PrimaryKey => {
+                    write!(f, "违反主键约束: 表 '{}' 的主键 ({}) 的值 ({}) 重复", table, column, value)
+                }
+                Constraint::NotNull => write!(f, "违反非空约束: 表 '{}' 的列 '{}' 不能为 NULL", table, column),
+                Constraint::Unique => write!(f, "违反唯一约束: 表 '{}' 的列 '{}' 的值 '{}' 重复", table, column, value),
+                Constraint::Check(check) => write!(
+                    f,
+                    "违反检查约束 CHECK ({}): 表 '{}' 的列 '{}' 的值为 '{}'",
+                    check, table, column, value
+                ),
+                Constraint::Length(data_type) => write!(
+                    f,
+                    "超出长度限制: 表 '{}' 的列 '{}' 的类型为 {}，值 '{}' 有 {} 个字符",
+                    table,
+                    column,
+                    data_type,
+                    value,
+                    value.chars().count()
+                ),
+                Constraint::ForeignKey(ref_table) => write!(
+                    f,
+                    "违反外键约束: 表 '{}' 的列 ({}) 的值 ({}) 在表 '{}' 中不存在",
+                    table, column, value, ref_table
+                ),
+                Constraint::Referenced(child) => write!(
+                    f,
+                    "违反外键约束: 表 '{}' 中仍有行引用表 '{}' 的 ({}) = ({})",
+                    child, table, column, value
+                ),
+            },
         }
     }
+}
+
+impl std::error::Error for DbError {}
+
+impl Database {
+    // 执行一条 SQL 语句
+    pub fn execute(&mut self, sql: &str) -> Result<QueryResult, DbError> {
+        self.execute_at(sql, Timestamp::now())
+    }
+
+    // 以 now 作为当前时间执行，语句中的 NOW()、CURRENT_DATE 等都取这一时间；用于重放日志
+    pub fn execute_at(&mut self, sql: &str, now: Timestamp) -> Result<QueryResult, DbError> {
+        let statement = crate::parser::parse(sql)?;
+        with_statement_time(now, || self.execute_statement(statement))
+    }
+
+    // 按名称查找表，未加引号的名称大小写不敏感，完全匹配优先
+    pub fn table(&self, name: &str) -> Result<&Table, DbError> {
+        self.table_position(name).map(|i| &self.tables[i])
+    }
+
+    pub fn table_mut(&mut self, name: &str) -> Result<&mut Table, DbError> {
+        self.table_position(name).map(move |i| &mut self.tables[i])
+    }
+
+    fn table_position(&self, name: &str) -> Result<usize, DbError> {
+        self.tables
+            .iter()
+            .position(|t| t.name == name)
+            .or_else(|| self.tables.iter().position(|t| t.name.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| DbError::TableNotFound(name.to_string()))
+    }
+}
 
-    // 删除行
-    pub fn delete_row(&mut self, table_name: &str, row_index: usize) {
-        if let Some(table) = self.tables.iter_mut().find(|t| t.name == table_name) {
-            if row_index < table.rows.len() {
-                table.rows.remove(row_index);
+impl Database {
+    // 重命名表，新名称不能与其他表重复，引用它的外键随之修改
+    pub fn rename_table(&mut self, name: &str, new_name: &str) -> Result<(), DbError> {
+        let i = self.table_position(name)?;
+        if self.table_position(new_name).is_ok_and(|j| j != i) {
+            return Err(DbError::TableExists(new_name.to_string()));
+        }
+        let old_name = std::mem::replace(&mut self.tables[i].name, new_name.to_string());
+        for fk in self.tables.iter_mut().flat_map(|t| t.foreign_keys.iter_mut()) {
+            if fk.ref_table.eq_ignore_ascii_case(&old_name) {
+                fk.ref_table = new_name.to_string();
             }
         }
+        Ok(())
+    }
+}
+
+impl Table {
+    // 添加列，已有的行以列的默认值填充
+    pub fn add_column(&mut self, column: Column) -> Result<(), DbError> {
+        self.check_new_column_name(&column.name, None)?;
+        if column.is_primary_key && !self.primary_key().is_empty() {
+            return Err(multiple_primary_keys(&self.name));
+        }
+        let default = column.default_value()?;
+        for row in &mut self.rows {
+            row.values.resize(self.columns.len(), Value::Null);
+            row.values.push(default.clone());
+        }
+        self.columns.push(column);
+        Ok(())
+    }
+
+    // 删除列及各行中对应的值，表中至少保留一列
+    pub fn drop_column(&mut self, name: &str) -> Result<Column, DbError> {
+        let idx = self.column_index(name)?;
+        if self.columns.len() == 1 {
+            return Err(DbError::InvalidQuery(format!("不能删除表 '{}' 的唯一一列", self.name)));
+        }
+        self.check_drop_check_references(idx)?;
+        for row in &mut self.rows {
+            if idx < row.values.len() {
+                row.values.remove(idx);
+            }
+        }
+        Ok(self.columns.remove(idx))
+    }
+
+    pub fn rename_column(&mut self, name: &str, new_name: &str) -> Result<(), DbError> {
+        let idx = self.column_index(name)?;
+        self.check_new_column_name(new_name, Some(idx))?;
+        let old_name = self.columns[idx].name.clone();
+        self.rename_check_references(&old_name, new_name)?;
+        self.columns[idx].name = new_name.to_string();
+        Ok(())
+    }
+
+    // 修改列类型，先把所有已有值转换为新类型，任何一个值无法转换时不做修改
+    pub fn alter_column_type(&mut self, name: &str, data_type: DataType) -> Result<(), DbError> {
+        let idx = self.column_index(name)?;
+        let old_type = &self.columns[idx].data_type;
+        let mut converted = Vec::with_capacity(self.rows.len());
+        for (i, row) in self.rows.iter().enumerate() {
+            let value = Value::from_stored(row.values.get(idx), old_type);
+            let value = value.cast(&data_type).map_err(|e| match e {
+                DbError::TypeMismatch(msg) => DbError::TypeMismatch(format!("第 {} 行: {}", i + 1, msg)),
+                e => e,
+            })?;
+            converted.push(value);
+        }
+        for (row, value) in self.rows.iter_mut().zip(converted) {
+            row.values.resize(self.columns.len(), Value::Null);
+            row.values[idx] = value;
+        }
+        self.columns[idx].data_type = data_type;
+        Ok(())
+    }
+
+    // 列名（大小写不敏感）不能与 except 之外的列重复
+    fn check_new_column_name(&self, name: &str, except: Option<usize>) -> Result<(), DbError> {
+        let duplicate = self
+            .columns
+            .iter()
+            .enumerate()
+            .any(|(i, c)| Some(i) != except && c.name.eq_ignore_ascii_case(name));
+        if duplicate {
+            return Err(DbError::InvalidQuery(format!("表 '{}' 中已有列 '{}'", self.name, name)));
+        }
+        Ok(())
+    }
+
+    // 按名称查找列下标，规则与表名相同
+    pub fn column_index(&self, name: &str) -> Result<usize, DbError> {
+        self.columns
+            .iter()
+            .position(|c| c.name == name)
+            .or_else(|| self.columns.iter().position(|c| c.name.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| DbError::ColumnNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn execute_returns_structured_results_without_a_gui() {
+        let mut db = Database::new("test");
+        let created = db.execute("CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(10))").unwrap();
+        assert!(!created.is_query());
+        let inserted = db.execute("INSERT INTO t VALUES (1, 'a'), (2, 'b')").unwrap();
+        assert_eq!(inserted.affected_rows, 2);
+
+        let result = db.execute("SELECT name, id FROM t WHERE id = 2").unwrap();
+        assert!(result.is_query());
+        let columns: Vec<(&str, Option<DataType>)> = result.columns.iter().map(|c| (c.name.as_str(), c.data_type.clone())).collect();
+        assert_eq!(columns, [("name", Some(DataType::String(10))), ("id", Some(DataType::Int))]);
+        assert_eq!(result.rows[0].values, [Value::Text("b".to_string()), Value::Int(2)]);
+
+        assert_eq!(db.execute("SELECT * FROM nope").unwrap_err(), DbError::TableNotFound("nope".to_string()));
+        assert!(matches!(db.execute("SELEC 1"), Err(DbError::Syntax { line: 1, column: 1, .. })));
+        assert_eq!(db.read_rows("t").unwrap().len(), 2);
     }
 }
@@ -0,0 +1,297 @@
+// SELECT 结果的 ORDER BY、OFFSET 和 LIMIT，结果行可以超出内存。
+// 没有 ORDER BY 时按到达的顺序直接输出，够 LIMIT 后通知调用者停止；
+// 有 ORDER BY 且 OFFSET + LIMIT 不超过一段的行数时，只在堆中保留排在前面的行；
+// 否则每攒够一段就在内存中排好，写入临时文件，最后多路归并各段，段数过多时先分组归并。
+// 排序是稳定的：排序键相同的行保持到达的顺序，与内存中的 sort_and_limit 一致
+use crate::ast::OrderByExpr;
+use crate::binary::{decode_row, encode_row};
+use crate::executor::compare_keys;
+use crate::part1::DbError;
+use crate::value::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+// 一次归并的段数上限
+const MERGE_WIDTH: usize = 16;
+
+// 接收排好序的结果行，返回 false 表示不再需要更多的行
+type Emit<'a> = dyn FnMut(Vec<Value>) -> Result<bool, DbError> + 'a;
+
+pub(crate) struct Sorter<'a> {
+    order_by: &'a [OrderByExpr],
+    offset: usize,
+    limit: usize,
+    run_rows: usize,
+    arrived: usize,
+    mode: Mode<'a>,
+}
+
+enum Mode<'a> {
+    Direct,
+    Top(BinaryHeap<Ranked<'a>>),
+    External { buffer: Vec<Ranked<'a>>, runs: Vec<Run> },
+}
+
+impl<'a> Sorter<'a> {
+    // run_rows 为内存中最多保留的行数
+    pub(crate) fn new(order_by: &'a [OrderByExpr], offset: Option<usize>, limit: Option<usize>, run_rows: usize) -> Self {
+        let offset = offset.unwrap_or(0);
+        let limit = limit.unwrap_or(usize::MAX);
+        let mode = if order_by.is_empty() {
+            Mode::Direct
+        } else if offset.saturating_add(limit) <= run_rows {
+            Mode::Top(BinaryHeap::new())
+        } else {
+            Mode::External { buffer: Vec::new(), runs: Vec::new() }
+        };
+        Sorter { order_by, offset, limit, run_rows, arrived: 0, mode }
+    }
+
+    // 加入一行及其排序键，返回是否还需要更多的行
+    pub(crate) fn push(&mut self, values: Vec<Value>, keys: Vec<Value>, emit: &mut Emit) -> Result<bool, DbError> {
+        let row = Ranked { keys, seq: self.arrived, values, order_by: self.order_by };
+        self.arrived += 1;
+        match &mut self.mode {
+            Mode::Direct => output(&mut self.offset, &mut self.limit, row.values, emit),
+            Mode::Top(heap) => {
+                heap.push(row);
+                if heap.len() > self.offset + self.limit {
+                    heap.pop();
+                }
+                Ok(true)
+            }
+            Mode::External { buffer, runs } => {
+                buffer.push(row);
+                if buffer.len() >= self.run_rows {
+                    runs.push(Run::write(sorted(std::mem::take(buffer)).into_iter().map(Ok))?);
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    // 按顺序输出留下的行
+    pub(crate) fn finish(mut self, emit: &mut Emit) -> Result<(), DbError> {
+        let (offset, limit) = (&mut self.offset, &mut self.limit);
+        match self.mode {
+            Mode::Direct => Ok(()),
+            Mode::Top(heap) => {
+                for row in heap.into_sorted_vec() {
+                    if !output(offset, limit, row.values, emit)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Mode::External { buffer, runs } if runs.is_empty() => {
+                for row in sorted(buffer) {
+                    if !output(offset, limit, row.values, emit)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Mode::External { buffer, mut runs } => {
+                if !buffer.is_empty() {
+                    runs.push(Run::write(sorted(buffer).into_iter().map(Ok))?);
+                }
+                while runs.len() > MERGE_WIDTH {
+                    let mut merged = Vec::new();
+                    let mut pending = runs.into_iter().peekable();
+                    while pending.peek().is_some() {
+                        let group: Vec<Run> = pending.by_ref().take(MERGE_WIDTH).collect();
+                        merged.push(Run::write(Merge::new(&group, self.order_by)?)?);
+                    }
+                    runs = merged;
+                }
+                for row in Merge::new(&runs, self.order_by)? {
+                    if !output(offset, limit, row?.values, emit)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// 跳过 OFFSET 行后输出，直到 LIMIT 行
+fn output(offset: &mut usize, limit: &mut usize, values: Vec<Value>, emit: &mut Emit) -> Result<bool, DbError> {
+    if *offset > 0 {
+        *offset -= 1;
+    } else if *limit > 0 {
+        *limit -= 1;
+        if !emit(values)? {
+            *limit = 0;
+        }
+    }
+    Ok(*limit > 0)
+}
+
+fn sorted(mut rows: Vec<Ranked>) -> Vec<Ranked> {
+    rows.sort();
+    rows
+}
+
+// 带排序键和到达顺序的结果行，按排序键比较，相同时先到的在前
+struct Ranked<'a> {
+    keys: Vec<Value>,
+    seq: usize,
+    values: Vec<Value>,
+    order_by: &'a [OrderByExpr],
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_keys(self.order_by, &self.keys, &other.keys).then(self.seq.cmp(&other.seq))
+    }
+}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+// 写入临时文件的一段有序的行，每行为长度（u32）和排序键、值依次编码的一行；丢弃时删除文件
+struct Run {
+    path: PathBuf,
+    rows: usize,
+}
+
+impl Run {
+    fn write<'a>(rows: impl Iterator<Item = Result<Ranked<'a>, DbError>>) -> Result<Run, DbError> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("sql_homework_sort_{}_{}", std::process::id(), NEXT.fetch_add(1, AtomicOrdering::Relaxed));
+        let mut run = Run { path: std::env::temp_dir().join(name), rows: 0 };
+        let mut file = BufWriter::new(File::create(&run.path).map_err(storage_error)?);
+        for row in rows {
+            let row = row?;
+            let data = encode_row(&[row.keys, row.values].concat());
+            let len = u32::try_from(data.len()).map_err(|_| DbError::Storage("排序的行过长".to_string()))?;
+            file.write_all(&len.to_le_bytes()).and_then(|_| file.write_all(&data)).map_err(storage_error)?;
+            run.rows += 1;
+        }
+        file.flush().map_err(storage_error)?;
+        Ok(run)
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// 多路归并若干段，排序键相同时靠前的段在前，seq 为段的序号
+struct Merge<'a> {
+    order_by: &'a [OrderByExpr],
+    readers: Vec<(BufReader<File>, usize)>, // 各段的文件和剩余的行数
+    heap: BinaryHeap<Reverse<Ranked<'a>>>,
+}
+
+impl<'a> Merge<'a> {
+    fn new(runs: &[Run], order_by: &'a [OrderByExpr]) -> Result<Self, DbError> {
+        let readers = runs
+            .iter()
+            .map(|run| Ok((BufReader::new(File::open(&run.path)?), run.rows)))
+            .collect::<io::Result<_>>()
+            .map_err(storage_error)?;
+        let mut merge = Merge { order_by, readers, heap: BinaryHeap::new() };
+        for i in 0..runs.len() {
+            merge.refill(i)?;
+        }
+        Ok(merge)
+    }
+
+    // 从第 i 段读入下一行
+    fn refill(&mut self, i: usize) -> Result<(), DbError> {
+        let (reader, remaining) = &mut self.readers[i];
+        if *remaining == 0 {
+            return Ok(());
+        }
+        *remaining -= 1;
+        let mut len = [0; 4];
+        reader.read_exact(&mut len).map_err(storage_error)?;
+        let mut data = vec![0; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut data).map_err(storage_error)?;
+        let mut values = decode_row(&data).map_err(storage_error)?;
+        let keys = values.drain(..self.order_by.len()).collect();
+        self.heap.push(Reverse(Ranked { keys, seq: i, values, order_by: self.order_by }));
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Merge<'a> {
+    type Item = Result<Ranked<'a>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(row) = self.heap.pop()?;
+        Some(self.refill(row.seq).map(|_| row))
+    }
+}
+
+fn storage_error(e: io::Error) -> DbError {
+    DbError::Storage(format!("排序的临时文件: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Expr, OrderByExpr};
+
+    // 按第一个键升序、第二个键降序排序各行，值为行的序号
+    fn sort(rows: &[(i64, i64)], offset: Option<usize>, limit: Option<usize>, run_rows: usize) -> Vec<i64> {
+        let order_by = [
+            OrderByExpr { expr: Expr::Column("a".to_string()), descending: false, nulls_first: None },
+            OrderByExpr { expr: Expr::Column("b".to_string()), descending: true, nulls_first: None },
+        ];
+        let mut sorter = Sorter::new(&order_by, offset, limit, run_rows);
+        let mut out = Vec::new();
+        let mut emit = |values: Vec<Value>| {
+            let Value::Int(i) = values[0] else { unreachable!() };
+            out.push(i);
+            Ok(true)
+        };
+        for (i, &(a, b)) in rows.iter().enumerate() {
+            sorter.push(vec![Value::Int(i as i64)], vec![Value::Int(a), Value::Int(b)], &mut emit).unwrap();
+        }
+        sorter.finish(&mut emit).unwrap();
+        out
+    }
+
+    #[test]
+    fn spilled_runs_merge_like_an_in_memory_stable_sort() {
+        let rows: Vec<(i64, i64)> = (0..1000).map(|i| (i * 37 % 11, i * 13 % 5)).collect();
+        let mut expected: Vec<usize> = (0..rows.len()).collect();
+        expected.sort_by(|&x, &y| rows[x].0.cmp(&rows[y].0).then(rows[y].1.cmp(&rows[x].1)));
+        let expected: Vec<i64> = expected.into_iter().map(|i| i as i64).collect();
+
+        // 1000 行在内存中排序、分为 334 段经两层归并、取前 20 行的堆，结果都相同
+        assert_eq!(sort(&rows, None, None, 5000), expected);
+        assert_eq!(sort(&rows, None, None, 3), expected);
+        assert_eq!(sort(&rows, Some(990), None, 3), &expected[990..]);
+        assert_eq!(sort(&rows, Some(15), Some(5), 3000), &expected[15..20]);
+        assert_eq!(sort(&rows, Some(15), Some(5), 3), &expected[15..20]);
+        assert_eq!(sort(&rows, None, Some(0), 3), Vec::<i64>::new());
+
+        // 段的临时文件在丢弃时删除
+        let run = Run::write(std::iter::empty()).unwrap();
+        let path = run.path.clone();
+        assert!(path.exists());
+        drop(run);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::binary;
+use crate::page_store::{self, PagedDatabase};
+use crate::pager::DEFAULT_POOL_PAGES;
+use crate::part1::Database;
+use crate::wal::{self, Wal};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// 上次保存被中断时，加载前的恢复结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recovery {
+    Clean,     // 没有未完成的保存
+    Completed, // 新数据已完整写入，补完了剩下的步骤
+    Discarded, // 新数据没有写完，已丢弃，保留上一次保存的内容
+}
+
+// 数据文件的格式，按扩展名区分：.rsdb 为二进制格式，其他为 JSON
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageFormat {
+    Json,   // 便于查看和手工编辑
+    Binary, // 紧凑，读写更快，见 binary 模块
+}
+
+impl StorageFormat {
+    pub const BINARY_EXTENSION: &'static str = "rsdb";
+
+    pub fn of(path: &Path) -> StorageFormat {
+        match path.extension() {
+            Some(ext) if ext.eq_ignore_ascii_case(Self::BINARY_EXTENSION) => StorageFormat::Binary,
+            _ => StorageFormat::Json,
+        }
+    }
+
+    pub fn encode(self, db: &Database) -> io::Result<Vec<u8>> {
+        match self {
+            StorageFormat::Json => serde_json::to_vec_pretty(db).map_err(io::Error::other),
+            StorageFormat::Binary => binary::encode(db),
+        }
+    }
+}
+
+// 按文件内容解码数据库：以二进制文件头开始的按二进制格式读取，否则按 JSON 读取，
+// 因此改了扩展名的文件也能打开
+pub fn decode(bytes: &[u8]) -> io::Result<Database> {
+    if binary::is_binary(bytes) {
+        return binary::decode(bytes);
+    }
+    serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn read_database(path: &Path) -> io::Result<Database> {
+    decode(&fs::read(path)?)
+}
+
+// 把数据文件转换为 to 的扩展名对应的格式，同时写入新文件的哈希。
+// 哈希只取决于数据库的内容，与格式无关，转换前后相同。分页文件（.rspg）没有哈希文件。
+// 源文件的日志中还有未写回的修改时，先重放并做检查点，转换的是最新的内容；旧格式的数据升级后写入
+pub fn convert(from: &Path, to: &Path) -> io::Result<()> {
+    let db = if page_store::is_paged(from) {
+        PagedDatabase::open(from, DEFAULT_POOL_PAGES)?.export()?
+    } else {
+        recover(from, &hash_path(from))?;
+        let mut db = read_database(from)?;
+        // 与界面中打开时的顺序相同：先升级，日志中的修改是在升级后的数据上记录的
+        db.migrate();
+        if wal::wal_path(from).exists() {
+            let (mut log, replayed) = Wal::open(from, &mut db)?;
+            if replayed > 0 {
+                log.checkpoint(&mut db)?;
+            }
+        }
+        db
+    };
+    if page_store::is_paged(to) {
+        PagedDatabase::import(&db, to, DEFAULT_POOL_PAGES).map(|_| ())
+    } else {
+        save(&db, to, &hash_path(to))?;
+        // 目标位置的旧日志属于之前的文件，清空以免打开时被重放到新文件上
+        if wal::wal_path(to).exists() {
+            Wal::create(to, &db)?;
+        }
+        Ok(())
+    }
+}
+
+// 数据库内容的 SHA-256，用于检测文件是否被修改。按 JSON 序列化计算，与数据文件的格式无关
+pub fn database_hash(db: &Database) -> String {
+    match serde_json::to_string(db) {
+        Ok(json) => format!("{:x}", Sha256::digest(json.as_bytes())),
+        Err(_) => String::new(),
+    }
+}
+
+// 保存数据库和哈希。两者都先写入临时文件并刷到磁盘，哈希的临时文件最后写入，
+// 然后依次改名覆盖原文件；任何一步中断时，recover 都能恢复到一致的状态
+// 数据按 data_path 的扩展名选择格式
+pub fn save(db: &Database, data_path: &Path, hash_path: &Path) -> io::Result<()> {
+    let data = StorageFormat::of(data_path).encode(db)?;
+    let (data_temp, hash_temp) = (temp_path(data_path), temp_path(hash_path));
+    write_synced(&data_temp, &data)?;
+    write_synced(&hash_temp, database_hash(db).as_bytes())?;
+    fs::rename(&data_temp, data_path)?;
+    fs::rename(&hash_temp, hash_path)?;
+    sync_dir(data_path);
+    Ok(())
+}
+
+// 单独保存一个文件：写入临时文件、刷到磁盘后改名覆盖
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+    write_synced(&temp, contents)?;
+    fs::rename(&temp, path)?;
+    sync_dir(path);
+    Ok(())
+}
+
+// 加载前处理上次中断的保存：哈希的临时文件与新数据一致时说明新数据已完整写入，补完改名；
+// 否则删除临时文件，原来的数据和哈希没有被改动过
+pub fn recover(data_path: &Path, hash_path: &Path) -> io::Result<Recovery> {
+    let (data_temp, hash_temp) = (temp_path(data_path), temp_path(hash_path));
+    if !data_temp.exists() && !hash_temp.exists() {
+        return Ok(Recovery::Clean);
+    }
+    // 数据的临时文件已经改名时，新数据就在原文件中
+    let new_data = if data_temp.exists() { &data_temp } else { data_path };
+    let committed = match (fs::read_to_string(&hash_temp), fs::read(new_data)) {
+        (Ok(hash), Ok(data)) => decode(&data).is_ok_and(|db| database_hash(&db) == hash),
+        _ => false,
+    };
+    if committed {
+        if data_temp.exists() {
+            fs::rename(&data_temp, data_path)?;
+        }
+        fs::rename(&hash_temp, hash_path)?;
+        sync_dir(data_path);
+        return Ok(Recovery::Completed);
+    }
+    for temp in [&data_temp, &hash_temp] {
+        if temp.exists() {
+            fs::remove_file(temp)?;
+        }
+    }
+    Ok(Recovery::Discarded)
+}
+
+// 数据文件对应的哈希文件，如 database.json 对应同目录下的 database_hash.txt；
+// 二进制文件加上格式区分，database.rsdb 对应 database_rsdb_hash.txt，两种格式的文件可以放在一起
+pub fn hash_path(data_path: &Path) -> PathBuf {
+    let stem = data_path.file_stem().unwrap_or_default().to_string_lossy();
+    match StorageFormat::of(data_path) {
+        StorageFormat::Json => data_path.with_file_name(format!("{}_hash.txt", stem)),
+        StorageFormat::Binary => data_path.with_file_name(format!("{}_{}_hash.txt", stem, StorageFormat::BINARY_EXTENSION)),
+    }
+}
+
+// 同目录下的临时文件，如 database.json.tmp
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+// 把改名操作刷到磁盘。Windows 不支持打开目录，此时忽略，改名本身仍是原子的
+pub(crate) fn sync_dir(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sql_homework_storage_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn database(rows: &str) -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (id INT)").unwrap();
+        db.execute(&format!("INSERT INTO t VALUES {}", rows)).unwrap();
+        db
+    }
+
+    #[test]
+    fn interrupted_save_is_completed_or_discarded() {
+        let dir = temp_dir("recover");
+        let (data, hash) = (dir.join("db.json"), dir.join("db_hash.txt"));
+        let (old, new) = (database("(1)"), database("(1), (2)"));
+        save(&old, &data, &hash).unwrap();
+        assert_eq!(recover(&data, &hash).unwrap(), Recovery::Clean);
+
+        // 两个临时文件都已写完，数据文件已改名：补完哈希的改名
+        fs::write(&data, StorageFormat::Json.encode(&new).unwrap()).unwrap();
+        fs::write(temp_path(&hash), database_hash(&new)).unwrap();
+        assert_eq!(recover(&data, &hash).unwrap(), Recovery::Completed);
+        assert_eq!(fs::read_to_string(&hash).unwrap(), database_hash(&new));
+
+        // 数据的临时文件只写了一半：丢弃，保留原来的文件
+        let bytes = StorageFormat::Json.encode(&old).unwrap();
+        fs::write(temp_path(&data), &bytes[..bytes.len() / 2]).unwrap();
+        fs::write(temp_path(&hash), database_hash(&old)).unwrap();
+        assert_eq!(recover(&data, &hash).unwrap(), Recovery::Discarded);
+        assert!(!temp_path(&data).exists() && !temp_path(&hash).exists());
+        assert_eq!(database_hash(&read_database(&data).unwrap()), database_hash(&new));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn databases_in_one_directory_keep_separate_files() {
+        let dir = temp_dir("named");
+        assert_eq!(hash_path(&dir.join("shop.json")), dir.join("shop_hash.txt"));
+        assert_eq!(hash_path(&dir.join("shop.rsdb")), dir.join("shop_rsdb_hash.txt"));
+
+        let (a, b) = (database("(1)"), database("(1), (2)"));
+        let (a_path, b_path) = (dir.join("a.json"), dir.join("b.rsdb"));
+        save(&a, &a_path, &hash_path(&a_path)).unwrap();
+        save(&b, &b_path, &hash_path(&b_path)).unwrap();
+        assert!(binary::is_binary(&fs::read(&b_path).unwrap()));
+        assert_eq!(read_database(&a_path).unwrap().read_rows("t").unwrap().len(), 1);
+        assert_eq!(read_database(&b_path).unwrap().read_rows("t").unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(hash_path(&b_path)).unwrap(), database_hash(&b));
+
+        // 转换后内容和哈希不变
+        let converted = dir.join("c.json");
+        convert(&b_path, &converted).unwrap();
+        assert_eq!(fs::read_to_string(hash_path(&converted)).unwrap(), database_hash(&b));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
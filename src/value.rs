@@ -0,0 +1,515 @@
+use crate::blob::{serialize_base64, to_hex};
+use crate::datetime::{serialize_utc, Date, Interval, Time, Timestamp, MICROS_PER_DAY};
+use crate::decimal::Decimal;
+use crate::json;
+use crate::part1::{DataType, DbError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::cmp::Ordering;
+use std::fmt;
+
+// 带类型的值，既用于表达式求值，也是行中存储的形式；在数据文件中保存为对应的 JSON 值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+    // 在数据文件中保存为字符串，读入时按列的类型还原，见 Table 的反序列化
+    #[serde(skip_deserializing)]
+    Decimal(Decimal),
+    // 日期时间类型同样以 ISO-8601 字符串保存
+    #[serde(skip_deserializing)]
+    Date(Date),
+    #[serde(skip_deserializing)]
+    Time(Time),
+    #[serde(skip_deserializing)]
+    Timestamp(Timestamp),
+    #[serde(skip_deserializing, serialize_with = "serialize_utc")]
+    TimestampTz(Timestamp),
+    #[serde(skip_deserializing)]
+    Interval(Interval),
+    // 二进制数据在数据文件中保存为 base64 字符串
+    #[serde(skip_deserializing, serialize_with = "serialize_base64")]
+    Blob(Vec<u8>),
+    // JSON 按原样保存在数据文件中；其中的标量读入时与其他类型相同，按列的类型还原
+    Json(JsonValue),
+}
+
+impl Value {
+    // 读取行中存储的值，缺少的值为 NULL；CHAR 的尾部空格只用于补足长度，不参与计算
+    pub fn from_stored(stored: Option<&Value>, data_type: &DataType) -> Value {
+        match (stored, data_type) {
+            (Some(Value::Text(text)), DataType::Char(_)) => Value::Text(text.trim_end_matches(' ').to_string()),
+            (Some(value), _) => value.clone(),
+            (None, _) => Value::Null,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Int(_) => "Int",
+            Value::Float(_) => "Float",
+            Value::Bool(_) => "Bool",
+            Value::Text(_) => "Text",
+            Value::Decimal(_) => "Decimal",
+            Value::Date(_) => "Date",
+            Value::Time(_) => "Time",
+            Value::Timestamp(_) => "Timestamp",
+            Value::TimestampTz(_) => "TimestampTz",
+            Value::Interval(_) => "Interval",
+            Value::Blob(_) => "Blob",
+            Value::Json(_) => "Json",
+        }
+    }
+
+    // 比较两个值，任一方为 NULL 时结果未知；文本与数字、布尔比较时先尝试转换
+    pub fn compare(&self, other: &Value) -> Result<Option<Ordering>, DbError> {
+        let ordering = match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => return Ok(None),
+            (Value::Json(_), _) | (_, Value::Json(_)) => return compare_json(self, other),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            // 文本按 UTF-8 编码与二进制数据比较，与写入 BLOB 列时的转换一致
+            (Value::Blob(a), Value::Text(b)) => a.as_slice().cmp(b.as_bytes()),
+            (Value::Text(a), Value::Blob(b)) => a.as_bytes().cmp(b.as_slice()),
+            (Value::Bool(a), Value::Text(b)) => match parse_bool(b) {
+                Some(b) => a.cmp(&b),
+                None => return Err(mismatch(self, other)),
+            },
+            (Value::Text(a), Value::Bool(b)) => match parse_bool(a) {
+                Some(a) => a.cmp(b),
+                None => return Err(mismatch(self, other)),
+            },
+            (Value::Text(text), number) | (number, Value::Text(text)) if number.is_number() => {
+                let Some(parsed) = parse_number(text) else {
+                    return Err(mismatch(self, other));
+                };
+                let (a, b) = if self.is_number() { (self, &parsed) } else { (&parsed, other) };
+                match compare_numbers(a, b) {
+                    Some(ordering) => ordering,
+                    None => return Err(mismatch(self, other)),
+                }
+            }
+            (a, b) if a.is_number() && b.is_number() => match compare_numbers(a, b) {
+                Some(ordering) => ordering,
+                None => return Err(mismatch(self, other)),
+            },
+            (Value::Text(text), temporal) | (temporal, Value::Text(text)) if temporal.is_temporal() => {
+                let data_type = temporal.temporal_type().unwrap_or(DataType::Interval);
+                let parsed = Value::Text(text.clone())
+                    .convert(&data_type)
+                    .map_err(|_| mismatch(self, other))?;
+                let (a, b) = if self.is_temporal() { (self, &parsed) } else { (&parsed, other) };
+                match compare_temporal(a, b) {
+                    Some(ordering) => ordering,
+                    None => return Err(mismatch(self, other)),
+                }
+            }
+            (a, b) => match compare_temporal(a, b) {
+                Some(ordering) => ordering,
+                None => return Err(mismatch(self, other)),
+            },
+        };
+        Ok(Some(ordering))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::Float(_) | Value::Decimal(_))
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        self.temporal_type().is_some()
+    }
+
+    // 日期时间值对应的列类型
+    pub fn temporal_type(&self) -> Option<DataType> {
+        match self {
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::TimestampTz(_) => Some(DataType::TimestampTz),
+            Value::Interval(_) => Some(DataType::Interval),
+            _ => None,
+        }
+    }
+
+    // 转换为列类型对应的值，超过 CHAR(n)/VARCHAR(n) 的长度时报错，CHAR 以空格补足。
+    // 用于修改列类型和计算默认值
+    pub fn cast(self, data_type: &DataType) -> Result<Value, DbError> {
+        match self.convert(data_type)? {
+            Value::Text(text) => match fit_length(&text, data_type) {
+                (_, true) => Err(DbError::TypeMismatch(format!("'{}' 超过了 {} 的长度限制", text, data_type))),
+                (fitted, false) => Ok(Value::Text(fitted)),
+            },
+            value => Ok(value),
+        }
+    }
+
+    // 转换为列类型对应的值，不检查文本的长度；布尔列也接受旧数据中的 0 和 1
+    pub fn convert(self, data_type: &DataType) -> Result<Value, DbError> {
+        let fail = |value: &Value| {
+            Err(DbError::TypeMismatch(format!(
+                "无法将 {} '{}' 转换为 {}",
+                value.type_name(),
+                value,
+                data_type
+            )))
+        };
+        match (data_type, self) {
+            (_, Value::Null) => Ok(Value::Null),
+            (DataType::Int, Value::Int(i)) => Ok(Value::Int(i)),
+            (DataType::Int, Value::Float(x)) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => Ok(Value::Int(x as i64)),
+            (DataType::Int, Value::Decimal(d)) => match d.to_i64() {
+                Some(i) => Ok(Value::Int(i)),
+                None => fail(&Value::Decimal(d)),
+            },
+            (DataType::Int, Value::Text(s)) => match s.trim().parse() {
+                Ok(i) => Ok(Value::Int(i)),
+                Err(_) => fail(&Value::Text(s)),
+            },
+            (DataType::Float | DataType::Double, value) => {
+                let x = match &value {
+                    Value::Int(i) => *i as f64,
+                    Value::Float(x) => *x,
+                    Value::Decimal(d) => d.to_f64(),
+                    Value::Text(s) => match s.trim().parse::<f64>() {
+                        Ok(x) => x,
+                        Err(_) => return fail(&value),
+                    },
+                    _ => return fail(&value),
+                };
+                if !x.is_finite() {
+                    return Err(DbError::TypeMismatch(format!("'{}' 超出了 {} 的范围", value, data_type)));
+                }
+                if *data_type == DataType::Double {
+                    return Ok(Value::Float(x));
+                }
+                // FLOAT 为单精度，按单精度的最短十进制表示保存
+                let single = x as f32;
+                if !single.is_finite() {
+                    return Err(DbError::TypeMismatch(format!("'{}' 超出了 {} 的范围", value, data_type)));
+                }
+                Ok(Value::Float(single.to_string().parse().unwrap_or(x)))
+            }
+            (DataType::Decimal(precision, scale), value) => {
+                let decimal = match &value {
+                    Value::Int(i) => Some(Decimal::from_i64(*i)),
+                    Value::Float(x) => Decimal::from_f64(*x),
+                    Value::Decimal(d) => Some(*d),
+                    Value::Text(s) => s.trim().parse().ok(),
+                    _ => return fail(&value),
+                };
+                let Some(decimal) = decimal else {
+                    return fail(&value);
+                };
+                // 小数位数多于 scale 时四舍五入，整数部分的位数不能超过 precision - scale
+                match decimal.round_to(*scale) {
+                    Some(rounded) if rounded.precision() <= *precision => Ok(Value::Decimal(rounded)),
+                    _ => Err(DbError::TypeMismatch(format!("'{}' 超出了 {} 的范围", value, data_type))),
+                }
+            }
+            (DataType::Bool, Value::Bool(b)) => Ok(Value::Bool(b)),
+            (DataType::Bool, Value::Int(i)) if i == 0 || i == 1 => Ok(Value::Bool(i == 1)),
+            (DataType::Bool, Value::Text(s)) => match (parse_bool(&s), s.trim()) {
+                (Some(b), _) => Ok(Value::Bool(b)),
+                (None, "0" | "1") => Ok(Value::Bool(s.trim() == "1")),
+                (None, _) => fail(&Value::Text(s)),
+            },
+            (DataType::Date, value) => match value {
+                Value::Date(d) => Ok(Value::Date(d)),
+                Value::Timestamp(ts) | Value::TimestampTz(ts) => Ok(Value::Date(ts.date())),
+                Value::Text(s) => match s.parse().ok().or_else(|| Some(Timestamp::parse(&s, false)?.date())) {
+                    Some(d) => Ok(Value::Date(d)),
+                    None => fail(&Value::Text(s)),
+                },
+                value => fail(&value),
+            },
+            (DataType::Time, value) => match value {
+                Value::Time(t) => Ok(Value::Time(t)),
+                Value::Timestamp(ts) | Value::TimestampTz(ts) => Ok(Value::Time(ts.time())),
+                Value::Text(s) => match s.parse().ok().or_else(|| Some(Timestamp::parse(&s, false)?.time())) {
+                    Some(t) => Ok(Value::Time(t)),
+                    None => fail(&Value::Text(s)),
+                },
+                value => fail(&value),
+            },
+            // 不带时区与带时区的时间戳互相转换时，不带时区的时间视为 UTC
+            (DataType::Timestamp | DataType::TimestampTz, value) => {
+                let with_time_zone = *data_type == DataType::TimestampTz;
+                let timestamp = match &value {
+                    Value::Timestamp(ts) | Value::TimestampTz(ts) => Some(*ts),
+                    Value::Date(d) => Some(Timestamp::new(*d, Time::from_micros(0))),
+                    Value::Text(s) => Timestamp::parse(s, with_time_zone),
+                    _ => None,
+                };
+                match timestamp {
+                    Some(ts) if with_time_zone => Ok(Value::TimestampTz(ts)),
+                    Some(ts) => Ok(Value::Timestamp(ts)),
+                    None => fail(&value),
+                }
+            }
+            (DataType::Interval, value) => match value {
+                Value::Interval(i) => Ok(Value::Interval(i)),
+                Value::Text(s) => match s.parse::<Interval>() {
+                    Ok(i) => Ok(Value::Interval(i)),
+                    Err(_) => fail(&Value::Text(s)),
+                },
+                value => fail(&value),
+            },
+            // 文本按 UTF-8 编码保存，超过列的大小限制时报错
+            (DataType::Blob(max_size), value) => {
+                let bytes = match value {
+                    Value::Blob(bytes) => bytes,
+                    Value::Text(s) => s.into_bytes(),
+                    value => return fail(&value),
+                };
+                if bytes.len() > *max_size as usize {
+                    return Err(DbError::TypeMismatch(format!(
+                        "{} 字节的数据超过了 {} 的大小限制",
+                        bytes.len(),
+                        data_type
+                    )));
+                }
+                Ok(Value::Blob(bytes))
+            }
+            // 文本按 JSON 解析，其他标量转换为对应的 JSON 值；顶层的 JSON null 按 SQL NULL 保存
+            (DataType::Json, value) => {
+                let json = match value {
+                    Value::Json(j) => j,
+                    Value::Text(s) => json::parse(&s)?,
+                    Value::Int(i) => JsonValue::from(i),
+                    Value::Bool(b) => JsonValue::from(b),
+                    Value::Float(x) => match serde_json::Number::from_f64(x) {
+                        Some(n) => JsonValue::Number(n),
+                        None => return fail(&value),
+                    },
+                    Value::Decimal(d) => match serde_json::from_str(&d.to_string()) {
+                        Ok(j) => j,
+                        Err(_) => return fail(&value),
+                    },
+                    value => return fail(&value),
+                };
+                match json {
+                    JsonValue::Null => Ok(Value::Null),
+                    json => Ok(Value::Json(json)),
+                }
+            }
+            (DataType::Char(_) | DataType::String(_), Value::Text(s)) => Ok(Value::Text(s)),
+            (DataType::Char(_) | DataType::String(_), value) => Ok(Value::Text(value.to_string())),
+            (_, value) => fail(&value),
+        }
+    }
+
+    // 排序用的全序：能比较时按类型语义比较，否则先按类型名再按文本
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        match self.compare(other) {
+            Ok(Some(ordering)) => ordering,
+            _ => (self.type_name(), self.to_string()).cmp(&(other.type_name(), other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Date(d) => write!(f, "{}", d),
+            Value::Time(t) => write!(f, "{}", t),
+            Value::Timestamp(ts) => write!(f, "{}", ts),
+            Value::TimestampTz(ts) => write!(f, "{}Z", ts),
+            Value::Interval(i) => write!(f, "{}", i),
+            Value::Blob(bytes) => write!(f, "X'{}'", to_hex(bytes)),
+            Value::Json(j) => write!(f, "{}", j),
+        }
+    }
+}
+
+// 含 JSON 的比较：有数组或对象时与 JSON 或 JSON 文本比较，相等以外按文本排序；
+// 否则 JSON 标量按对应的 SQL 值比较，如 body->'age' > 18、body->'name' = 'Alice'
+fn compare_json(a: &Value, b: &Value) -> Result<Option<Ordering>, DbError> {
+    let document = |v: &Value| match v {
+        Value::Json(j) => Some(j.clone()),
+        // 不是 JSON 的文本视为 JSON 字符串
+        Value::Text(t) => Some(json::parse(t).unwrap_or_else(|_| JsonValue::String(t.clone()))),
+        v => match v.clone().convert(&DataType::Json) {
+            Ok(Value::Json(j)) => Some(j),
+            _ => None,
+        },
+    };
+    let compare_documents = |x: Option<JsonValue>, y: Option<JsonValue>| match (x, y) {
+        (Some(x), Some(y)) if x == y => Ok(Some(Ordering::Equal)),
+        (Some(x), Some(y)) => Ok(Some(x.to_string().cmp(&y.to_string()))),
+        _ => Err(mismatch(a, b)),
+    };
+    let is_container = |j: &JsonValue| j.is_array() || j.is_object();
+    let (x, y) = (document(a), document(b));
+    if x.as_ref().is_some_and(is_container) || y.as_ref().is_some_and(is_container) {
+        return compare_documents(x, y);
+    }
+    let scalar = |v: &Value| match v {
+        Value::Json(j) => json::to_sql(j),
+        v => v.clone(),
+    };
+    // 同一 JSON 列中可以有不同类型的标量，无法按 SQL 值比较时同样按 JSON 比较
+    scalar(a).compare(&scalar(b)).or_else(|_| compare_documents(x, y))
+}
+
+// 比较两个数：有浮点数时按浮点数比较，否则按精确的十进制比较；NaN 无法比较
+fn compare_numbers(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Float(_), _) | (_, Value::Float(_)) => to_f64(a)?.partial_cmp(&to_f64(b)?),
+        _ => Some(to_decimal(a)?.cmp(&to_decimal(b)?)),
+    }
+}
+
+// 比较两个日期时间值：日期与时间戳之间按零点比较，不带时区的时间戳视为 UTC
+fn compare_temporal(a: &Value, b: &Value) -> Option<Ordering> {
+    let instant = |value: &Value| match value {
+        Value::Date(d) => Some(d.days() * MICROS_PER_DAY),
+        Value::Timestamp(ts) | Value::TimestampTz(ts) => Some(ts.micros()),
+        _ => None,
+    };
+    match (a, b) {
+        (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+        (Value::Interval(a), Value::Interval(b)) => Some(a.total_micros().cmp(&b.total_micros())),
+        _ => Some(instant(a)?.cmp(&instant(b)?)),
+    }
+}
+
+pub fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(i) => Some(*i as f64),
+        Value::Float(x) => Some(*x),
+        Value::Decimal(d) => Some(d.to_f64()),
+        _ => None,
+    }
+}
+
+pub fn to_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Int(i) => Some(Decimal::from_i64(*i)),
+        Value::Decimal(d) => Some(*d),
+        _ => None,
+    }
+}
+
+// 把文本解析为数字：整数为 Int，不带指数的小数为 Decimal，其余为 Float
+pub fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(i) = text.parse() {
+        return Some(Value::Int(i));
+    }
+    if let Ok(d) = text.parse() {
+        return Some(Value::Decimal(d));
+    }
+    text.parse().ok().map(Value::Float)
+}
+
+fn mismatch(left: &Value, right: &Value) -> DbError {
+    DbError::TypeMismatch(format!(
+        "无法比较 {} '{}' 与 {} '{}'",
+        left.type_name(),
+        left,
+        right.type_name(),
+        right
+    ))
+}
+
+// 把文本调整为 CHAR(n)/VARCHAR(n) 的长度（按 Unicode 字符计）：截掉超出的部分，CHAR 再以空格补足 n 个字符。
+// 第二项表示截掉的部分中是否有空格以外的字符，只截掉空格时不算超长
+pub fn fit_length(text: &str, data_type: &DataType) -> (String, bool) {
+    let (len, pad) = match data_type {
+        DataType::Char(len) => (*len as usize, true),
+        DataType::String(len) => (*len as usize, false),
+        _ => return (text.to_string(), false),
+    };
+    let mut fitted: String = text.chars().take(len).collect();
+    let truncated = text.chars().skip(len).any(|c| c != ' ');
+    if pad {
+        let count = fitted.chars().count();
+        fitted.extend(std::iter::repeat_n(' ', len - count));
+    }
+    (fitted, truncated)
+}
+
+// 解析布尔文本，大小写不敏感
+pub fn parse_bool(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+// 用于去重和分组的键，区分 NULL 与文本 'NULL'。数按值取键：相等的 INT、浮点数和 Decimal
+// （包括小数位数不同的 Decimal）键相同，浮点数取能还原它的最短十进制写法，-0 与 0 相同
+pub fn values_key(values: &[Value]) -> Vec<(&'static str, String)> {
+    values
+        .iter()
+        .map(|v| match v {
+            Value::Int(i) => ("Number", i.to_string()),
+            Value::Decimal(d) => ("Number", d.normalized().to_string()),
+            Value::Float(f) => ("Number", if *f == 0.0 { 0.0 } else { *f }.to_string()),
+            v => (v.type_name(), v.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1::{Constraint, Database, LengthPolicy};
+
+    #[test]
+    fn text_lengths_count_characters() {
+        assert_eq!(fit_length("ab", &DataType::Char(3)), ("ab ".to_string(), false));
+        assert_eq!(fit_length("好好好", &DataType::String(2)), ("好好".to_string(), true));
+        assert_eq!(fit_length("ab   ", &DataType::String(2)), ("ab".to_string(), false));
+
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE s (c CHAR(3), v VARCHAR(2))").unwrap();
+        db.execute("INSERT INTO s VALUES ('ab', '好好')").unwrap();
+        let err = db.execute("INSERT INTO s VALUES ('a', '好好好')").unwrap_err();
+        assert!(matches!(err, DbError::ConstraintViolation { constraint: Constraint::Length(DataType::String(2)), .. }));
+        // CHAR 比较时忽略补足的空格
+        assert_eq!(db.execute("SELECT c FROM s WHERE c = 'ab'").unwrap().rows.len(), 1);
+
+        db.length_policy = LengthPolicy::Truncate;
+        let result = db.execute("INSERT INTO s VALUES ('abcd', '好好好')").unwrap();
+        assert!(result.message.contains("截断"), "{}", result.message);
+        assert_eq!(db.read_rows("s").unwrap()[1].values[1], Value::Text("好好".to_string()));
+    }
+
+    #[test]
+    fn values_are_stored_with_column_types() {
+        assert_eq!(Value::Text("12".to_string()).convert(&DataType::Int), Ok(Value::Int(12)));
+        assert_eq!(Value::Text("TRUE".to_string()).convert(&DataType::Bool), Ok(Value::Bool(true)));
+        assert!(matches!(Value::Text("x".to_string()).convert(&DataType::Int), Err(DbError::TypeMismatch(_))));
+        // INT 按数值比较，不按文本
+        assert_eq!(Value::Int(9).compare(&Value::Int(10)), Ok(Some(Ordering::Less)));
+
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE n (i INT, f DOUBLE, b BOOL, t VARCHAR(5))").unwrap();
+        db.execute("INSERT INTO n VALUES ('10', 1, 'false', 'NULL'), (9, 2.5, true, NULL)").unwrap();
+        // 数据文件中保存为对应的 JSON 值，读回后类型不变
+        let json = serde_json::to_string(&db).unwrap();
+        assert!(json.contains(r#"[10,1.0,false,"NULL"]"#), "{}", json);
+        let db: Database = serde_json::from_str(&json).unwrap();
+        assert_eq!(db.read_rows("n").unwrap()[1].values, [Value::Int(9), Value::Float(2.5), Value::Bool(true), Value::Null]);
+    }
+}
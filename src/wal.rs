@@ -0,0 +1,239 @@
+use crate::datetime::Timestamp;
+use crate::part1::{Database, DbError, LengthPolicy, StoredRow, Table};
+use crate::storage::{self, StorageFormat};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// 日志中的记录数达到此值时做一次检查点
+pub const CHECKPOINT_INTERVAL: usize = 100;
+
+// 日志中记录的逻辑修改，重放时按原来的顺序再执行一遍
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Change {
+    // 修改数据的 SQL 语句与执行时的当前时间（UTC 微秒），重放时 NOW() 等取值不变
+    Sql { sql: String, now: i64 },
+    // 界面中创建的表
+    CreateTable(Table),
+    // 界面中插入的行，按转换为列的类型之后的值记录，与数据文件中的行格式相同
+    InsertRow { table: String, row: StoredRow },
+    LengthPolicy(LengthPolicy),
+}
+
+impl Change {
+    pub fn sql(sql: &str, now: Timestamp) -> Change {
+        Change::Sql { sql: sql.to_string(), now: now.micros() }
+    }
+
+    fn apply(self, db: &mut Database) -> Result<(), DbError> {
+        match self {
+            Change::Sql { sql, now } => {
+                let now = Timestamp::from_micros(now)
+                    .ok_or_else(|| DbError::InvalidQuery(format!("无效的时间 {}", now)))?;
+                db.execute_at(&sql, now).map(|_| ())
+            }
+            Change::CreateTable(table) => db.create_table(table),
+            Change::InsertRow { table, row } => {
+                let row = db.table(&table)?.decode_stored(row);
+                db.insert_row(&table, row).map(|_| ())
+            }
+            Change::LengthPolicy(policy) => {
+                db.length_policy = policy;
+                Ok(())
+            }
+        }
+    }
+}
+
+// 日志中的一行。序号连续递增，检查点时写入 Database::wal_sequence，
+// 重放时跳过已经包含在数据文件中的记录
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    change: Change,
+}
+
+// 数据文件的预写日志，如 database.json 对应同目录下的 database.wal。
+// 每条记录是一行 JSON，追加后立即刷到磁盘；检查点把数据库整体写回数据文件后清空日志
+pub struct Wal {
+    data_path: PathBuf,
+    file: File,
+    next_seq: u64,
+    pending: usize, // 上次检查点之后写入的记录数
+}
+
+impl Wal {
+    // 打开 data_path 对应的日志，把比 db 新的记录依次重放到 db 上，返回日志和重放的记录数。
+    // 最后一行没有换行符说明写入时崩溃，该修改没有生效，直接丢弃
+    pub fn open(data_path: &Path, db: &mut Database) -> io::Result<(Wal, usize)> {
+        let path = wal_path(data_path);
+        let contents = if path.exists() { fs::read(&path)? } else { Vec::new() };
+        let complete = contents.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let corrupted = |e: &dyn std::fmt::Display| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("日志 {} 已损坏: {}", path.display(), e))
+        };
+        let text = std::str::from_utf8(&contents[..complete]).map_err(|e| corrupted(&e))?;
+
+        let mut replayed = 0;
+        for line in text.lines() {
+            let entry: Entry = serde_json::from_str(line).map_err(|e| corrupted(&e))?;
+            if entry.seq <= db.wal_sequence {
+                continue;
+            }
+            let seq = entry.seq;
+            entry.change.apply(db).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("重放日志记录 {} 失败: {}", seq, e))
+            })?;
+            db.wal_sequence = seq;
+            replayed += 1;
+        }
+
+        // 丢弃不完整的最后一行，之后的记录从新的一行开始
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if complete < contents.len() {
+            file.set_len(complete as u64)?;
+            file.sync_all()?;
+        }
+        let wal = Wal {
+            data_path: data_path.to_path_buf(),
+            file,
+            next_seq: db.wal_sequence + 1,
+            pending: replayed,
+        };
+        Ok((wal, replayed))
+    }
+
+    // 为新建或另存为的数据文件创建空日志，覆盖同名的旧日志
+    pub fn create(data_path: &Path, db: &Database) -> io::Result<Wal> {
+        let file = OpenOptions::new().create(true).append(true).open(wal_path(data_path))?;
+        file.set_len(0)?;
+        file.sync_all()?;
+        Ok(Wal {
+            data_path: data_path.to_path_buf(),
+            file,
+            next_seq: db.wal_sequence + 1,
+            pending: 0,
+        })
+    }
+
+    // 追加一条已在内存中生效的修改，返回前已刷到磁盘
+    pub fn append(&mut self, change: Change) -> io::Result<()> {
+        let entry = Entry { seq: self.next_seq, change };
+        let mut line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.next_seq += 1;
+        self.pending += 1;
+        Ok(())
+    }
+
+    // 是否应该做检查点
+    pub fn is_full(&self) -> bool {
+        self.pending >= CHECKPOINT_INTERVAL
+    }
+
+    // 把数据库连同已写入的日志序号原子地保存到数据文件，然后清空日志。
+    // 清空前崩溃时，日志中的记录序号都不大于 wal_sequence，重放时会被跳过
+    pub fn checkpoint(&mut self, db: &mut Database) -> io::Result<()> {
+        db.wal_sequence = self.next_seq - 1;
+        storage::save(db, &self.data_path, &storage::hash_path(&self.data_path))?;
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+// 数据文件对应的日志文件，如 database.json 对应 database.wal，database.rsdb 对应 database.rsdb.wal
+pub fn wal_path(data_path: &Path) -> PathBuf {
+    match StorageFormat::of(data_path) {
+        StorageFormat::Json => data_path.with_extension("wal"),
+        StorageFormat::Binary => data_path.with_extension(format!("{}.wal", StorageFormat::BINARY_EXTENSION)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试使用单独的临时目录
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sql_homework_wal_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn line(seq: u64, sql: &str) -> String {
+        let entry = Entry { seq, change: Change::Sql { sql: sql.to_string(), now: 0 } };
+        serde_json::to_string(&entry).unwrap() + "\n"
+    }
+
+    fn ids(db: &mut Database) -> Vec<String> {
+        let result = db.execute("SELECT id FROM t ORDER BY id").unwrap();
+        result.rows.iter().map(|row| row.values[0].to_string()).collect()
+    }
+
+    fn database() -> Database {
+        let mut db = Database::new("test");
+        db.execute("CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        db
+    }
+
+    #[test]
+    fn replay_skips_entries_already_in_data_file() {
+        let dir = temp_dir("skip");
+        let data_path = dir.join("db.json");
+        let mut db = database();
+        db.execute("INSERT INTO t VALUES (1), (2)").unwrap();
+        db.wal_sequence = 2;
+        let log = line(1, "INSERT INTO t VALUES (1)") + &line(2, "INSERT INTO t VALUES (2)") + &line(3, "INSERT INTO t VALUES (3)");
+        fs::write(wal_path(&data_path), log).unwrap();
+
+        let (mut wal, replayed) = Wal::open(&data_path, &mut db).unwrap();
+        assert_eq!(replayed, 1);
+        assert_eq!(db.wal_sequence, 3);
+        assert_eq!(ids(&mut db), ["1", "2", "3"]);
+
+        // 之后的记录接着已有的序号
+        wal.append(Change::sql("INSERT INTO t VALUES (4)", Timestamp::from_micros(0).unwrap())).unwrap();
+        let written = fs::read_to_string(wal_path(&data_path)).unwrap();
+        assert!(written.lines().last().unwrap().starts_with("{\"seq\":4,"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn torn_last_line_is_discarded_and_truncated() {
+        let dir = temp_dir("torn");
+        let data_path = dir.join("db.json");
+        let complete = line(1, "INSERT INTO t VALUES (1)");
+        let torn = &line(2, "INSERT INTO t VALUES (2)")[..20];
+        fs::write(wal_path(&data_path), complete.clone() + torn).unwrap();
+
+        let mut db = database();
+        let (mut wal, replayed) = Wal::open(&data_path, &mut db).unwrap();
+        assert_eq!(replayed, 1);
+        assert_eq!(ids(&mut db), ["1"]);
+        assert_eq!(fs::read_to_string(wal_path(&data_path)).unwrap(), complete);
+
+        // 截断后追加的记录从新的一行开始，再次打开时能完整重放
+        wal.append(Change::sql("INSERT INTO t VALUES (2)", Timestamp::from_micros(0).unwrap())).unwrap();
+        let mut reopened = database();
+        let (_, replayed) = Wal::open(&data_path, &mut reopened).unwrap();
+        assert_eq!(replayed, 2);
+        assert_eq!(ids(&mut reopened), ["1", "2"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_complete_line_is_an_error() {
+        let dir = temp_dir("corrupt");
+        let data_path = dir.join("db.json");
+        fs::write(wal_path(&data_path), "not json\n").unwrap();
+        let err = Wal::open(&data_path, &mut database()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}